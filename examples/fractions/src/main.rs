@@ -1,7 +1,8 @@
 use std::io;
 use std::process::exit;
 
-use funki_templates::external_operators::{CustomBuiltIn, CustomType};
+use funki_templates::external_operators::{CustomType, TypedBuiltIn};
+use funki_templates::types::Type;
 use funki_templates::*;
 use gcd::Gcd;
 
@@ -174,39 +175,30 @@ impl CustomType for Fraction {
 fn main() {
   let mut lang = Language::<Fraction>::new();
 
-  lang.add_custom_function(
+  lang.add_typed_function(
     "new_frac".to_string(),
-    CustomBuiltIn {
-      function: |f| {
-        if let ReturnVal::Tuple(v) = f {
-          if v.len() == 2 {
-            if let (ReturnVal::Int(n), ReturnVal::Int(d)) = (v.get(0).unwrap(), v.get(1).unwrap()) {
-              if d >= &0 {
-                Ok(Argument::Custom(
-                  Fraction {
-                    num: *n as isize,
-                    denom: *d as usize,
-                  }
-                  .simplify(),
-                ))
-              } else {
-                Ok(Argument::Custom(
-                  Fraction {
-                    num: (-*n) as isize,
-                    denom: (-*d) as usize,
-                  }
-                  .simplify(),
-                ))
-              }
-            } else {
-              Err(Box::new("Wrong arg type."))
-            }
-          } else {
-            Err(Box::new("Wrong arg type."))
+    TypedBuiltIn {
+      params: vec![
+        ("num".to_string(), Type::Int),
+        ("denom".to_string(), Type::Int),
+      ],
+      result: Type::Custom,
+      function: |args| match args.as_slice() {
+        [ReturnVal::Int(n), ReturnVal::Int(d)] if *d >= 0 => Ok(Argument::Custom(
+          Fraction {
+            num: *n as isize,
+            denom: *d as usize,
           }
-        } else {
-          Err(Box::new("Wrong arg type."))
-        }
+          .simplify(),
+        )),
+        [ReturnVal::Int(n), ReturnVal::Int(d)] => Ok(Argument::Custom(
+          Fraction {
+            num: (-*n) as isize,
+            denom: (-*d) as usize,
+          }
+          .simplify(),
+        )),
+        _ => unreachable!("arguments are checked against the parameters"),
       },
     },
   );
@@ -0,0 +1,74 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::error::CliError;
+
+// The command line arguments given to a subcommand
+#[derive(Debug, Default)]
+pub struct Args {
+  positional: Vec<String>,
+  options: HashMap<String, String>,
+  flags: HashSet<String>,
+}
+
+impl Args {
+  // Splits the arguments into positional arguments, `--name value` options and `--name` flags
+  // `takes_value` lists the options that are followed by a value, `--name=value` also works
+  pub fn parse<I: IntoIterator<Item = String>>(
+    args: I,
+    takes_value: &[&str],
+    flags: &[&str],
+  ) -> Result<Self, CliError> {
+    let mut res = Args::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+      let name = match arg.strip_prefix("--") {
+        Some(n) => n,
+        None => {
+          res.positional.push(arg);
+          continue;
+        }
+      };
+      if let Some((name, value)) = name.split_once('=') {
+        if !takes_value.contains(&name) {
+          return Err(CliError::usage(format!("Unknown option `--{}`.", name)));
+        }
+        res.options.insert(name.to_string(), value.to_string());
+      } else if takes_value.contains(&name) {
+        let value = args
+          .next()
+          .ok_or_else(|| CliError::usage(format!("Option `--{}` needs a value.", name)))?;
+        res.options.insert(name.to_string(), value);
+      } else if flags.contains(&name) {
+        res.flags.insert(name.to_string());
+      } else {
+        return Err(CliError::usage(format!("Unknown option `--{}`.", name)));
+      }
+    }
+    Ok(res)
+  }
+
+  // The positional argument at `index`, which is described by `name` in the error if it is missing
+  pub fn positional(&self, index: usize, name: &str) -> Result<&str, CliError> {
+    self
+      .positional
+      .get(index)
+      .map(|s| s.as_str())
+      .ok_or_else(|| CliError::usage(format!("Missing argument <{}>.", name)))
+  }
+
+  // All the positional arguments, which must be at least one
+  pub fn all_positional(&self, name: &str) -> Result<&[String], CliError> {
+    self.positional(0, name)?;
+    Ok(&self.positional)
+  }
+
+  // The value of an option, if it was given
+  pub fn option(&self, name: &str) -> Option<&str> {
+    self.options.get(name).map(|s| s.as_str())
+  }
+
+  // Whether a flag was given
+  pub fn flag(&self, name: &str) -> bool {
+    self.flags.contains(name)
+  }
+}
@@ -0,0 +1,20 @@
+use funki_lang::{BlankCustom, Language};
+
+use crate::args::Args;
+use crate::error::CliError;
+use crate::read_source;
+
+// Parses a script into its tree as indented JSON, with the span of every node
+pub fn ast_json(code: String) -> Result<String, CliError> {
+  let script = Language::<BlankCustom>::new().parse(code)?;
+  serde_json::to_string_pretty(&script.syntax())
+    .map_err(|e| CliError::failure(format!("Failed to convert the tree to JSON: {}.", e)))
+}
+
+// `funki ast <file>`: prints the tree a script is parsed into, to debug how code is parsed
+pub fn ast(args: Vec<String>) -> Result<(), CliError> {
+  let args = Args::parse(args, &[], &[])?;
+  let path = args.positional(0, "file")?;
+  println!("{}", ast_json(read_source(path)?)?);
+  Ok(())
+}
@@ -0,0 +1,51 @@
+use std::path::Path;
+
+use funki_lang::{BlankCustom, DocFormat, Language};
+
+use crate::args::Args;
+use crate::error::CliError;
+use crate::read_source;
+
+// The format of the page, from `--format`
+fn doc_format(args: &Args) -> Result<DocFormat, CliError> {
+  match args.option("format").unwrap_or("markdown") {
+    "markdown" | "md" => Ok(DocFormat::Markdown),
+    "html" => Ok(DocFormat::Html),
+    f => Err(CliError::usage(format!(
+      "Unknown doc format `{}`, expected `markdown` or `html`.",
+      f
+    ))),
+  }
+}
+
+// Makes the reference page for a script, titled with the name of its file
+pub fn reference(path: &str, code: String, format: DocFormat) -> Result<String, CliError> {
+  let title = match path {
+    "-" => "stdin",
+    p => Path::new(p)
+      .file_stem()
+      .and_then(|s| s.to_str())
+      .unwrap_or(p),
+  };
+  Ok(
+    Language::<BlankCustom>::new()
+      .parse(code)?
+      .reference(title, format),
+  )
+}
+
+// `funki doc <file>`: prints a reference page for a script, or writes it to `--output`
+pub fn doc(args: Vec<String>) -> Result<(), CliError> {
+  let args = Args::parse(args, &["format", "output"], &[])?;
+  let path = args.positional(0, "file")?;
+  let page = reference(path, read_source(path)?, doc_format(&args)?)?;
+
+  match args.option("output") {
+    Some(out) => std::fs::write(out, page)
+      .map_err(|e| CliError::failure(format!("Failed to write `{}`: {}.", out, e))),
+    None => {
+      print!("{}", page);
+      Ok(())
+    }
+  }
+}
@@ -0,0 +1,100 @@
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use std::io::IsTerminal;
+
+use funki_lang::{ErrorKind, LanguageErr};
+
+// Exit codes for failures that are not errors from the script
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+// An error that stops a command, reported on stderr before exiting
+#[derive(Debug)]
+pub enum CliError {
+  // The command line arguments were wrong
+  Usage(String),
+  // A file could not be read or written, or data could not be converted
+  Failure(String),
+  // The script could not be parsed or run
+  Language(LanguageErr),
+}
+
+impl CliError {
+  pub fn usage(message: String) -> Self {
+    CliError::Usage(message)
+  }
+
+  pub fn failure(message: String) -> Self {
+    CliError::Failure(message)
+  }
+
+  // The exit code the process ends with for this error
+  pub fn exit_code(&self) -> i32 {
+    match self {
+      CliError::Usage(_) => EXIT_USAGE,
+      CliError::Failure(_) => EXIT_FAILURE,
+      CliError::Language(e) => kind_exit_code(e.kind()),
+    }
+  }
+
+  // Prints the error to stderr, rendering script errors with the code they refer to
+  pub fn report(&self) {
+    eprint!("{}", self.render(std::io::stderr().is_terminal()));
+  }
+
+  // Renders the error, script errors are shown with the code they refer to
+  pub fn render(&self, colours: bool) -> String {
+    match self {
+      CliError::Language(e) => e.render(colours),
+      e => format!("{}\n", e),
+    }
+  }
+}
+
+// The exit code for each kind of script error, so callers can tell failures apart
+pub fn kind_exit_code(kind: ErrorKind) -> i32 {
+  match kind {
+    ErrorKind::ParseError => 10,
+    ErrorKind::UnexpectedEof => 11,
+    ErrorKind::UnknownFunction => 12,
+    ErrorKind::UnknownVariable => 13,
+    ErrorKind::TypeMismatch => 14,
+    ErrorKind::NoMatchingPattern => 15,
+    ErrorKind::HostError => 16,
+    ErrorKind::LimitExceeded(_) => 17,
+    ErrorKind::Cancelled => 18,
+    ErrorKind::SandboxViolation => 19,
+    ErrorKind::InvalidGlobal => 20,
+    ErrorKind::RuntimeError => 21,
+    ErrorKind::AssertionFailed => 22,
+    _ => 29,
+  }
+}
+
+// The message of a panic caught while calling a script, so it can be reported like an error
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+  match (
+    payload.downcast_ref::<&str>(),
+    payload.downcast_ref::<String>(),
+  ) {
+    (Some(m), _) => m.to_string(),
+    (_, Some(m)) => m.clone(),
+    _ => "unknown panic".to_string(),
+  }
+}
+
+impl Display for CliError {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CliError::Usage(m) => write!(fmt, "error: {}\n\n{}", m, crate::USAGE),
+      CliError::Failure(m) => write!(fmt, "error: {}", m),
+      CliError::Language(e) => write!(fmt, "{}", e),
+    }
+  }
+}
+
+impl From<LanguageErr> for CliError {
+  fn from(e: LanguageErr) -> Self {
+    CliError::Language(e)
+  }
+}
@@ -0,0 +1,38 @@
+use funki_lang::{BlankCustom, Language};
+
+use crate::args::Args;
+use crate::error::CliError;
+use crate::read_source;
+
+// Formats a script's code
+pub fn format_code(code: String) -> Result<String, CliError> {
+  Ok(Language::<BlankCustom>::new().parse(code)?.format())
+}
+
+// `funki fmt <files>`: formats scripts in place, or checks they are formatted with `--check`
+pub fn fmt(args: Vec<String>) -> Result<(), CliError> {
+  let args = Args::parse(args, &[], &["check"])?;
+  let mut unformatted = 0;
+
+  for path in args.all_positional("files")? {
+    let code = read_source(path)?;
+    let formatted = format_code(code.clone())?;
+    if path == "-" && !args.flag("check") {
+      print!("{}", formatted);
+    } else if formatted != code {
+      unformatted += 1;
+      if args.flag("check") {
+        println!("{} is not formatted", path);
+      } else {
+        std::fs::write(path, formatted)
+          .map_err(|e| CliError::failure(format!("Failed to write `{}`: {}.", path, e)))?;
+      }
+    }
+  }
+
+  match (args.flag("check"), unformatted) {
+    (true, 1) => Err(CliError::failure("1 file is not formatted.".to_string())),
+    (true, n) if n > 0 => Err(CliError::failure(format!("{} files are not formatted.", n))),
+    _ => Ok(()),
+  }
+}
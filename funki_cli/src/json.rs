@@ -0,0 +1,72 @@
+use funki_lang::{Argument, BlankCustom, ReturnVal};
+use serde_json::{Number, Value};
+
+use crate::error::CliError;
+
+// Converts a JSON value to an argument
+// Objects become lists of `(key, value)` tuples, in the order of their keys, and `null` becomes
+// the empty tuple
+pub fn to_argument(val: &Value) -> Result<Argument<BlankCustom>, CliError> {
+  Ok(match val {
+    Value::Null => Argument::Tuple(vec![]),
+    Value::Bool(b) => Argument::Bool(*b),
+    Value::Number(n) => Argument::Int(
+      n.as_i64()
+        .and_then(|i| i32::try_from(i).ok())
+        .ok_or_else(|| CliError::failure(format!("{} is not a 32 bit integer.", n)))?,
+    ),
+    Value::String(s) => Argument::String(s.clone()),
+    Value::Array(v) => Argument::List(v.iter().map(to_argument).collect::<Result<_, _>>()?),
+    Value::Object(m) => Argument::List(
+      m.iter()
+        .map(|(k, v)| {
+          Ok(Argument::Tuple(vec![
+            Argument::String(k.clone()),
+            to_argument(v)?,
+          ]))
+        })
+        .collect::<Result<_, CliError>>()?,
+    ),
+  })
+}
+
+// Converts a JSON array of arguments to the argument of a function call
+// A single argument is passed on its own, more than one are passed as a tuple
+pub fn to_call_argument(val: &Value) -> Result<Argument<BlankCustom>, CliError> {
+  let args = match val {
+    Value::Array(v) => v,
+    _ => {
+      return Err(CliError::failure(
+        "The arguments must be a JSON array, with an item for each argument.".to_string(),
+      ))
+    }
+  };
+  let mut args = args
+    .iter()
+    .map(to_argument)
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(match args.len() {
+    1 => args.remove(0),
+    _ => Argument::Tuple(args),
+  })
+}
+
+// Parses JSON text, naming where it came from in the error
+pub fn parse(text: &str, source: &str) -> Result<Value, CliError> {
+  serde_json::from_str(text)
+    .map_err(|e| CliError::failure(format!("Invalid JSON in {}: {}.", source, e)))
+}
+
+// Converts a returned value to JSON
+// Tuples become arrays, and custom values and functions become their text
+pub fn from_return_val(val: &ReturnVal<BlankCustom>) -> Value {
+  match val {
+    ReturnVal::String(s) => Value::String(s.clone()),
+    ReturnVal::Int(i) => Value::Number(Number::from(*i)),
+    ReturnVal::Bool(b) => Value::Bool(*b),
+    ReturnVal::Tuple(v) | ReturnVal::List(v) => {
+      Value::Array(v.iter().map(from_return_val).collect())
+    }
+    ReturnVal::Custom(_) | ReturnVal::Callable(_) => Value::String(val.to_string()),
+  }
+}
@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use funki_lang::limits::Limits;
+
+use crate::args::Args;
+use crate::error::CliError;
+
+// The number of steps a call can take, a few seconds of evaluation, so a script that never
+//  finishes fails rather than hanging
+pub const MAX_STEPS: u64 = 20_000_000;
+
+// The limits scripts are run with, the library's depth limit with `MAX_STEPS`, and the
+//  timeout in seconds from `--timeout` if it is given
+pub fn limits(args: &Args) -> Result<Limits, CliError> {
+  let timeout = match args.option("timeout") {
+    Some(t) => Some(
+      t.parse()
+        .ok()
+        .filter(|t: &f64| t.is_finite() && *t > 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| {
+          CliError::usage(format!(
+            "`--timeout` must be a positive number of seconds, not `{}`.",
+            t
+          ))
+        })?,
+    ),
+    None => None,
+  };
+  Ok(Limits {
+    max_steps: Some(MAX_STEPS),
+    timeout,
+    ..Limits::default()
+  })
+}
@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use funki_lang::{BlankCustom, Language, LanguageErr, ReferenceKind, Script, Span};
+use serde_json::{json, Value};
+
+use crate::args::Args;
+use crate::error::CliError;
+
+// JSON-RPC error code for requests the server does not support
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// LSP diagnostic severities
+const ERROR: u8 = 1;
+const WARNING: u8 = 2;
+
+// LSP symbol and completion item kinds
+const SYMBOL_FUNCTION: u8 = 12;
+const COMPLETION_FUNCTION: u8 = 3;
+const COMPLETION_VARIABLE: u8 = 6;
+const COMPLETION_CONSTANT: u8 = 21;
+
+// An open document, with the last version of it that parsed
+struct Document {
+  text: String,
+  script: Option<Script<BlankCustom>>,
+  // Whether the script was parsed from the current text, so its spans can be used with it
+  current: bool,
+}
+
+impl Document {
+  // The script, if it was parsed from the current text
+  fn current_script(&self) -> Option<&Script<BlankCustom>> {
+    self.script.as_ref().filter(|_| self.current)
+  }
+}
+
+// A language server for Funki scripts, answering requests from an editor
+pub struct Server {
+  lang: Language<BlankCustom>,
+  docs: HashMap<String, Document>,
+  shut_down: bool,
+}
+
+impl Server {
+  pub fn new(lang: Language<BlankCustom>) -> Self {
+    Server {
+      lang,
+      docs: HashMap::new(),
+      shut_down: false,
+    }
+  }
+
+  // Handles a message from the editor, returning the messages to send back
+  // Returns `None` when the editor asks the server to exit
+  pub fn handle(&mut self, msg: &Value) -> Option<Vec<Value>> {
+    let method = msg["method"].as_str().unwrap_or_default();
+    let params = &msg["params"];
+    let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+    let result = match method {
+      "initialize" => json!({
+        "capabilities": {
+          "textDocumentSync": 1,
+          "hoverProvider": true,
+          "definitionProvider": true,
+          "completionProvider": {},
+          "documentSymbolProvider": true,
+        },
+        "serverInfo": { "name": "funki", "version": env!("CARGO_PKG_VERSION") },
+      }),
+      "shutdown" => {
+        self.shut_down = true;
+        Value::Null
+      }
+      "exit" => return None,
+      "textDocument/didOpen" => {
+        return Some(self.update(uri, params["textDocument"]["text"].as_str()))
+      }
+      "textDocument/didChange" => {
+        let text = params["contentChanges"]
+          .as_array()
+          .and_then(|c| c.last())
+          .and_then(|c| c["text"].as_str());
+        return Some(self.update(uri, text));
+      }
+      "textDocument/didClose" => {
+        self.docs.remove(uri);
+        return Some(vec![diagnostics(uri, vec![])]);
+      }
+      "textDocument/hover" => self.hover(uri, &params["position"]),
+      "textDocument/definition" => self.definition(uri, &params["position"]),
+      "textDocument/completion" => self.completion(uri, &params["position"]),
+      "textDocument/documentSymbol" => self.symbols(uri),
+      // Other notifications are ignored
+      _ if msg.get("id").is_none() => return Some(vec![]),
+      _ => {
+        return Some(vec![json!({
+          "jsonrpc": "2.0",
+          "id": msg["id"],
+          "error": { "code": METHOD_NOT_FOUND, "message": format!("Unknown method `{}`.", method) },
+        })])
+      }
+    };
+    Some(vec![
+      json!({ "jsonrpc": "2.0", "id": msg["id"], "result": result }),
+    ])
+  }
+
+  // Whether the editor asked the server to shut down before exiting
+  pub fn shut_down(&self) -> bool {
+    self.shut_down
+  }
+
+  // Updates a document's text, and reports its errors and lints
+  fn update(&mut self, uri: &str, text: Option<&str>) -> Vec<Value> {
+    let text = match text {
+      Some(t) => t.to_string(),
+      None => return vec![],
+    };
+    let (script, found) = match self.lang.parse(text.clone()) {
+      Ok(s) => {
+        let lints = s
+          .lints()
+          .into_iter()
+          .map(|l| diagnostic(&text, &l.span, WARNING, l.code(), &l.message))
+          .collect();
+        (Some(s), lints)
+      }
+      Err(e) => (None, errors(&text, &e)),
+    };
+
+    let doc = self.docs.entry(uri.to_string()).or_insert(Document {
+      text: String::new(),
+      script: None,
+      current: false,
+    });
+    doc.text = text;
+    doc.current = script.is_some();
+    // Keep the last script that parsed, so names can still be completed while the code is edited
+    // Its spans are for the old text, so it isn't used to find names until the code parses again
+    if script.is_some() {
+      doc.script = script;
+    }
+    vec![diagnostics(uri, found)]
+  }
+
+  // The document and the byte offset of a position in it
+  fn at(&self, uri: &str, pos: &Value) -> Option<(&Document, usize)> {
+    let doc = self.docs.get(uri)?;
+    let offset = offset_of(
+      &doc.text,
+      pos["line"].as_u64()? as usize,
+      pos["character"].as_u64()? as usize,
+    );
+    Some((doc, offset))
+  }
+
+  // Describes the name at a position, functions are shown with their patterns and type
+  fn hover(&self, uri: &str, pos: &Value) -> Value {
+    let (doc, offset) = match self.at(uri, pos) {
+      Some(d) => d,
+      None => return Value::Null,
+    };
+    let (script, r) = match doc
+      .current_script()
+      .and_then(|s| Some((s, s.reference_at(offset)?)))
+    {
+      Some(r) => r,
+      None => return Value::Null,
+    };
+
+    let value = match r.kind {
+      ReferenceKind::Function => {
+        let symbol = script.symbols().into_iter().find(|s| s.name == r.name);
+        let (patterns, doc) = symbol.map(|s| (s.patterns, s.doc)).unwrap_or_default();
+        let mut value = format!("```funki\n#{} {};", r.name, patterns.join(";\n  "));
+        value.push_str("\n```");
+        if let Some(t) = script
+          .signatures()
+          .ok()
+          .and_then(|s| s.get(&r.name).cloned())
+        {
+          value.push_str(&format!("\n\n`{}: {}`", r.name, t));
+        }
+        if let Some(doc) = doc {
+          value.push_str(&format!("\n\n{}", doc));
+        }
+        value
+      }
+      ReferenceKind::Variable => format!("variable `{}`", r.name),
+      ReferenceKind::Builtin => format!("builtin function `{}`", r.name),
+      ReferenceKind::Global => format!("global `{}`", r.name),
+      _ => format!("unknown name `{}`", r.name),
+    };
+    json!({
+      "contents": { "kind": "markdown", "value": value },
+      "range": range(&doc.text, &r.span),
+    })
+  }
+
+  // Finds where the name at a position is defined
+  fn definition(&self, uri: &str, pos: &Value) -> Value {
+    self
+      .at(uri, pos)
+      .and_then(|(doc, offset)| {
+        let span = doc.current_script()?.reference_at(offset)?.definition?;
+        Some(json!({ "uri": uri, "range": range(&doc.text, &span) }))
+      })
+      .unwrap_or(Value::Null)
+  }
+
+  // Lists the names that can be used at a position, with the builtins if the code doesn't parse
+  // While the code doesn't parse, the offset doesn't match the last script, so only the names
+  //  that can be used anywhere in it are listed, without the variables in scope
+  fn completion(&self, uri: &str, pos: &Value) -> Value {
+    let mut names = self
+      .at(uri, pos)
+      .and_then(|(doc, offset)| {
+        let names = doc.script.as_ref()?.names_at(offset).into_iter();
+        Some(
+          names
+            .filter(|(_, k)| doc.current || *k != ReferenceKind::Variable)
+            .collect::<Vec<_>>(),
+        )
+      })
+      .unwrap_or_default();
+    for b in self.lang.builtins() {
+      if !names.iter().any(|(n, _)| *n == b) {
+        names.push((b, ReferenceKind::Builtin));
+      }
+    }
+
+    let items: Vec<Value> = names
+      .into_iter()
+      .map(|(name, kind)| {
+        let (kind, detail) = match kind {
+          ReferenceKind::Function => (COMPLETION_FUNCTION, "function"),
+          ReferenceKind::Variable => (COMPLETION_VARIABLE, "variable"),
+          ReferenceKind::Global => (COMPLETION_CONSTANT, "global"),
+          _ => (COMPLETION_FUNCTION, "builtin"),
+        };
+        json!({ "label": name, "kind": kind, "detail": detail })
+      })
+      .collect();
+    Value::Array(items)
+  }
+
+  // Lists the functions in a document
+  fn symbols(&self, uri: &str) -> Value {
+    let doc = match self.docs.get(uri) {
+      Some(d) => d,
+      None => return Value::Null,
+    };
+    let script = match doc.current_script() {
+      Some(s) => s,
+      None => return json!([]),
+    };
+    let types = script.signatures().unwrap_or_default();
+    let symbols: Vec<Value> = script
+      .symbols()
+      .into_iter()
+      .map(|s| {
+        json!({
+          "name": s.name,
+          "detail": types.get(&s.name).map(|t| t.to_string()).unwrap_or_default(),
+          "kind": SYMBOL_FUNCTION,
+          "range": range(&doc.text, &s.span),
+          "selectionRange": range(&doc.text, &s.name_span),
+        })
+      })
+      .collect();
+    Value::Array(symbols)
+  }
+}
+
+// The diagnostics for an error, which can contain more than one error
+fn errors(text: &str, e: &LanguageErr) -> Vec<Value> {
+  e.errors()
+    .map(|e| {
+      let span = e.span().unwrap_or_else(start_span);
+      diagnostic(text, &span, ERROR, &e.kind().to_string(), e.message())
+    })
+    .collect()
+}
+
+// An empty span at the start of the code, for errors without a location
+fn start_span() -> Span {
+  Span {
+    start: 0,
+    end: 0,
+    start_line: 1,
+    start_col: 1,
+    end_line: 1,
+    end_col: 1,
+  }
+}
+
+// A single diagnostic
+fn diagnostic(text: &str, span: &Span, severity: u8, code: &str, message: &str) -> Value {
+  json!({
+    "range": range(text, span),
+    "severity": severity,
+    "code": code,
+    "source": "funki",
+    "message": message,
+  })
+}
+
+// The notification that replaces a document's diagnostics
+fn diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+  json!({
+    "jsonrpc": "2.0",
+    "method": "textDocument/publishDiagnostics",
+    "params": { "uri": uri, "diagnostics": diagnostics },
+  })
+}
+
+// The LSP range of a span
+fn range(text: &str, span: &Span) -> Value {
+  json!({ "start": position_of(text, span.start), "end": position_of(text, span.end) })
+}
+
+// The LSP position of a byte offset, where characters are counted in UTF-16 code units
+pub fn position_of(text: &str, offset: usize) -> Value {
+  let offset = offset.min(text.len());
+  let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+  json!({
+    "line": text[..offset].matches('\n').count(),
+    "character": text[line_start..offset].encode_utf16().count(),
+  })
+}
+
+// The byte offset of an LSP position
+pub fn offset_of(text: &str, line: usize, character: usize) -> usize {
+  let line_start = match line {
+    0 => 0,
+    l => match text.match_indices('\n').nth(l - 1) {
+      Some((i, _)) => i + 1,
+      None => return text.len(),
+    },
+  };
+  let mut units = 0;
+  for (i, c) in text[line_start..].char_indices() {
+    if units >= character || c == '\n' {
+      return line_start + i;
+    }
+    units += c.len_utf16();
+  }
+  text.len()
+}
+
+// Reads a message, returning `None` at the end of the input
+fn read_message(input: &mut impl BufRead) -> Result<Option<Value>, CliError> {
+  let err = |e: std::io::Error| CliError::failure(format!("Failed to read a message: {}.", e));
+  let mut length = None;
+  loop {
+    let mut line = String::new();
+    if input.read_line(&mut line).map_err(err)? == 0 {
+      return Ok(None);
+    }
+    let line = line.trim_end();
+    if line.is_empty() {
+      break;
+    }
+    if let Some((name, value)) = line.split_once(':') {
+      if name.eq_ignore_ascii_case("content-length") {
+        length = value.trim().parse::<usize>().ok();
+      }
+    }
+  }
+
+  let length = length.ok_or_else(|| CliError::failure("A message has no length.".to_string()))?;
+  let mut body = vec![0; length];
+  input.read_exact(&mut body).map_err(err)?;
+  serde_json::from_slice(&body)
+    .map(Some)
+    .map_err(|e| CliError::failure(format!("Invalid message: {}.", e)))
+}
+
+// Writes a message
+fn write_message(output: &mut impl Write, msg: &Value) -> Result<(), CliError> {
+  let body = msg.to_string();
+  write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+    .and_then(|_| output.flush())
+    .map_err(|e| CliError::failure(format!("Failed to write a message: {}.", e)))
+}
+
+// `funki lsp`: runs a language server, talking to an editor over stdin and stdout
+pub fn lsp(args: Vec<String>) -> Result<(), CliError> {
+  Args::parse(args, &[], &[])?;
+  let mut server = Server::new(Language::new());
+  let mut input = std::io::stdin().lock();
+  let mut output = std::io::stdout().lock();
+
+  while let Some(msg) = read_message(&mut input)? {
+    match server.handle(&msg) {
+      Some(replies) => {
+        for r in replies {
+          write_message(&mut output, &r)?;
+        }
+      }
+      None => break,
+    }
+  }
+  match server.shut_down() {
+    true => Ok(()),
+    false => Err(CliError::failure(
+      "The editor exited without shutting down the server.".to_string(),
+    )),
+  }
+}
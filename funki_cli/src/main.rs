@@ -0,0 +1,116 @@
+use std::io::Read;
+use std::process::exit;
+
+use crate::error::CliError;
+
+mod args;
+mod ast;
+mod doc;
+mod error;
+mod fmt;
+mod json;
+mod limits;
+mod lsp;
+mod render;
+mod repl;
+mod run;
+mod serve;
+mod test;
+mod unit_tests;
+mod watch;
+
+// The help text printed for `funki help` and after usage errors
+pub const USAGE: &str = "Usage: funki <command> [options]
+
+Commands:
+  run <file>    Calls a function of a script and prints the result
+      --fn <name>          The function to call, `main` by default
+      --arg-json <json>    A JSON array with an item for each argument
+      --args-file <path>   A file containing the JSON array of arguments
+      --output <format>    `text` (default) or `json`
+      --check-types        Checks the types of the script before running it
+      --timeout <seconds>  Stops the call if it runs for longer
+      --watch              Runs the function again whenever the script or arguments file change
+  repl [file]   Starts an interactive session, loading the functions in the file if given
+  fmt <files>   Formats scripts in place, or prints the formatted script for `-`
+      --check              Lists the files that are not formatted instead, failing if any are
+  render <dir>  Renders each `.funki` template in a directory by calling its `main` with the data
+      --out <dir>          The directory to write to, `a/b.html.funki` is written to `<dir>/a/b.html`
+      --data <path>        A JSON file, or a TOML file ending in `.toml`, to call `main` with
+      --jobs <n>           How many templates to render at once, the number of cores by default
+      --timeout <seconds>  Stops rendering a template if it runs for longer
+      --watch              Renders the templates that changed, or all if the data changed, whenever
+                           a file changes
+  serve <file>  Serves a script's functions on localhost, `/` calls `main` and `/name` calls `name`
+      --port <port>        The port to listen on, 8000 by default
+      --timeout <seconds>  Stops a request's call if it runs for longer, 5 by default
+      --data <path>        A JSON or TOML file, functions are called with `(data, params)` where
+                           params are the query parameters as a list of (name, value) tuples
+  test <files>  Runs the functions named `test_...` in scripts, failing if any fail
+      --filter <text>      Only runs the tests whose names contain the text
+      --timeout <seconds>  Stops each test if it runs for longer
+  doc <file>    Prints a reference page for a script, with its functions' docs and the builtins
+      --format <format>    `markdown` (default) or `html`
+      --output <path>      Writes the page to a file instead
+  lsp           Runs a language server for editors, over stdin and stdout
+  ast <file>    Prints the tree a script is parsed into as JSON, with where each part is
+  help          Prints this message
+
+A file of `-` is read from stdin.
+JSON objects are passed as lists of (key, value) tuples, and `null` as `()`.
+Calls stop with a limit error after 20,000,000 steps, or if they recurse too deeply.
+
+Exit codes:
+  0   success
+  1   a file could not be read, data could not be converted, or tests failed
+  2   the command line arguments were wrong
+  10  parse error               16  host error
+  11  unexpected end of file    17  limit exceeded
+  12  unknown function          18  cancelled
+  13  unknown variable          19  sandbox violation
+  14  type mismatch             20  invalid global
+  15  no matching pattern       21  runtime error
+                                22  assertion failed";
+
+fn main() {
+  let mut args = std::env::args().skip(1);
+  let command = args.next();
+  let args = args.collect();
+
+  let res = match command.as_deref() {
+    Some("run") => run::run(args),
+    Some("repl") => repl::repl(args),
+    Some("fmt") => fmt::fmt(args),
+    Some("render") => render::render(args),
+    Some("serve") => serve::serve(args),
+    Some("test") => unit_tests::test(args),
+    Some("doc") => doc::doc(args),
+    Some("lsp") => lsp::lsp(args),
+    Some("ast") => ast::ast(args),
+    Some("help" | "--help" | "-h") => {
+      println!("{}", USAGE);
+      Ok(())
+    }
+    Some(c) => Err(CliError::usage(format!("Unknown command `{}`.", c))),
+    None => Err(CliError::usage("No command given.".to_string())),
+  };
+
+  if let Err(e) = res {
+    e.report();
+    exit(e.exit_code());
+  }
+}
+
+// Reads a file, or stdin when the path is `-`
+pub fn read_source(path: &str) -> Result<String, CliError> {
+  if path == "-" {
+    let mut buffer = String::new();
+    std::io::stdin()
+      .read_to_string(&mut buffer)
+      .map_err(|e| CliError::failure(format!("Failed to read stdin: {}.", e)))?;
+    Ok(buffer)
+  } else {
+    std::fs::read_to_string(path)
+      .map_err(|e| CliError::failure(format!("Failed to read `{}`: {}.", path, e)))
+  }
+}
@@ -0,0 +1,253 @@
+use std::io::IsTerminal;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use funki_lang::limits::Limits;
+use funki_lang::{BlankCustom, Language, ReturnVal};
+use serde_json::Value;
+
+use crate::args::Args;
+use crate::error::{panic_message, CliError};
+use crate::limits::limits;
+use crate::watch::{hash, Cache, Watcher};
+use crate::{json, read_source};
+
+// The extension of template files, which is removed from the name of the rendered file
+pub const EXTENSION: &str = "funki";
+
+// Finds the templates in a directory and its subdirectories, in order of their paths
+// Links to directories are not followed, so a link back to a parent directory can't loop forever
+pub fn templates(dir: &Path) -> Result<Vec<PathBuf>, CliError> {
+  let mut res = vec![];
+  let mut dirs = vec![dir.to_path_buf()];
+  while let Some(d) = dirs.pop() {
+    let entries = std::fs::read_dir(&d)
+      .map_err(|e| CliError::failure(format!("Failed to read `{}`: {}.", d.display(), e)))?;
+    for entry in entries {
+      let entry = entry
+        .map_err(|e| CliError::failure(format!("Failed to read `{}`: {}.", d.display(), e)))?;
+      let path = entry.path();
+      if entry.file_type().is_ok_and(|t| t.is_dir()) {
+        dirs.push(path);
+      } else if !path.is_dir() && path.extension().is_some_and(|e| e == EXTENSION) {
+        res.push(path);
+      }
+    }
+  }
+  res.sort();
+  Ok(res)
+}
+
+// Where a template is rendered to, at the same path in the output directory without `.funki`
+// `pages/index.html.funki` is rendered to `out/pages/index.html`
+pub fn output_path(templates: &Path, out: &Path, template: &Path) -> PathBuf {
+  let relative = template.strip_prefix(templates).unwrap_or(template);
+  out.join(relative.with_extension(""))
+}
+
+// Reads the data templates are called with, from a JSON file or a TOML file ending in `.toml`
+pub fn load_data(path: &str) -> Result<Value, CliError> {
+  let text = read_source(path)?;
+  match path.ends_with(".toml") {
+    true => toml::from_str(&text)
+      .map_err(|e| CliError::failure(format!("Invalid TOML in {}: {}", path, e))),
+    false => json::parse(&text, path),
+  }
+}
+
+// Renders a template by calling its `main` function with the data, which must return a string
+// A template that panics fails with the panic's message, so the other templates still render
+pub fn render_template(
+  code: String,
+  data: Option<&Value>,
+  limits: &Limits,
+) -> Result<String, CliError> {
+  let mut lang = Language::<BlankCustom>::new();
+  lang.set_limits(limits.clone());
+  let script = lang.parse(code)?;
+  let mut main = script.function("main")?;
+  if let Some(data) = data {
+    main = main.arg(json::to_argument(data)?);
+  }
+  let res = catch_unwind(AssertUnwindSafe(|| main.call()))
+    .map_err(|p| CliError::failure(format!("`main` panicked: {}.", panic_message(&*p))))?;
+  match res? {
+    ReturnVal::String(s) => Ok(s),
+    v => Err(CliError::failure(format!(
+      "`main` returned {} rather than a string.",
+      v
+    ))),
+  }
+}
+
+// Renders templates in parallel on up to `jobs` threads, returning the result for each template
+//  in the same order
+pub fn render_all<T: Sync, R: Send>(
+  templates: &[T],
+  jobs: usize,
+  render: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+  let next = AtomicUsize::new(0);
+  let results = Mutex::new((0..templates.len()).map(|_| None).collect::<Vec<_>>());
+  std::thread::scope(|s| {
+    for _ in 0..jobs.clamp(1, templates.len().max(1)) {
+      s.spawn(|| loop {
+        let i = next.fetch_add(1, Ordering::Relaxed);
+        match templates.get(i) {
+          Some(t) => {
+            let res = render(t);
+            results.lock().unwrap()[i] = Some(res);
+          }
+          None => break,
+        }
+      });
+    }
+  });
+  results
+    .into_inner()
+    .unwrap()
+    .into_iter()
+    .map(|r| r.unwrap())
+    .collect()
+}
+
+// The number of threads to render on, from `--jobs` or the number of cores
+pub fn jobs(args: &Args) -> Result<usize, CliError> {
+  match args.option("jobs") {
+    Some(j) => j
+      .parse()
+      .ok()
+      .filter(|j| *j > 0)
+      .ok_or_else(|| CliError::usage(format!("`--jobs` must be a positive number, not `{}`.", j))),
+    None => Ok(std::thread::available_parallelism().map_or(1, |n| n.get())),
+  }
+}
+
+// Writes a rendered template, creating the directories it is in
+pub fn write_output(path: &Path, text: &str) -> Result<(), CliError> {
+  let err =
+    |e: std::io::Error| CliError::failure(format!("Failed to write `{}`: {}.", path.display(), e));
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent).map_err(err)?;
+  }
+  std::fs::write(path, text).map_err(err)
+}
+
+// The result of rendering a template, with the rendered error if it failed
+type Rendered = Result<(), String>;
+
+// Renders the templates whose code or data changed since they were last rendered, reusing the
+//  results in the cache for the rest. Returns how many of those rendered successfully, and the
+//  errors of every template that currently fails.
+pub fn render_changed(
+  dir: &Path,
+  out: &Path,
+  data: Option<&Value>,
+  limits: &Limits,
+  jobs: usize,
+  cache: &mut Cache<Rendered>,
+) -> Result<(usize, Vec<(PathBuf, String)>), CliError> {
+  let templates = templates(dir)?;
+  let data_text = data.map(|d| d.to_string()).unwrap_or_default();
+  let colours = std::io::stderr().is_terminal();
+
+  // A template that can't be read is rendered, so the error is reported
+  let stale: Vec<(PathBuf, u64)> = templates
+    .iter()
+    .map(|t| {
+      let code = std::fs::read_to_string(t).unwrap_or_default();
+      (t.clone(), hash(&[&code, &data_text]))
+    })
+    .filter(|(t, key)| cache.get(t, *key).is_none())
+    .collect();
+  let results = render_all(&stale, jobs, |(t, _)| {
+    let text = render_template(read_source(&t.to_string_lossy())?, data, limits)?;
+    write_output(&output_path(dir, out, t), &text)
+  });
+  let mut rendered = 0;
+  for ((t, key), res) in stale.iter().zip(results) {
+    rendered += res.is_ok() as usize;
+    cache.insert(t.clone(), *key, res.map_err(|e| e.render(colours)));
+  }
+  cache.retain(&templates);
+
+  let errors = templates
+    .iter()
+    .filter_map(|t| match cache.get_any(t) {
+      Some(Err(e)) => Some((t.clone(), e.clone())),
+      _ => None,
+    })
+    .collect();
+  Ok((rendered, errors))
+}
+
+// Prints the errors of the templates that failed, and how many rendered
+fn report(rendered: usize, errors: &[(PathBuf, String)], out: &Path) {
+  for (t, e) in errors {
+    eprint!("\n---- {} ----\n{}", t.display(), e);
+  }
+  println!(
+    "Rendered {} templates into {}, {} failed.",
+    rendered,
+    out.display(),
+    errors.len()
+  );
+}
+
+// `funki render <templates>`: renders each template in a directory into the output directory,
+//  reporting the errors of every template that failed at the end
+// With `--watch`, renders the templates again whenever they or the data change, until stopped
+pub fn render(args: Vec<String>) -> Result<(), CliError> {
+  let args = Args::parse(args, &["data", "out", "jobs", "timeout"], &["watch"])?;
+  let dir = Path::new(args.positional(0, "templates")?);
+  let out = Path::new(
+    args
+      .option("out")
+      .ok_or_else(|| CliError::usage("No output directory given with `--out`.".to_string()))?,
+  );
+  let jobs = jobs(&args)?;
+  let limits = limits(&args)?;
+  let mut cache = Cache::new();
+
+  if !args.flag("watch") {
+    let data = args.option("data").map(load_data).transpose()?;
+    let (rendered, errors) = render_changed(dir, out, data.as_ref(), &limits, jobs, &mut cache)?;
+    report(rendered, &errors, out);
+    return match errors.len() {
+      0 => Ok(()),
+      1 => Err(CliError::failure(
+        "1 template failed to render.".to_string(),
+      )),
+      n => Err(CliError::failure(format!(
+        "{} templates failed to render.",
+        n
+      ))),
+    };
+  }
+
+  let files = || {
+    let mut files = templates(dir).unwrap_or_default();
+    files.extend(args.option("data").map(PathBuf::from));
+    files
+  };
+  let mut watcher = Watcher::new();
+  watcher.changed(&files());
+  println!("Watching {} for changes.", dir.display());
+  loop {
+    // Errors in the data or the directory are reported, then fixed while watching
+    let res = args
+      .option("data")
+      .map(load_data)
+      .transpose()
+      .and_then(|data| render_changed(dir, out, data.as_ref(), &limits, jobs, &mut cache));
+    match res {
+      Ok((rendered, errors)) => report(rendered, &errors, out),
+      Err(e) => e.report(),
+    }
+    for changed in watcher.wait(files) {
+      println!("\n{} changed.", changed.display());
+    }
+  }
+}
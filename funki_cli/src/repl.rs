@@ -0,0 +1,212 @@
+use std::io::IsTerminal;
+
+use funki_lang::types::Type;
+use funki_lang::{BlankCustom, ErrorKind, Language, LanguageErr, ReturnVal, Script};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use crate::args::Args;
+use crate::error::CliError;
+use crate::read_source;
+
+// The name of the function expressions are wrapped in to be evaluated
+// It contains a `-`, which names in scripts rarely do, so it is unlikely to shadow a definition
+const EXPR_FN: &str = "repl-expr";
+
+const PROMPT: &str = "funki> ";
+const CONTINUATION_PROMPT: &str = "  ...> ";
+
+const HELP: &str = "Enter `#name patterns;` to define a function, or an expression to evaluate it.
+Input that is not finished continues on the next line.
+
+Commands:
+  :type <expr>   Prints the type of an expression
+  :list          Lists the functions defined so far
+  :load <file>   Loads the functions in a file
+  :help          Prints this message
+  :quit          Exits, as does Ctrl-D";
+
+// What happened after some input was entered
+#[derive(Debug)]
+pub enum Outcome {
+  // The input is not finished, the next line continues it
+  Incomplete,
+  // The input was handled, with text to print
+  Output(String),
+  // The input failed
+  Error(CliError),
+  // The REPL should exit
+  Quit,
+}
+
+// The state of a REPL session, the definitions entered so far
+pub struct Repl {
+  lang: Language<BlankCustom>,
+  defs: String,
+}
+
+impl Repl {
+  pub fn new(lang: Language<BlankCustom>) -> Self {
+    Repl {
+      lang,
+      defs: String::new(),
+    }
+  }
+
+  // Handles the input entered since the last complete input
+  pub fn enter(&mut self, input: &str) -> Outcome {
+    let trimmed = input.trim();
+    let res = if let Some(command) = trimmed.strip_prefix(':') {
+      self.command(command)
+    } else if trimmed.is_empty() {
+      Ok(Outcome::Output(String::new()))
+    } else if trimmed.starts_with('#') {
+      self.define(input)
+    } else {
+      self.evaluate(trimmed)
+    };
+    res.unwrap_or_else(Outcome::Error)
+  }
+
+  // Runs a `:command`
+  fn command(&mut self, command: &str) -> Result<Outcome, CliError> {
+    let (name, rest) = command
+      .split_once(char::is_whitespace)
+      .unwrap_or((command, ""));
+    let rest = rest.trim();
+    match name {
+      "type" | "t" => self.type_of(rest).map(Outcome::Output),
+      "list" | "l" => Ok(Outcome::Output(self.functions()?.join("\n"))),
+      "load" => {
+        let code = read_source(rest)?;
+        self.add_definitions(code).map(Outcome::Output)
+      }
+      "help" | "h" => Ok(Outcome::Output(HELP.to_string())),
+      "quit" | "q" => Ok(Outcome::Quit),
+      _ => Err(CliError::failure(format!(
+        "Unknown command `:{}`, enter `:help` for a list of commands.",
+        name
+      ))),
+    }
+  }
+
+  // Adds function definitions, or waits for more input if they are unfinished
+  fn define(&mut self, input: &str) -> Result<Outcome, CliError> {
+    match self.lang.parse(input.to_string()) {
+      Err(e) if is_unfinished(&e) => Ok(Outcome::Incomplete),
+      Err(e) => Err(e.into()),
+      Ok(_) => self.add_definitions(input.to_string()).map(Outcome::Output),
+    }
+  }
+
+  // Adds definitions to the session, a function that is already defined is replaced
+  fn add_definitions(&mut self, code: String) -> Result<String, CliError> {
+    let mut names = self.lang.parse(code.clone())?.list();
+    names.sort();
+    self.defs.push_str(&code);
+    self.defs.push('\n');
+    Ok(format!("Defined {}.", names.join(", ")))
+  }
+
+  // Parses the definitions so far with an expression wrapped in a function, or returns `None` if
+  // the expression is unfinished
+  fn with_expr(&self, expr: &str) -> Result<Option<Script<BlankCustom>>, CliError> {
+    let expr = expr.strip_suffix(';').unwrap_or(expr);
+    let open = format!("#{}\n{}", EXPR_FN, expr);
+    match self.lang.parse(open.clone()) {
+      Err(e) if is_unfinished(&e) => match self.lang.parse(format!("{};", open)) {
+        Ok(_) => (),
+        Err(_) => return Ok(None),
+      },
+      Err(e) => return Err(e.into()),
+      Ok(_) => (),
+    }
+    Ok(Some(self.lang.parse(format!("{}{};", self.defs, open))?))
+  }
+
+  // Evaluates an expression
+  fn evaluate(&self, expr: &str) -> Result<Outcome, CliError> {
+    match self.with_expr(expr)? {
+      Some(script) => Ok(Outcome::Output(show(&script.function(EXPR_FN)?.call()?))),
+      None => Ok(Outcome::Incomplete),
+    }
+  }
+
+  // The type of an expression
+  fn type_of(&self, expr: &str) -> Result<String, CliError> {
+    let script = self
+      .with_expr(expr)?
+      .ok_or_else(|| CliError::failure("The expression is not finished.".to_string()))?;
+    match script.signatures()?.remove(EXPR_FN) {
+      Some(Type::Function(_, res)) => Ok(res.to_string()),
+      t => Ok(t.map(|t| t.to_string()).unwrap_or_default()),
+    }
+  }
+
+  // The names of the functions defined so far
+  fn functions(&self) -> Result<Vec<String>, CliError> {
+    let mut names = self.lang.parse(self.defs.clone())?.list();
+    names.sort();
+    Ok(names)
+  }
+}
+
+// Whether parsing failed only because the code ended early
+fn is_unfinished(e: &LanguageErr) -> bool {
+  e.errors().all(|e| e.kind() == ErrorKind::UnexpectedEof)
+}
+
+// Formats a value for the REPL, with strings quoted so they can be told apart from other values
+pub fn show(val: &ReturnVal<BlankCustom>) -> String {
+  match val {
+    ReturnVal::String(s) => format!("{:?}", s),
+    ReturnVal::Tuple(v) => format!("({})", v.iter().map(show).collect::<Vec<_>>().join(", ")),
+    ReturnVal::List(v) => format!("[{}]", v.iter().map(show).collect::<Vec<_>>().join(", ")),
+    v => v.to_string(),
+  }
+}
+
+// `funki repl [file]`: reads and evaluates input until it is closed
+pub fn repl(args: Vec<String>) -> Result<(), CliError> {
+  let args = Args::parse(args, &[], &[])?;
+  let mut repl = Repl::new(Language::new());
+  if let Ok(path) = args.positional(0, "file") {
+    println!("{}", repl.add_definitions(read_source(path)?)?);
+  }
+
+  let mut editor = DefaultEditor::new()
+    .map_err(|e| CliError::failure(format!("Failed to start the line editor: {}.", e)))?;
+  if std::io::stdin().is_terminal() {
+    println!("Funki REPL, enter `:help` for help.");
+  }
+  let mut buffer = String::new();
+  loop {
+    let prompt = match buffer.is_empty() {
+      true => PROMPT,
+      false => CONTINUATION_PROMPT,
+    };
+    match editor.readline(prompt) {
+      Ok(line) => {
+        buffer.push_str(&line);
+        buffer.push('\n');
+      }
+      // Ctrl-C abandons the current input
+      Err(ReadlineError::Interrupted) => {
+        buffer.clear();
+        continue;
+      }
+      Err(ReadlineError::Eof) => return Ok(()),
+      Err(e) => return Err(CliError::failure(format!("Failed to read input: {}.", e))),
+    }
+
+    match repl.enter(&buffer) {
+      Outcome::Incomplete => continue,
+      Outcome::Output(s) if s.is_empty() => (),
+      Outcome::Output(s) => println!("{}", s),
+      Outcome::Error(e) => e.report(),
+      Outcome::Quit => return Ok(()),
+    }
+    let _ = editor.add_history_entry(buffer.trim_end());
+    buffer.clear();
+  }
+}
@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+
+use funki_lang::{Argument, BlankCustom, Language, ReturnVal};
+
+use crate::args::Args;
+use crate::error::CliError;
+use crate::limits::limits;
+use crate::watch::{hash, Watcher};
+use crate::{json, read_source};
+
+// How a returned value is printed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Output {
+  // The value as text, strings are printed without quotes
+  Text,
+  // The value as JSON
+  Json,
+}
+
+impl Output {
+  pub fn from_args(args: &Args) -> Result<Self, CliError> {
+    match args.option("output").unwrap_or("text") {
+      "text" => Ok(Output::Text),
+      "json" => Ok(Output::Json),
+      o => Err(CliError::usage(format!(
+        "Unknown output format `{}`, expected `text` or `json`.",
+        o
+      ))),
+    }
+  }
+
+  // Formats a value in this format
+  pub fn format(&self, val: &ReturnVal<BlankCustom>) -> String {
+    match self {
+      Output::Text => val.to_string(),
+      Output::Json => json::from_return_val(val).to_string(),
+    }
+  }
+}
+
+// The argument for the call, from `--arg-json` or `--args-file`
+pub fn call_argument(args: &Args) -> Result<Option<Argument<BlankCustom>>, CliError> {
+  match (args.option("arg-json"), args.option("args-file")) {
+    (Some(_), Some(_)) => Err(CliError::usage(
+      "Only one of `--arg-json` and `--args-file` can be given.".to_string(),
+    )),
+    (Some(text), None) => json::to_call_argument(&json::parse(text, "--arg-json")?).map(Some),
+    (None, Some(path)) => {
+      json::to_call_argument(&json::parse(&read_source(path)?, path)?).map(Some)
+    }
+    (None, None) => Ok(None),
+  }
+}
+
+// Parses a script and calls the function, returning its formatted result
+fn run_once(args: &Args, lang: &Language<BlankCustom>, output: Output) -> Result<String, CliError> {
+  let arg = call_argument(args)?;
+  let script = lang.parse(read_source(args.positional(0, "file")?)?)?;
+  let mut func = script.function(args.option("fn").unwrap_or("main"))?;
+  if let Some(arg) = arg {
+    func = func.arg(arg);
+  }
+  Ok(output.format(&func.call()?))
+}
+
+// `funki run <file>`: parses a script, calls one of its functions and prints the result
+// With `--watch`, runs it again whenever the script or the arguments file change, until stopped
+pub fn run(args: Vec<String>) -> Result<(), CliError> {
+  let args = Args::parse(
+    args,
+    &["fn", "arg-json", "args-file", "output", "timeout"],
+    &["check-types", "watch"],
+  )?;
+  let path = args.positional(0, "file")?;
+  let output = Output::from_args(&args)?;
+
+  let mut lang = Language::<BlankCustom>::new();
+  lang.set_limits(limits(&args)?);
+  if args.flag("check-types") {
+    lang.enable_type_checking();
+  }
+  if !args.flag("watch") {
+    println!("{}", run_once(&args, &lang, output)?);
+    return Ok(());
+  }
+
+  if path == "-" || args.option("args-file") == Some("-") {
+    return Err(CliError::usage("Can't watch stdin.".to_string()));
+  }
+  let files: Vec<PathBuf> = [Some(path), args.option("args-file")]
+    .into_iter()
+    .flatten()
+    .map(PathBuf::from)
+    .collect();
+  let mut watcher = Watcher::new();
+  watcher.changed(&files);
+  // The inputs of the last run, which is not repeated if they are unchanged
+  let mut last = None;
+  loop {
+    let inputs: Vec<String> = files
+      .iter()
+      .map(|f| std::fs::read_to_string(f).unwrap_or_default())
+      .collect();
+    let key = hash(&inputs.iter().map(|i| i.as_str()).collect::<Vec<_>>());
+    if last != Some(key) {
+      last = Some(key);
+      match run_once(&args, &lang, output) {
+        Ok(out) => println!("{}", out),
+        Err(e) => e.report(),
+      }
+    }
+    for changed in watcher.wait(|| files.clone()) {
+      println!("\n{} changed.", changed.display());
+    }
+  }
+}
@@ -0,0 +1,269 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::Duration;
+
+use funki_lang::limits::Limits;
+use funki_lang::{Argument, BlankCustom, ErrorKind, Language, ReturnVal};
+
+use crate::args::Args;
+use crate::error::{panic_message, CliError};
+use crate::limits::limits;
+use crate::render::load_data;
+use crate::{json, read_source};
+
+// The port the server listens on without `--port`
+const DEFAULT_PORT: u16 = 8000;
+
+// How long a request's call can run for without `--timeout`, so a page that never finishes
+//  doesn't keep a thread busy
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// The content types of responses
+const HTML: &str = "text/html; charset=utf-8";
+const JSON: &str = "application/json";
+
+// A response to a request
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+  pub status: u16,
+  pub content_type: &'static str,
+  pub body: String,
+}
+
+impl Response {
+  fn new(status: u16, content_type: &'static str, body: String) -> Self {
+    Response {
+      status,
+      content_type,
+      body,
+    }
+  }
+
+  // Writes the response as HTTP, leaving out the body for `HEAD` requests
+  fn write_to(&self, stream: &mut impl Write, head: bool) -> std::io::Result<()> {
+    let reason = match self.status {
+      200 => "OK",
+      400 => "Bad Request",
+      404 => "Not Found",
+      405 => "Method Not Allowed",
+      _ => "Internal Server Error",
+    };
+    write!(
+      stream,
+      "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+      self.status,
+      reason,
+      self.content_type,
+      self.body.len()
+    )?;
+    if !head {
+      stream.write_all(self.body.as_bytes())?;
+    }
+    stream.flush()
+  }
+}
+
+// The function a URL path is mapped to, `/` is `main` and `/name` is `name`
+pub fn function_name(path: &str) -> Option<&str> {
+  match path.trim_matches('/') {
+    "" => Some("main"),
+    p if p.contains('/') => None,
+    p => Some(p),
+  }
+}
+
+// Decodes the `%XX` escapes and `+`s of a part of a query string
+fn decode(text: &str) -> String {
+  let bytes = text.as_bytes();
+  let mut res = vec![];
+  let mut i = 0;
+  while i < bytes.len() {
+    let hex = text
+      .get(i + 1..i + 3)
+      .and_then(|h| u8::from_str_radix(h, 16).ok());
+    match (bytes[i], hex) {
+      (b'%', Some(b)) => {
+        res.push(b);
+        i += 2;
+      }
+      (b'+', _) => res.push(b' '),
+      (b, _) => res.push(b),
+    }
+    i += 1;
+  }
+  String::from_utf8_lossy(&res).into_owned()
+}
+
+// Converts a query string to a list of `(name, value)` tuples, in the order they are given
+// Values that are whole numbers become ints and `true` and `false` become bools, the rest are
+//  strings
+pub fn query_argument(query: &str) -> Argument<BlankCustom> {
+  let params = query
+    .split('&')
+    .filter(|p| !p.is_empty())
+    .map(|p| {
+      let (name, value) = p.split_once('=').unwrap_or((p, ""));
+      let value = decode(value);
+      let value = match value.as_str() {
+        "true" => Argument::Bool(true),
+        "false" => Argument::Bool(false),
+        v => v
+          .parse()
+          .map_or_else(|_| Argument::String(value.clone()), Argument::Int),
+      };
+      Argument::Tuple(vec![Argument::String(decode(name)), value])
+    })
+    .collect();
+  Argument::List(params)
+}
+
+// Calls the function for a request target, such as `/post?id=2`, with the data and the query
+//  parameters as a `(data, params)` tuple. The data is `()` without a data file.
+// Strings are sent as HTML and other values as JSON, errors and panics are sent as an HTML page
+pub fn respond(path: &str, data_path: Option<&str>, limits: &Limits, target: &str) -> Response {
+  let (url, query) = target.split_once('?').unwrap_or((target, ""));
+  let name = match function_name(url) {
+    Some(n) => n,
+    None => {
+      let message = format!("No function for `{}`, paths are `/` or `/<function>`.", url);
+      return Response::new(404, HTML, error_page("not found", target, &message));
+    }
+  };
+
+  let res = (|| {
+    let data = match data_path {
+      Some(d) => json::to_argument(&load_data(d)?)?,
+      None => Argument::Tuple(vec![]),
+    };
+    let mut lang = Language::<BlankCustom>::new();
+    lang.set_limits(limits.clone());
+    let script = lang.parse(read_source(path)?)?;
+    let func = script
+      .function(name)?
+      .arg(Argument::Tuple(vec![data, query_argument(query)]));
+    let res = catch_unwind(AssertUnwindSafe(|| func.call()))
+      .map_err(|p| CliError::failure(format!("`{}` panicked: {}.", name, panic_message(&*p))))?;
+    Ok::<_, CliError>(res?)
+  })();
+
+  match res {
+    Ok(ReturnVal::String(s)) => Response::new(200, HTML, s),
+    Ok(v) => Response::new(200, JSON, json::from_return_val(&v).to_string()),
+    Err(e) => {
+      let (status, title) = match &e {
+        CliError::Language(l) if l.kind() == ErrorKind::UnknownFunction => {
+          (404, l.kind().to_string())
+        }
+        CliError::Language(l) => (500, l.kind().to_string()),
+        _ => (500, "error".to_string()),
+      };
+      Response::new(status, HTML, error_page(&title, target, &e.render(false)))
+    }
+  }
+}
+
+// A page showing an error, with the request it happened for
+pub fn error_page(title: &str, target: &str, message: &str) -> String {
+  format!(
+    "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+pre {{ background: #fdf2f2; border-left: 4px solid #c0392b; padding: 1em; overflow-x: auto; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p>While rendering <code>{target}</code></p>
+<pre>{message}</pre>
+</body>
+</html>
+",
+    title = escape(title),
+    target = escape(target),
+    message = escape(message.trim_end()),
+  )
+}
+
+// Escapes the characters with a meaning in HTML
+fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+// Reads a request and sends the response for it
+pub fn handle(
+  stream: &mut (impl Read + Write),
+  path: &str,
+  data_path: Option<&str>,
+  limits: &Limits,
+) -> std::io::Result<()> {
+  let mut reader = BufReader::new(&mut *stream);
+  let mut request = String::new();
+  reader.read_line(&mut request)?;
+  // The headers are not used, but are read so the client is not cut off while sending them
+  let mut header = String::new();
+  while reader.read_line(&mut header)? > 2 {
+    header.clear();
+  }
+
+  let mut parts = request.split_whitespace();
+  let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+  let response = match method {
+    "GET" | "HEAD" => respond(path, data_path, limits, target),
+    "" => Response::new(400, HTML, error_page("bad request", "", "Empty request.")),
+    m => {
+      let message = format!("`{}` requests are not supported, only `GET` and `HEAD`.", m);
+      Response::new(
+        405,
+        HTML,
+        error_page("method not allowed", target, &message),
+      )
+    }
+  };
+  println!("{} {} {}", method, target, response.status);
+  response.write_to(stream, method == "HEAD")
+}
+
+// `funki serve <file>`: serves the functions of a script on localhost, re-reading the script and
+//  data for each request so changes show up when the page is reloaded
+pub fn serve(args: Vec<String>) -> Result<(), CliError> {
+  let args = Args::parse(args, &["data", "port", "timeout"], &[])?;
+  let path = args.positional(0, "file")?;
+  if path == "-" {
+    return Err(CliError::usage(
+      "Can't serve stdin, as the script is read for each request.".to_string(),
+    ));
+  }
+  let port = match args.option("port") {
+    Some(p) => p
+      .parse()
+      .map_err(|_| CliError::usage(format!("`{}` is not a port number.", p)))?,
+    None => DEFAULT_PORT,
+  };
+  let mut limits = limits(&args)?;
+  limits.timeout = limits.timeout.or(Some(DEFAULT_TIMEOUT));
+
+  let listener = TcpListener::bind(("127.0.0.1", port))
+    .map_err(|e| CliError::failure(format!("Failed to listen on port {}: {}.", port, e)))?;
+  println!("Serving {} on http://localhost:{}/", path, port);
+  std::thread::scope(|s| {
+    for stream in listener.incoming().flatten() {
+      let (data, limits) = (args.option("data"), &limits);
+      s.spawn(move || {
+        let mut stream = stream;
+        if let Err(e) = handle(&mut stream, path, data, limits) {
+          eprintln!("error: Failed to respond: {}.", e);
+        }
+      });
+    }
+  });
+  Ok(())
+}
@@ -0,0 +1,587 @@
+// Tests options, flags and positional arguments are split up
+#[test]
+fn test_args() {
+  use crate::args::Args;
+  let args = Args::parse(
+    ["file.funki", "--fn", "greet", "--output=json", "--check"].map(String::from),
+    &["fn", "output"],
+    &["check"],
+  )
+  .unwrap();
+  assert_eq!(args.positional(0, "file").unwrap(), "file.funki");
+  assert_eq!(args.option("fn"), Some("greet"));
+  assert_eq!(args.option("output"), Some("json"));
+  assert!(args.flag("check"));
+  assert!(args.positional(1, "other").is_err());
+
+  let err = Args::parse(["--fn"].map(String::from), &["fn"], &[]).unwrap_err();
+  assert_eq!(err.exit_code(), crate::error::EXIT_USAGE);
+  let err = Args::parse(["--nope"].map(String::from), &[], &[]).unwrap_err();
+  assert_eq!(err.exit_code(), crate::error::EXIT_USAGE);
+}
+
+// Tests JSON arguments are converted and passed to the function
+#[test]
+fn test_json_arguments() {
+  use crate::json;
+  use funki_lang::{BlankCustom, Script};
+  let script = Script::<BlankCustom>::from_text(
+    "#main (name, n, loud, extra) -> (f\"{name} {n}\"f, loud, len(extra));
+     #single x -> x;",
+  )
+  .unwrap();
+
+  let arg = json::to_call_argument(
+    &json::parse(r#"["Alfie", 5, true, {"a": null, "b": [1, 2]}]"#, "test").unwrap(),
+  )
+  .unwrap();
+  let res = script.function("main").unwrap().arg(arg).call().unwrap();
+  assert_eq!(
+    json::from_return_val(&res).to_string(),
+    r#"["Alfie 5",true,2]"#
+  );
+
+  // Objects are lists of key value pairs
+  let arg = json::to_call_argument(&json::parse(r#"[{"a": 1}]"#, "test").unwrap()).unwrap();
+  let res = script.function("single").unwrap().arg(arg).call().unwrap();
+  assert_eq!(json::from_return_val(&res).to_string(), r#"[["a",1]]"#);
+
+  assert!(json::to_call_argument(&json::parse("5", "test").unwrap()).is_err());
+  assert!(json::to_call_argument(&json::parse("[5000000000]", "test").unwrap()).is_err());
+  assert!(json::parse("[1,", "test").is_err());
+}
+
+// Tests each kind of script error exits with its own code
+#[test]
+fn test_exit_codes() {
+  use crate::error::CliError;
+  use funki_lang::{BlankCustom, Language, Script};
+  let parse_err = Language::<BlankCustom>::new()
+    .parse("#main 1 +".to_string())
+    .unwrap_err();
+  let script = Script::<BlankCustom>::from_text("#main x;").unwrap();
+  let unknown_fn = script.function("other").err().unwrap();
+  let unknown_var = script.function("main").unwrap().call().unwrap_err();
+
+  let codes = [parse_err, unknown_fn, unknown_var].map(|e| CliError::from(e).exit_code());
+  assert!(codes.iter().all(|c| *c >= 10));
+  assert_ne!(codes[0], codes[1]);
+  assert_ne!(codes[1], codes[2]);
+  assert_ne!(codes[0], codes[2]);
+}
+
+// Tests scripts that never finish stop with a limit error, which has its own exit code
+#[test]
+fn test_limits() {
+  use crate::args::Args;
+  use crate::error::CliError;
+  use crate::limits::{limits, MAX_STEPS};
+  use funki_lang::{BlankCustom, ErrorKind, Language};
+  use std::time::Duration;
+  let args = Args::parse(["--timeout", "0.05"].map(String::from), &["timeout"], &[]).unwrap();
+  let limits = limits(&args).unwrap();
+  assert_eq!(limits.timeout, Some(Duration::from_millis(50)));
+  assert_eq!(limits.max_steps, Some(MAX_STEPS));
+  assert!(limits.max_depth.is_some());
+
+  // A smaller depth, as unoptimised builds use more stack for each call than a test thread has
+  let mut lang = Language::<BlankCustom>::new();
+  lang.set_limits(funki_lang::limits::Limits {
+    max_depth: Some(20),
+    ..limits
+  });
+  let script = lang
+    .parse(
+      "#main x -> main(x);
+       #fib n -> fib(n - 1) + fib(n - 2) | n > 1;
+         n -> n;"
+        .to_string(),
+    )
+    .unwrap();
+  let call = |name| {
+    let func = script.function(name).unwrap();
+    let err = func.arg(funki_lang::Argument::Int(40)).call().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::LimitExceeded(_)));
+    CliError::from(err).exit_code()
+  };
+  assert_eq!(call("main"), 17);
+  assert_eq!(call("fib"), 17);
+
+  for bad in ["0", "-1", "soon"] {
+    let args = Args::parse(["--timeout", bad].map(String::from), &["timeout"], &[]).unwrap();
+    assert!(crate::limits::limits(&args).is_err());
+  }
+}
+
+// Tests definitions persist between inputs and unfinished input waits for more lines
+#[test]
+fn test_repl() {
+  use crate::repl::{Outcome, Repl};
+  use funki_lang::{ErrorKind, Language};
+  let mut repl = Repl::new(Language::new());
+  let output = |o: Outcome| match o {
+    Outcome::Output(s) => s,
+    o => panic!("Expected output, found {:?}", o),
+  };
+
+  assert_eq!(output(repl.enter("#double x -> x * 2;")), "Defined double.");
+  assert!(matches!(
+    repl.enter("#add (a, b) ->\n"),
+    Outcome::Incomplete
+  ));
+  assert_eq!(
+    output(repl.enter("#add (a, b) ->\n  a + b;\n")),
+    "Defined add."
+  );
+  assert!(matches!(repl.enter("double(add(1,\n"), Outcome::Incomplete));
+  assert_eq!(output(repl.enter("double(add(1,\n 2))\n")), "6");
+  assert_eq!(output(repl.enter("(\"a\", list(1))")), "(\"a\", [1])");
+
+  assert_eq!(output(repl.enter(":list")), "add\ndouble");
+  assert_eq!(output(repl.enter(":type add")), "(Int, Int) -> Int");
+  assert_eq!(output(repl.enter(":type double(2)")), "Int");
+
+  // Redefining a function replaces it
+  repl.enter("#double x -> x + x;");
+  assert_eq!(output(repl.enter("double(\"a\")")), "\"aa\"");
+
+  match repl.enter("#f x -> x +;") {
+    Outcome::Error(crate::error::CliError::Language(e)) => {
+      assert_eq!(e.kind(), ErrorKind::ParseError)
+    }
+    o => panic!("Expected an error, found {:?}", o),
+  }
+  assert!(matches!(repl.enter("nope(1)"), Outcome::Error(_)));
+  assert!(matches!(repl.enter(":quit"), Outcome::Quit));
+}
+
+// Tests scripts are formatted, keeping their comments
+#[test]
+fn test_fmt() {
+  use crate::fmt::format_code;
+  assert_eq!(
+    format_code("#main x ->  f(x) ; // call\n#f x ->x+1;".to_string()).unwrap(),
+    "#main x -> f(x); // call\n\n#f x -> x + 1;\n"
+  );
+  assert!(format_code("#main x -> ;".to_string()).is_err());
+}
+
+// Tests the language server reports errors and answers requests about the open document
+#[test]
+fn test_lsp() {
+  use crate::lsp::{offset_of, position_of, Server};
+  use funki_lang::Language;
+  use serde_json::json;
+  let mut server = Server::new(Language::new());
+  let mut request = |id: u32, method: &str, params: serde_json::Value| {
+    let msg = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+    server.handle(&msg).unwrap()
+  };
+  let doc = json!({ "uri": "file:///a.funki" });
+
+  let init = request(1, "initialize", json!({}));
+  assert_eq!(init[0]["result"]["capabilities"]["hoverProvider"], true);
+
+  let open = |text: &str| json!({ "textDocument": { "uri": "file:///a.funki", "text": text } });
+  let res = request(0, "textDocument/didOpen", open("#main x -> x +;"));
+  assert_eq!(res[0]["method"], "textDocument/publishDiagnostics");
+  assert_eq!(res[0]["params"]["diagnostics"][0]["severity"], 1);
+
+  let code = "#main x -> double(x);\n#double y -> y * 2;";
+  let res = request(0, "textDocument/didOpen", open(code));
+  assert_eq!(res[0]["params"]["diagnostics"], json!([]));
+
+  // `double` in the call to it
+  let at = json!({ "textDocument": doc, "position": { "line": 0, "character": 13 } });
+  let res = request(2, "textDocument/definition", at.clone());
+  assert_eq!(
+    res[0]["result"]["range"],
+    json!({ "start": { "line": 1, "character": 0 }, "end": { "line": 1, "character": 7 } })
+  );
+  let res = request(3, "textDocument/hover", at);
+  let hover = res[0]["result"]["contents"]["value"].as_str().unwrap();
+  assert!(hover.contains("#double y -> y * 2;"));
+  assert!(hover.contains("double: Int -> Int"));
+
+  let at = json!({ "textDocument": doc, "position": { "line": 0, "character": 18 } });
+  let res = request(4, "textDocument/completion", at);
+  let labels: Vec<&str> = res[0]["result"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .map(|i| i["label"].as_str().unwrap())
+    .collect();
+  assert_eq!(labels[..3], ["x", "double", "main"]);
+  assert!(labels.contains(&"len"));
+
+  let res = request(
+    5,
+    "textDocument/documentSymbol",
+    json!({ "textDocument": doc }),
+  );
+  assert_eq!(res[0]["result"][1]["name"], "double");
+
+  // After an edit that doesn't parse, the position is in the scope of `y` in the last script
+  let code = "#double y -> y * 2;\n#main x -> double(";
+  let change = json!({ "textDocument": doc, "contentChanges": [{ "text": code }] });
+  request(0, "textDocument/didChange", change);
+  let at = json!({ "textDocument": doc, "position": { "line": 1, "character": 18 } });
+  let res = request(5, "textDocument/completion", at);
+  let labels: Vec<&str> = res[0]["result"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .map(|i| i["label"].as_str().unwrap())
+    .collect();
+  assert_eq!(labels[..2], ["double", "main"]);
+  assert!(!labels.contains(&"x") && !labels.contains(&"y"));
+
+  // Once an edit stops the code parsing, the spans of the last script no longer match the text
+  let change = json!({ "textDocument": doc, "contentChanges": [{ "text": "ééééééééé" }] });
+  let res = request(0, "textDocument/didChange", change);
+  assert_eq!(res[0]["params"]["diagnostics"][0]["severity"], 1);
+  let res = request(
+    5,
+    "textDocument/documentSymbol",
+    json!({ "textDocument": doc }),
+  );
+  assert_eq!(res[0]["result"], json!([]));
+  let at = json!({ "textDocument": doc, "position": { "line": 0, "character": 3 } });
+  let res = request(5, "textDocument/hover", at.clone());
+  assert_eq!(res[0]["result"], json!(null));
+  let res = request(5, "textDocument/completion", at);
+  assert!(res[0]["result"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .any(|i| i["label"] == "double"));
+
+  let res = request(6, "textDocument/formatting", json!({}));
+  assert_eq!(res[0]["error"]["code"], -32601);
+  request(7, "shutdown", json!(null));
+  assert!(server.handle(&json!({ "method": "exit" })).is_none());
+  assert!(server.shut_down());
+
+  let text = "ab\n\u{1F600}c";
+  assert_eq!(position_of(text, 8), json!({ "line": 1, "character": 3 }));
+  assert_eq!(offset_of(text, 1, 3), 8);
+  assert_eq!(offset_of(text, 5, 0), text.len());
+}
+
+// Tests the test functions in a script are found and run separately
+#[test]
+fn test_unit_tests() {
+  use crate::unit_tests::{discover, run_test, Outcome};
+  use funki_lang::{BlankCustom, Script};
+  let script = Script::<BlankCustom>::from_text(
+    "#test_double assert_eq(double(2), 4);
+#double x -> x * 2;
+#test_false double(1) == 3;
+#test_diff assert_eq(list(double(1), double(2)), list(2, 5));
+#test_error double(\"a\", 1);",
+  )
+  .unwrap();
+
+  assert_eq!(
+    discover(&script, ""),
+    ["test_double", "test_false", "test_diff", "test_error"]
+  );
+  assert_eq!(discover(&script, "d"), ["test_double", "test_diff"]);
+  assert_eq!(run_test(&script, "test_double", false), Outcome::Passed);
+  assert_eq!(
+    run_test(&script, "test_false", false),
+    Outcome::Failed("`test_false` returned false.\n".to_string())
+  );
+  match run_test(&script, "test_diff", false) {
+    Outcome::Failed(report) => assert!(report.starts_with(
+      "error[assertion failed]: Assertion failed at `[1]`, expected 5 but found 4.\n --> 4:22"
+    )),
+    o => panic!("Expected a failure, found {:?}", o),
+  }
+  assert!(matches!(
+    run_test(&script, "test_error", false),
+    Outcome::Failed(_)
+  ));
+
+  // Tests that panic or never finish fail without stopping the tests after them
+  let args = crate::args::Args::parse(Vec::<String>::new(), &[], &[]).unwrap();
+  let mut lang = funki_lang::Language::<BlankCustom>::new();
+  lang.set_limits(funki_lang::limits::Limits {
+    max_depth: Some(20),
+    ..crate::limits::limits(&args).unwrap()
+  });
+  let script = lang
+    .parse(
+      "#test_a 1 == 1;
+#test_panic get(list(1), -1);
+#test_loop loop(1);
+#loop x -> 1 + loop(x);
+#test_c assert_eq(1, 2);"
+        .to_string(),
+    )
+    .unwrap();
+  let outcomes: Vec<Outcome> = discover(&script, "")
+    .iter()
+    .map(|t| run_test(&script, t, false))
+    .collect();
+  assert_eq!(outcomes[0], Outcome::Passed);
+  match &outcomes[1] {
+    Outcome::Failed(report) => assert!(report.starts_with("`test_panic` panicked: "), "{}", report),
+    o => panic!("Expected a failure, found {:?}", o),
+  }
+  match &outcomes[2] {
+    Outcome::Failed(report) => assert!(report.contains("limit"), "{}", report),
+    o => panic!("Expected a failure, found {:?}", o),
+  }
+  assert!(matches!(outcomes[3], Outcome::Failed(_)));
+}
+
+// Tests reference pages are titled with the script's file name
+#[test]
+fn test_doc() {
+  use crate::doc::reference;
+  use funki_lang::DocFormat;
+  let code = "/// Says hello\n#main f\"Hello {name}\"f;".to_string();
+  let page = reference("pages/hello.funki", code.clone(), DocFormat::Markdown).unwrap();
+  assert!(page.starts_with("# hello\n\n## Functions\n\n### `main`\n"));
+  assert!(page.contains("\nSays hello\n"));
+  let page = reference("-", code, DocFormat::Html).unwrap();
+  assert!(page.contains("<h1>stdin</h1>"));
+}
+
+// Tests the tree of a script is printed as JSON with the spans of its parts
+#[test]
+fn test_ast() {
+  use crate::ast::ast_json;
+  let json: serde_json::Value =
+    serde_json::from_str(&ast_json("#main -x;".to_string()).unwrap()).unwrap();
+  let main = &json["functions"][0];
+  assert_eq!(main["name"], "main");
+  assert_eq!(main["patterns"][0]["args"], serde_json::Value::Null);
+  let result = &main["patterns"][0]["result"];
+  assert_eq!(result["kind"]["Unary"]["op"], "Neg");
+  assert_eq!(result["kind"]["Unary"]["expr"]["kind"]["Var"], "x");
+  assert_eq!(result["span"]["start"], 6);
+  assert_eq!(result["span"]["end_col"], 9);
+  assert!(ast_json("#main (;".to_string()).is_err());
+}
+
+// Tests a directory of templates is rendered, with the errors of each template kept separate
+#[test]
+fn test_render() {
+  use crate::render::{load_data, output_path, render_all, render_template, templates};
+  use std::path::Path;
+  let dir = std::env::temp_dir().join(format!("funki-render-{}", std::process::id()));
+  let write = |path: &str, text: &str| {
+    let path = dir.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, text).unwrap();
+  };
+  write(
+    "site/index.html.funki",
+    "#main data -> f\"<h1>{lookup(data, \"title\")}</h1>\"f;
+#lookup (data, key) -> get(map(filter(data, |(k, v) => k == key|), |(k, v) => v|), 0);",
+  );
+  write("site/posts/a.txt.funki", "#main data -> len(data);");
+  write("site/posts/b.txt.funki", "#main d -> \"b\";");
+  write("site/posts/c.txt.funki", "#main d -> get(list(1), -1);");
+  write("site/posts/d.txt.funki", "#main d -> main(d);");
+  write("site/notes.md", "not a template");
+  write("data.toml", "title = \"Home\"\nposts = [1, 2]\n");
+
+  let site = dir.join("site");
+  // Links to directories aren't followed, so a link back to the templates doesn't loop forever
+  #[cfg(unix)]
+  std::os::unix::fs::symlink(&site, site.join("posts/site")).unwrap();
+  let found = templates(&site).unwrap();
+  assert_eq!(
+    found,
+    [
+      "index.html.funki",
+      "posts/a.txt.funki",
+      "posts/b.txt.funki",
+      "posts/c.txt.funki",
+      "posts/d.txt.funki"
+    ]
+    .map(|t| site.join(t))
+  );
+  assert_eq!(
+    output_path(&site, Path::new("out"), &found[1]),
+    Path::new("out/posts/a.txt")
+  );
+
+  // TOML tables are passed as lists of (key, value) tuples, like JSON objects
+  let data = load_data(dir.join("data.toml").to_str().unwrap()).unwrap();
+  let args = crate::args::Args::parse(Vec::<String>::new(), &[], &[]).unwrap();
+  let limits = funki_lang::limits::Limits {
+    max_depth: Some(20),
+    ..crate::limits::limits(&args).unwrap()
+  };
+  let results = render_all(&found, 2, |t| {
+    render_template(std::fs::read_to_string(t).unwrap(), Some(&data), &limits)
+  });
+  assert_eq!(results[0].as_ref().unwrap(), "<h1>Home</h1>");
+  assert_eq!(
+    results[1].as_ref().unwrap_err().to_string(),
+    "error: `main` returned 2 rather than a string."
+  );
+  assert_eq!(results[2].as_ref().unwrap(), "b");
+  // A template that panics or never finishes fails without stopping the others
+  assert!(results[3]
+    .as_ref()
+    .unwrap_err()
+    .to_string()
+    .starts_with("error: `main` panicked: "));
+  assert!(matches!(
+    results[4].as_ref().unwrap_err(),
+    crate::error::CliError::Language(_)
+  ));
+  assert!(load_data(dir.join("site/notes.md").to_str().unwrap()).is_err());
+  std::fs::remove_dir_all(dir).unwrap();
+}
+
+// Tests watch mode notices changed files and only renders the templates that changed
+#[test]
+fn test_watch() {
+  use crate::render::render_changed;
+  use crate::watch::{Cache, Watcher};
+  use funki_lang::limits::Limits;
+  use serde_json::json;
+  let dir = std::env::temp_dir().join(format!("funki-watch-{}", std::process::id()));
+  let (templates, out) = (dir.join("templates"), dir.join("out"));
+  std::fs::create_dir_all(&templates).unwrap();
+  let a = templates.join("a.txt.funki");
+  std::fs::write(&a, "#main d -> f\"a {len(d)}\"f;").unwrap();
+  std::fs::write(templates.join("b.txt.funki"), "#main d -> d +;").unwrap();
+
+  let files = vec![a.clone()];
+  let mut watcher = Watcher::new();
+  assert_eq!(watcher.changed(&files), files);
+  assert!(watcher.changed(&files).is_empty());
+
+  let mut cache = Cache::new();
+  let limits = Limits::default();
+  let data = json!({ "x": 1 });
+  let (rendered, errors) =
+    render_changed(&templates, &out, Some(&data), &limits, 2, &mut cache).unwrap();
+  assert_eq!(rendered, 1);
+  assert_eq!(errors.len(), 1);
+  assert!(errors[0].1.starts_with("error[parse error]"));
+  assert_eq!(std::fs::read_to_string(out.join("a.txt")).unwrap(), "a 1");
+
+  // Nothing changed, so nothing is rendered but the error is still reported
+  let (rendered, errors) =
+    render_changed(&templates, &out, Some(&data), &limits, 2, &mut cache).unwrap();
+  assert_eq!((rendered, errors.len()), (0, 1));
+
+  std::fs::write(&a, "#main d -> f\"a {len(d)}!\"f;").unwrap();
+  assert_eq!(watcher.changed(&files), files);
+  let (rendered, _) =
+    render_changed(&templates, &out, Some(&data), &limits, 2, &mut cache).unwrap();
+  assert_eq!(rendered, 1);
+  assert_eq!(std::fs::read_to_string(out.join("a.txt")).unwrap(), "a 1!");
+
+  // Changing the data renders every template, only counting those that succeeded
+  let data = json!({ "x": 1, "y": 2 });
+  let (rendered, errors) =
+    render_changed(&templates, &out, Some(&data), &limits, 2, &mut cache).unwrap();
+  assert_eq!((rendered, errors.len()), (1, 1));
+  assert_eq!(std::fs::read_to_string(out.join("a.txt")).unwrap(), "a 2!");
+
+  std::fs::remove_file(&a).unwrap();
+  assert_eq!(watcher.changed(&files), files);
+  std::fs::remove_dir_all(dir).unwrap();
+}
+
+// Tests URL paths are mapped to functions called with the data and query parameters
+#[test]
+fn test_serve() {
+  use crate::serve::{function_name, handle, query_argument, respond};
+  use funki_lang::limits::Limits;
+  use std::io::{Read, Write};
+  use std::time::Duration;
+  let dir = std::env::temp_dir().join(format!("funki-serve-{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let (script, data) = (dir.join("site.funki"), dir.join("data.json"));
+  std::fs::write(
+    &script,
+    "#main (site, params) -> f\"<h1>{site}</h1>\"f;
+#page (_, params) -> f\"page {param(params, \"n\") + 1} {param(params, \"q\")}\"f;
+#param (params, name) -> get(map(filter(params, |(n, v) => n == name|), |(n, v) => v|), 0);
+#count (_, params) -> len(params);
+#broken (_, params) -> params + 1;
+#crash (_, params) -> get(list(1), -1);
+#slow (_, params) -> fib(22);
+#fib n -> fib(n - 1) + fib(n - 2) | n > 1;
+  n -> n;",
+  )
+  .unwrap();
+  std::fs::write(&data, "\"Home & <away>\"").unwrap();
+  let (script, data) = (script.to_str().unwrap(), data.to_str().unwrap());
+  let limits = Limits {
+    timeout: Some(Duration::from_millis(50)),
+    ..Limits::default()
+  };
+  let respond = |data, target| respond(script, data, &limits, target);
+
+  assert_eq!(function_name("/"), Some("main"));
+  assert_eq!(function_name("/page/"), Some("page"));
+  assert_eq!(function_name("/a/b"), None);
+  assert_eq!(
+    format!("{:?}", query_argument("q=a+b%21&n=2&on=true&empty")),
+    "List([Tuple([String(\"q\"), String(\"a b!\")]), Tuple([String(\"n\"), Int(2)]), \
+     Tuple([String(\"on\"), Bool(true)]), Tuple([String(\"empty\"), String(\"\")])])"
+  );
+
+  let res = respond(Some(data), "/");
+  assert_eq!(
+    (res.status, res.content_type),
+    (200, "text/html; charset=utf-8")
+  );
+  assert_eq!(res.body, "<h1>Home & <away></h1>");
+  assert_eq!(respond(None, "/page?n=4&q=hi").body, "page 5 hi");
+  let res = respond(None, "/count?a=1&b=2");
+  assert_eq!(
+    (res.status, res.content_type, res.body.as_str()),
+    (200, "application/json", "2")
+  );
+
+  let res = respond(None, "/broken?x=1");
+  assert_eq!(res.status, 500);
+  assert!(res.body.contains("<title>type mismatch</title>"));
+  assert!(res
+    .body
+    .contains("<p>While rendering <code>/broken?x=1</code></p>"));
+  assert!(res.body.contains("#broken (_, params) -&gt; params + 1;"));
+  assert!(res.body.contains(" --&gt; 5:"));
+  assert_eq!(respond(None, "/nope").status, 404);
+  assert_eq!(respond(None, "/a/b").status, 404);
+
+  // Panics and calls that run for too long are sent as error pages
+  let res = respond(None, "/crash");
+  assert_eq!(res.status, 500);
+  assert!(res.body.contains("`crash` panicked: "));
+  let res = respond(None, "/slow");
+  assert_eq!(res.status, 500);
+  assert!(res.body.contains("<title>timeout exceeded</title>"));
+
+  // A request over a real connection
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+  let client = std::thread::spawn(move || {
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    stream
+      .write_all(b"GET /page?n=1&q=x HTTP/1.1\r\nHost: localhost\r\n\r\n")
+      .unwrap();
+    let mut res = String::new();
+    stream.read_to_string(&mut res).unwrap();
+    res
+  });
+  let (mut stream, _) = listener.accept().unwrap();
+  handle(&mut stream, script, None, &limits).unwrap();
+  drop(stream);
+  let res = client.join().unwrap();
+  assert!(res.starts_with("HTTP/1.1 200 OK\r\n"));
+  assert!(res.ends_with("\r\n\r\npage 2 x"));
+  std::fs::remove_dir_all(dir).unwrap();
+}
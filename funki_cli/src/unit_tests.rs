@@ -0,0 +1,96 @@
+use std::io::IsTerminal;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use funki_lang::{BlankCustom, Language, ReturnVal, Script};
+
+use crate::args::Args;
+use crate::error::{panic_message, CliError};
+use crate::limits::limits;
+use crate::read_source;
+
+// The prefix of the functions in a script that are run as tests
+const TEST_PREFIX: &str = "test_";
+
+// The result of running a test
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+  Passed,
+  // The test failed, with the report of why
+  Failed(String),
+}
+
+// Finds the tests in a script whose names contain `filter`, in the order they are defined
+pub fn discover(script: &Script<BlankCustom>, filter: &str) -> Vec<String> {
+  let order: Vec<String> = script.symbols().into_iter().map(|s| s.name).collect();
+  let mut tests: Vec<String> = script
+    .list()
+    .into_iter()
+    .filter(|n| n.starts_with(TEST_PREFIX) && n.contains(filter))
+    .collect();
+  tests.sort_by_key(|t| order.iter().position(|n| n == t));
+  tests
+}
+
+// Runs a test with no argument, it passes if it returns anything but `false` without an error
+// Each test is a separate call, so nothing one test does can affect another
+// A test that panics fails with the panic's message rather than stopping the other tests
+pub fn run_test(script: &Script<BlankCustom>, name: &str, colours: bool) -> Outcome {
+  let res = catch_unwind(AssertUnwindSafe(|| {
+    script.function(name).and_then(|f| f.call())
+  }));
+  match res {
+    Ok(Ok(ReturnVal::Bool(false))) => Outcome::Failed(format!("`{}` returned false.\n", name)),
+    Ok(Ok(_)) => Outcome::Passed,
+    Ok(Err(e)) => Outcome::Failed(e.render(colours)),
+    Err(p) => Outcome::Failed(format!("`{}` panicked: {}\n", name, panic_message(&*p))),
+  }
+}
+
+// `funki test <files>`: runs the `test_` functions in scripts and reports which failed
+pub fn test(args: Vec<String>) -> Result<(), CliError> {
+  let args = Args::parse(args, &["filter", "timeout"], &[])?;
+  let filter = args.option("filter").unwrap_or_default();
+  let mut lang = Language::<BlankCustom>::new();
+  lang.set_limits(limits(&args)?);
+  let colours = std::io::stdout().is_terminal();
+  let (mut passed, mut failed) = (0, 0);
+
+  for path in args.all_positional("files")? {
+    let script = lang.parse(read_source(path)?)?;
+    let tests = discover(&script, filter);
+    println!("running {} tests in {}", tests.len(), path);
+
+    let mut failures = vec![];
+    for name in tests {
+      match run_test(&script, &name, colours) {
+        Outcome::Passed => {
+          passed += 1;
+          println!("test {} ... ok", name);
+        }
+        Outcome::Failed(report) => {
+          failed += 1;
+          println!("test {} ... FAILED", name);
+          failures.push((name, report));
+        }
+      }
+    }
+    for (name, report) in failures {
+      print!("\n---- {} ----\n{}", name, report);
+    }
+    println!();
+  }
+
+  let result = match failed {
+    0 => "ok",
+    _ => "FAILED",
+  };
+  println!(
+    "test result: {}. {} passed; {} failed",
+    result, passed, failed
+  );
+  match failed {
+    0 => Ok(()),
+    1 => Err(CliError::failure("1 test failed.".to_string())),
+    n => Err(CliError::failure(format!("{} tests failed.", n))),
+  }
+}
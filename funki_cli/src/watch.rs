@@ -0,0 +1,109 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// How often watched files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Hashes the inputs of a run, so a result can be reused while they are unchanged
+pub fn hash(parts: &[&str]) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  parts.hash(&mut hasher);
+  hasher.finish()
+}
+
+// When a file was last modified and its size, or `None` if it does not exist
+type Stamp = Option<(SystemTime, u64)>;
+
+// Watches files for changes by checking when they were last modified, as std has no way to be
+//  notified of changes
+pub struct Watcher {
+  files: HashMap<PathBuf, Stamp>,
+}
+
+impl Watcher {
+  pub fn new() -> Self {
+    Watcher {
+      files: HashMap::new(),
+    }
+  }
+
+  // Checks the files in `paths`, returning those that were changed, created or removed since the
+  //  last check. Files no longer in `paths` stop being watched, and new ones count as changed.
+  pub fn changed(&mut self, paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut changed = vec![];
+    let mut files = HashMap::new();
+    for p in paths {
+      let stamp = stamp(p);
+      if self.files.get(p) != Some(&stamp) {
+        changed.push(p.clone());
+      }
+      files.insert(p.clone(), stamp);
+    }
+    changed.extend(
+      self
+        .files
+        .keys()
+        .filter(|p| !files.contains_key(*p))
+        .cloned(),
+    );
+    self.files = files;
+    changed
+  }
+
+  // Waits until a file changes, finding the files to watch with `paths` each time they are checked
+  //  so new files are noticed
+  pub fn wait(&mut self, paths: impl Fn() -> Vec<PathBuf>) -> Vec<PathBuf> {
+    loop {
+      std::thread::sleep(POLL_INTERVAL);
+      let changed = self.changed(&paths());
+      if !changed.is_empty() {
+        return changed;
+      }
+    }
+  }
+}
+
+// The modification time and size of a file
+fn stamp(path: &Path) -> Stamp {
+  let meta = std::fs::metadata(path).ok()?;
+  Some((meta.modified().ok()?, meta.len()))
+}
+
+// Results kept between runs in watch mode, each reused while the inputs it was made from, given
+//  by a hash of them, are unchanged
+pub struct Cache<R> {
+  entries: HashMap<PathBuf, (u64, R)>,
+}
+
+impl<R> Cache<R> {
+  pub fn new() -> Self {
+    Cache {
+      entries: HashMap::new(),
+    }
+  }
+
+  // The result for a path, if it was made from the same inputs
+  pub fn get(&self, path: &Path, key: u64) -> Option<&R> {
+    match self.entries.get(path) {
+      Some((k, r)) if *k == key => Some(r),
+      _ => None,
+    }
+  }
+
+  // The latest result for a path, whatever inputs it was made from
+  pub fn get_any(&self, path: &Path) -> Option<&R> {
+    self.entries.get(path).map(|(_, r)| r)
+  }
+
+  pub fn insert(&mut self, path: PathBuf, key: u64, result: R) {
+    self.entries.insert(path, (key, result));
+  }
+
+  // Forgets the results for paths that are not in `paths`
+  pub fn retain(&mut self, paths: &[PathBuf]) {
+    self.entries.retain(|p, _| paths.contains(p));
+  }
+}
@@ -0,0 +1,182 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::iter;
+
+use crate::ast::{Expr, ExprInner, Pattern, UnaryOp};
+
+// A simplified pattern for checking which arguments a function can take
+#[derive(Clone, PartialEq)]
+enum Pat {
+  Wild,
+  Tuple(Vec<Pat>),
+  Int(i32),
+  Str(String),
+  // An expression whose value is not known until the function is called
+  Opaque,
+}
+
+impl Display for Pat {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Pat::Wild | Pat::Opaque => write!(fmt, "_"),
+      Pat::Tuple(v) => write!(
+        fmt,
+        "({})",
+        v.iter()
+          .map(|p| p.to_string())
+          .collect::<Vec<_>>()
+          .join(", ")
+      ),
+      Pat::Int(i) => write!(fmt, "{}", i),
+      Pat::Str(s) => write!(fmt, "{:?}", s),
+    }
+  }
+}
+
+// Converts the start of a pattern into the simplified form
+// Tuples of one item are the same as the item, as they are when matching
+fn to_pat(e: &Expr) -> Pat {
+  match &e.val {
+    ExprInner::Var(_) => Pat::Wild,
+    ExprInner::Tuple(v) if v.len() == 1 => to_pat(&v[0]),
+    ExprInner::Tuple(v) => Pat::Tuple(v.iter().map(to_pat).collect()),
+    ExprInner::Number(n) => Pat::Int(*n),
+    ExprInner::Unary(UnaryOp::Neg, e) => match e.val {
+      ExprInner::Number(n) => Pat::Int(-n),
+      _ => Pat::Opaque,
+    },
+    ExprInner::Str(s) => Pat::Str(s.clone()),
+    _ => Pat::Opaque,
+  }
+}
+
+// Finds examples of arguments that none of the patterns of a function match, up to `max` of them
+// Patterns with guards are not counted as matching anything, as the guards can fail
+// The argument is assumed to be a tuple when any pattern is a tuple, and an int or string when
+//  the patterns are literals of that type
+pub fn missing_cases(patterns: &[Pattern], max: usize) -> Vec<String> {
+  let mut rows: Vec<Vec<Pat>> = patterns
+    .iter()
+    .filter(|p| p.guards.is_empty())
+    .map(|p| vec![to_pat(&p.start)])
+    .collect();
+
+  let mut found = vec![];
+  while found.len() < max {
+    match witness(&rows, 1) {
+      Some(mut w) => {
+        let w = w.remove(0);
+        found.push(w.to_string());
+        // Covering the example means the next search finds a different one
+        rows.push(vec![w]);
+      }
+      None => break,
+    }
+  }
+  found
+}
+
+// Finds the patterns of a function that take tuples of a different length to the first pattern
+//  that takes a tuple, with the length of each and the expected length
+pub fn arity_mismatches(patterns: &[Pattern]) -> Vec<(&Expr, usize, usize)> {
+  let arities: Vec<(&Expr, usize)> = patterns
+    .iter()
+    .filter_map(|p| match to_pat(&p.start) {
+      Pat::Tuple(v) => Some((&p.start, v.len())),
+      _ => None,
+    })
+    .collect();
+
+  match arities.first() {
+    Some((_, expected)) => arities
+      .iter()
+      .filter(|(_, a)| a != expected)
+      .map(|(e, a)| (*e, *a, *expected))
+      .collect(),
+    None => vec![],
+  }
+}
+
+// Finds a set of values, one for each of the `n` columns, that no row matches
+fn witness(rows: &[Vec<Pat>], n: usize) -> Option<Vec<Pat>> {
+  if n == 0 {
+    return if rows.is_empty() { Some(vec![]) } else { None };
+  }
+
+  let arities: BTreeSet<usize> = rows
+    .iter()
+    .filter_map(|r| match &r[0] {
+      Pat::Tuple(v) => Some(v.len()),
+      _ => None,
+    })
+    .collect();
+
+  if !arities.is_empty() {
+    // Tuples of each length are checked separately, each item becoming a column of its own
+    for k in arities {
+      let specialised: Vec<Vec<Pat>> = rows
+        .iter()
+        .filter_map(|r| match &r[0] {
+          Pat::Tuple(v) if v.len() == k => Some(v.iter().chain(&r[1..]).cloned().collect()),
+          Pat::Wild => Some(
+            iter::repeat_n(Pat::Wild, k)
+              .chain(r[1..].iter().cloned())
+              .collect(),
+          ),
+          _ => None,
+        })
+        .collect();
+      if let Some(w) = witness(&specialised, k + n - 1) {
+        let (items, rest) = w.split_at(k);
+        return Some(
+          iter::once(Pat::Tuple(items.to_vec()))
+            .chain(rest.iter().cloned())
+            .collect(),
+        );
+      }
+    }
+    return None;
+  }
+
+  // Literals can never cover every value, so only rows matching anything in this column count
+  let rest: Vec<Vec<Pat>> = rows
+    .iter()
+    .filter(|r| r[0] == Pat::Wild)
+    .map(|r| r[1..].to_vec())
+    .collect();
+  let w = witness(&rest, n - 1)?;
+  Some(
+    iter::once(example(rows.iter().map(|r| &r[0])))
+      .chain(w)
+      .collect(),
+  )
+}
+
+// Finds a value that none of a set of literal patterns match
+fn example<'a>(pats: impl Iterator<Item = &'a Pat>) -> Pat {
+  let mut ints = BTreeSet::new();
+  let mut strs = BTreeSet::new();
+  for p in pats {
+    match p {
+      Pat::Int(i) => {
+        ints.insert(*i);
+      }
+      Pat::Str(s) => {
+        strs.insert(s.as_str());
+      }
+      Pat::Wild => (),
+      _ => return Pat::Wild,
+    }
+  }
+
+  match (ints.is_empty(), strs.is_empty()) {
+    (false, true) => Pat::Int((0..).find(|i| !ints.contains(i)).unwrap_or_default()),
+    (true, false) => Pat::Str(
+      (0..)
+        .map(|i| "a".repeat(i))
+        .find(|s| !strs.contains(s.as_str()))
+        .unwrap_or_default(),
+    ),
+    _ => Pat::Wild,
+  }
+}
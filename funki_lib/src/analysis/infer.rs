@@ -0,0 +1,676 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::analysis::walk_pattern_vars;
+use crate::ast::{Expr, ExprInner, InterpolationPart, Opcode, Pattern, Program, UnaryOp};
+use crate::types::Type;
+
+// An error found while inferring types, with the section of code it is in
+type TypeError = (usize, String, usize);
+
+// The variables bound by each enclosing pattern, innermost last
+type Scopes<'a> = Vec<HashMap<&'a str, Type>>;
+
+// Operations whose result depends on the types of their operands
+#[derive(Clone, Copy)]
+enum Operation {
+  Bin(Opcode),
+  Not,
+  Neg,
+  // A guard, which must be a bool
+  Guard,
+  // A value in the start of a pattern, which is compared to the argument
+  Match,
+}
+
+// An operation that is solved once the types of its operands are known
+// Operators on custom values are defined by the host, so they are only checked once it is known
+//  neither operand is custom
+struct Overload {
+  op: Operation,
+  left: Type,
+  right: Type,
+  result: Type,
+  start: usize,
+  end: usize,
+}
+
+// The types of the builtins other than `list`, which depends on how many arguments it is given
+pub fn builtin_type(name: &str) -> Option<Type> {
+  let (a, b) = (Type::Var(0), Type::Var(1));
+  let list = Type::list(a.clone());
+  let pred = Type::function(a.clone(), Type::Bool);
+  Some(match name {
+    "len" => Type::function(list, Type::Int),
+    "get" => Type::function(Type::tuple(vec![list, Type::Int]), a),
+    "map" => Type::function(
+      Type::tuple(vec![list, Type::function(a, b.clone())]),
+      Type::list(b),
+    ),
+    "filter" => Type::function(Type::tuple(vec![list.clone(), pred]), list),
+    "any" | "all" => Type::function(Type::tuple(vec![list, pred]), Type::Bool),
+    "assert_eq" => Type::function(Type::tuple(vec![a.clone(), a]), Type::Bool),
+    "fold" => Type::function(
+      Type::tuple(vec![
+        list,
+        b.clone(),
+        Type::function(Type::tuple(vec![b.clone(), a]), b.clone()),
+      ]),
+      b,
+    ),
+    _ => return None,
+  })
+}
+
+// Infers the type of every function in a program
+// `customs` has the names of the custom builtins, with their declared types if they have them
+// Functions are inferred in the order they call each other, so a function used by others is
+//  generalised before they use it and can be used with different types
+// Returns the type of each function, or the first error in each set of functions that call each
+//  other
+pub fn infer_types(
+  program: &Program,
+  customs: &HashMap<&str, Option<Type>>,
+) -> Result<BTreeMap<String, Type>, Vec<TypeError>> {
+  let mut refs: HashMap<&str, HashSet<&str>> = HashMap::new();
+  for (name, patterns) in &program.env {
+    let mut found = HashSet::new();
+    for p in patterns {
+      walk_pattern_vars(p, &mut vec![], &mut |_, s, bound| {
+        if !bound && program.env.contains_key(s) {
+          found.insert(s);
+        }
+      });
+    }
+    refs.insert(name.as_str(), found);
+  }
+
+  let mut infer = Infer {
+    customs,
+    subst: vec![],
+    schemes: HashMap::new(),
+    group: HashMap::new(),
+    overloads: vec![],
+  };
+  let mut names: Vec<&str> = program.env.keys().map(|k| k.as_str()).collect();
+  names.sort();
+  let mut done = HashSet::new();
+  let mut errors = vec![];
+  for name in names {
+    infer.function(program, name, &refs, &mut done, &mut errors);
+  }
+
+  if errors.is_empty() {
+    Ok(
+      infer
+        .schemes
+        .iter()
+        .map(|(n, t)| {
+          (
+            n.to_string(),
+            Type::normalise(std::slice::from_ref(t)).remove(0),
+          )
+        })
+        .collect(),
+    )
+  } else {
+    errors.sort_by_key(|(l, _, r)| (*l, *r));
+    Err(errors)
+  }
+}
+
+// The functions a function can reach through the functions it references, including itself
+fn reachable<'a>(refs: &HashMap<&'a str, HashSet<&'a str>>, from: &'a str) -> HashSet<&'a str> {
+  let mut seen = HashSet::new();
+  let mut stack = vec![from];
+  while let Some(f) = stack.pop() {
+    if seen.insert(f) {
+      if let Some(r) = refs.get(f) {
+        stack.extend(r.iter().copied());
+      }
+    }
+  }
+  seen
+}
+
+// Tracks the state of inference, with the types found so far
+struct Infer<'a> {
+  customs: &'a HashMap<&'a str, Option<Type>>,
+  // What each type variable has been found to be
+  subst: Vec<Option<Type>>,
+  // The types of functions that have been inferred, every type variable in them is generalised
+  schemes: HashMap<&'a str, Type>,
+  // The types of the functions being inferred together, which are not generalised yet
+  group: HashMap<&'a str, Type>,
+  overloads: Vec<Overload>,
+}
+
+impl<'a> Infer<'a> {
+  // Infers a function along with the functions that call it and that it calls, after the
+  //  functions it uses
+  fn function(
+    &mut self,
+    program: &'a Program,
+    name: &'a str,
+    refs: &HashMap<&'a str, HashSet<&'a str>>,
+    done: &mut HashSet<&'a str>,
+    errors: &mut Vec<TypeError>,
+  ) {
+    if done.contains(name) {
+      return;
+    }
+    let mut group: Vec<&str> = reachable(refs, name)
+      .into_iter()
+      .filter(|f| reachable(refs, f).contains(name))
+      .collect();
+    group.sort();
+    done.extend(&group);
+
+    for f in &group {
+      for r in &refs[f] {
+        self.function(program, r, refs, done, errors);
+      }
+    }
+
+    if let Err(e) = self.group(program, &group) {
+      errors.push(e);
+      // Functions with errors can be used as any type, so their errors are only reported once
+      for f in &group {
+        let t = self.fresh();
+        self.schemes.insert(f, t);
+      }
+    }
+    self.group.clear();
+    self.overloads.clear();
+  }
+
+  // Infers a set of functions that call each other, then generalises their types
+  fn group(&mut self, program: &'a Program, group: &[&'a str]) -> Result<(), TypeError> {
+    for f in group {
+      let t = Type::function(self.fresh(), self.fresh());
+      self.group.insert(f, t);
+    }
+    for f in group {
+      if let Type::Function(arg, res) = self.group[f].clone() {
+        for p in &program.env[*f] {
+          self.pattern(p, &mut vec![], &arg, &res)?;
+        }
+      }
+    }
+    self.solve()?;
+
+    for f in group {
+      let t = self.apply(&self.group[f]);
+      self.schemes.insert(f, t);
+    }
+    Ok(())
+  }
+
+  // A new type variable
+  fn fresh(&mut self) -> Type {
+    self.subst.push(None);
+    Type::Var(self.subst.len() - 1)
+  }
+
+  // Follows the type variables a type has been found to be, until it is not a found variable
+  fn resolve(&self, t: &Type) -> Type {
+    match t {
+      Type::Var(v) => match &self.subst[*v] {
+        Some(t) => self.resolve(t),
+        None => t.clone(),
+      },
+      _ => t.clone(),
+    }
+  }
+
+  // Replaces every found type variable within a type
+  fn apply(&self, t: &Type) -> Type {
+    match self.resolve(t) {
+      Type::Tuple(v) => Type::Tuple(v.iter().map(|t| self.apply(t)).collect()),
+      Type::List(t) => Type::list(self.apply(&t)),
+      Type::Function(a, r) => Type::function(self.apply(&a), self.apply(&r)),
+      t => t,
+    }
+  }
+
+  // Replaces every type variable in a generalised type with a new variable
+  fn instantiate(&mut self, t: &Type, vars: &mut HashMap<usize, Type>) -> Type {
+    match t {
+      Type::Var(v) => match vars.get(v) {
+        Some(t) => t.clone(),
+        None => {
+          let n = self.fresh();
+          vars.insert(*v, n.clone());
+          n
+        }
+      },
+      Type::Tuple(v) => Type::Tuple(v.iter().map(|t| self.instantiate(t, vars)).collect()),
+      Type::List(t) => Type::list(self.instantiate(t, vars)),
+      Type::Function(a, r) => Type::function(self.instantiate(a, vars), self.instantiate(r, vars)),
+      t => t.clone(),
+    }
+  }
+
+  // Checks if a type variable is within a type
+  fn occurs(&self, v: usize, t: &Type) -> bool {
+    match self.resolve(t) {
+      Type::Var(u) => u == v,
+      Type::Tuple(ts) => ts.iter().any(|t| self.occurs(v, t)),
+      Type::List(t) => self.occurs(v, &t),
+      Type::Function(a, r) => self.occurs(v, &a) || self.occurs(v, &r),
+      _ => false,
+    }
+  }
+
+  // Makes two types the same, if they can be
+  fn unify(&mut self, a: &Type, b: &Type) -> bool {
+    match (self.resolve(a), self.resolve(b)) {
+      (Type::Var(x), Type::Var(y)) if x == y => true,
+      (Type::Var(x), t) | (t, Type::Var(x)) => {
+        if self.occurs(x, &t) {
+          false
+        } else {
+          self.subst[x] = Some(t);
+          true
+        }
+      }
+      (Type::Tuple(x), Type::Tuple(y)) => {
+        x.len() == y.len() && x.iter().zip(&y).all(|(x, y)| self.unify(x, y))
+      }
+      (Type::List(x), Type::List(y)) => self.unify(&x, &y),
+      (Type::Function(a1, r1), Type::Function(a2, r2)) => {
+        self.unify(&a1, &a2) && self.unify(&r1, &r2)
+      }
+      (x, y) => x == y,
+    }
+  }
+
+  // Makes the type found for an expression the type expected for it, or errors at the expression
+  fn unify_at(&mut self, expected: &Type, found: &Type, e: &Expr) -> Result<(), TypeError> {
+    if self.unify(expected, found) {
+      Ok(())
+    } else {
+      let [expected, found] = self.show([expected, found]);
+      Err((
+        e.start,
+        format!("Expected {}, found {}.", expected, found),
+        e.end,
+      ))
+    }
+  }
+
+  // Formats types for an error message, naming their type variables from `a`
+  fn show<const N: usize>(&self, types: [&Type; N]) -> [String; N] {
+    let types = Type::normalise(&types.map(|t| self.apply(t)));
+    std::array::from_fn(|i| types[i].to_string())
+  }
+
+  // Records an operation to solve once the types of its operands are known
+  fn overload(&mut self, op: Operation, left: Type, right: Type, result: Type, e: &Expr) {
+    self.overloads.push(Overload {
+      op,
+      left,
+      right,
+      result,
+      start: e.start,
+      end: e.end,
+    });
+  }
+
+  // Infers the types of a pattern, which takes `arg` and gives `res`
+  fn pattern(
+    &mut self,
+    p: &'a Pattern,
+    scopes: &mut Scopes<'a>,
+    arg: &Type,
+    res: &Type,
+  ) -> Result<(), TypeError> {
+    let mut scope = HashMap::new();
+    self.bind(&p.start, arg.clone(), scopes, &mut scope)?;
+
+    scopes.push(scope);
+    for g in &p.guards {
+      let t = self.expr(&g.expr, scopes)?;
+      self.overload(Operation::Guard, t, Type::Bool, Type::Bool, &g.expr);
+    }
+    let t = self.expr(&p.result, scopes)?;
+    self.unify_at(res, &t, &p.result)?;
+    scopes.pop();
+    Ok(())
+  }
+
+  // Matches the start of a pattern against the type of its argument, binding its variables
+  fn bind(
+    &mut self,
+    e: &'a Expr,
+    t: Type,
+    scopes: &mut Scopes<'a>,
+    scope: &mut HashMap<&'a str, Type>,
+  ) -> Result<(), TypeError> {
+    match &e.val {
+      ExprInner::Var(s) => {
+        if s != "_" {
+          scope.insert(s, t);
+        }
+      }
+      ExprInner::Tuple(v) if v.len() == 1 => self.bind(&v[0], t, scopes, scope)?,
+      ExprInner::Tuple(v) => {
+        let items: Vec<Type> = v.iter().map(|_| self.fresh()).collect();
+        self.unify_at(&t, &Type::Tuple(items.clone()), e)?;
+        for (e, t) in v.iter().zip(items) {
+          self.bind(e, t, scopes, scope)?;
+        }
+      }
+      _ => {
+        let found = self.expr(e, scopes)?;
+        self.overload(Operation::Match, t, found, Type::Bool, e);
+      }
+    }
+    Ok(())
+  }
+
+  // Infers the type of an expression
+  fn expr(&mut self, e: &'a Expr, scopes: &mut Scopes<'a>) -> Result<Type, TypeError> {
+    use ExprInner::*;
+    Ok(match &e.val {
+      Number(_) => Type::Int,
+      Str(_) => Type::String,
+      InterpolationString(parts) => {
+        for p in parts {
+          if let InterpolationPart::Expr(x) = p {
+            self.expr(x, scopes)?;
+          }
+        }
+        Type::String
+      }
+      Var(s) => self.var(s, scopes),
+      Tuple(v) => Type::tuple(
+        v.iter()
+          .map(|x| self.expr(x, scopes))
+          .collect::<Result<_, _>>()?,
+      ),
+      Unary(o, x) => {
+        let t = self.expr(x, scopes)?;
+        let res = self.fresh();
+        let op = match o {
+          UnaryOp::Not => Operation::Not,
+          UnaryOp::Neg => Operation::Neg,
+        };
+        self.overload(op, t, Type::Tuple(vec![]), res.clone(), e);
+        res
+      }
+      Op(l, o, r) => {
+        let (l, r) = (self.expr(l, scopes)?, self.expr(r, scopes)?);
+        let res = match o {
+          Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div | Opcode::Mod => self.fresh(),
+          _ => Type::Bool,
+        };
+        self.overload(Operation::Bin(*o), l, r, res.clone(), e);
+        res
+      }
+      CustomBinOp(l, _, r) => {
+        self.expr(l, scopes)?;
+        self.expr(r, scopes)?;
+        self.fresh()
+      }
+      CustomUnaryOp(_, r) => {
+        self.expr(r, scopes)?;
+        self.fresh()
+      }
+      Lambda(p) => {
+        let (arg, res) = (self.fresh(), self.fresh());
+        self.pattern(p, scopes, &arg, &res)?;
+        Type::function(arg, res)
+      }
+      FuncCall(f, a) => self.call(e, f, a, scopes)?,
+    })
+  }
+
+  // Finds the type of a variable, looking it up in the same order as the interpreter
+  // Globals are not known until the script is called, so any other name can be any type
+  fn var(&mut self, name: &str, scopes: &Scopes<'a>) -> Type {
+    if name == "true" || name == "false" {
+      return Type::Bool;
+    }
+    if let Some(t) = scopes.iter().rev().find_map(|s| s.get(name)) {
+      return t.clone();
+    }
+    if let Some(t) = self.group.get(name) {
+      return t.clone();
+    }
+    let scheme = match self.schemes.get(name) {
+      Some(t) => Some(t.clone()),
+      None => match self.customs.get(name) {
+        Some(t) => t.clone(),
+        None => builtin_type(name),
+      },
+    };
+    match scheme {
+      Some(t) => self.instantiate(&t, &mut HashMap::new()),
+      None => self.fresh(),
+    }
+  }
+
+  // Infers the type of a call
+  fn call(
+    &mut self,
+    e: &'a Expr,
+    f: &'a Expr,
+    a: &'a Expr,
+    scopes: &mut Scopes<'a>,
+  ) -> Result<Type, TypeError> {
+    let args: Vec<&Expr> = match &a.val {
+      ExprInner::Tuple(v) => v.iter().collect(),
+      _ => vec![a],
+    };
+    if let ExprInner::Var(name) = &f.val {
+      if name == "list"
+        && !scopes.iter().any(|s| s.contains_key("list"))
+        && !self.group.contains_key("list")
+        && !self.schemes.contains_key("list")
+        && !self.customs.contains_key("list")
+      {
+        return self.list(&args, scopes);
+      }
+    }
+
+    let ft = self.expr(f, scopes)?;
+    let ts = args
+      .iter()
+      .map(|a| self.expr(a, scopes))
+      .collect::<Result<Vec<_>, _>>()?;
+    let at = Type::tuple(ts.clone());
+    match self.resolve(&ft) {
+      Type::Function(p, r) => {
+        // Each argument is checked on its own so errors point at the argument that is wrong
+        match self.resolve(&p) {
+          Type::Tuple(ps) if ps.len() == ts.len() && ts.len() != 1 => {
+            for ((p, t), a) in ps.iter().zip(&ts).zip(&args) {
+              self.unify_at(p, t, a)?;
+            }
+          }
+          _ if args.len() == 1 => self.unify_at(&p, &at, args[0])?,
+          _ => self.unify_at(&p, &at, a)?,
+        }
+        Ok(*r)
+      }
+      Type::Var(_) => {
+        let res = self.fresh();
+        self.unify_at(&ft, &Type::function(at, res.clone()), e)?;
+        Ok(res)
+      }
+      t => {
+        let [t] = self.show([&t]);
+        Err((
+          f.start,
+          format!("Called a value of type {}, which is not a function.", t),
+          f.end,
+        ))
+      }
+    }
+  }
+
+  // Infers the type of a call to `list`, which makes a list of its arguments, or of the items of a
+  //  tuple if it is given one
+  fn list(&mut self, args: &[&'a Expr], scopes: &mut Scopes<'a>) -> Result<Type, TypeError> {
+    let item = self.fresh();
+    let ts = args
+      .iter()
+      .map(|a| self.expr(a, scopes))
+      .collect::<Result<Vec<_>, _>>()?;
+    if let ([a], [t]) = (args, ts.as_slice()) {
+      if let Type::Tuple(items) = self.resolve(t) {
+        for t in items {
+          self.unify_at(&item, &t, a)?;
+        }
+        return Ok(Type::list(item));
+      }
+    }
+    for (a, t) in args.iter().zip(&ts) {
+      self.unify_at(&item, t, a)?;
+    }
+    Ok(Type::list(item))
+  }
+
+  // Solves the recorded operations, repeating while solving some lets others be solved
+  // When no more can be solved, operands that are still unknown are taken to be the simplest
+  //  types the operation works on, ints for arithmetic and bools for logic
+  fn solve(&mut self) -> Result<(), TypeError> {
+    let mut default = false;
+    loop {
+      let pending = std::mem::take(&mut self.overloads);
+      let before = pending.len();
+      for o in pending {
+        if !self.solve_one(&o, default)? {
+          self.overloads.push(o);
+        }
+      }
+
+      if self.overloads.is_empty() || (default && self.overloads.len() == before) {
+        return Ok(());
+      }
+      default = self.overloads.len() == before;
+    }
+  }
+
+  // Solves an operation if the types of its operands are known, returning if it was solved
+  // With `default` set, unknown operands are given their default types
+  fn solve_one(&mut self, o: &Overload, default: bool) -> Result<bool, TypeError> {
+    use Type::*;
+    let (l, r) = (self.resolve(&o.left), self.resolve(&o.right));
+    if matches!(l, Custom) || matches!(r, Custom) {
+      return Ok(true);
+    }
+    let unknown = |t: &Type| matches!(t, Var(_));
+    let is = |t: &Type, want: &Type| t == want || unknown(t);
+    // An unknown operand could still be found to be custom, so errors wait until it is defaulted
+    if (unknown(&l) || unknown(&r)) && !default {
+      let solvable = match o.op {
+        Operation::Bin(Opcode::Add) => matches!(l, String),
+        _ => false,
+      };
+      if !solvable {
+        return Ok(false);
+      }
+    }
+
+    // Makes both operands and the result a type, once the operands are known or defaulted
+    let settle = |s: &mut Self, left: Type, right: Type, res: Type| -> Result<bool, TypeError> {
+      if unknown(&l) || unknown(&r) {
+        if !default {
+          return Ok(false);
+        }
+        s.unify(&o.left, &left);
+        s.unify(&o.right, &right);
+      }
+      s.unify(&o.result, &res);
+      Ok(true)
+    };
+
+    let (name, symbol) = match o.op {
+      Operation::Bin(Opcode::Add) => match (&l, &r) {
+        (String, _) => {
+          self.unify(&o.result, &String);
+          return Ok(true);
+        }
+        (Var(_), t) if !is(t, &Int) => return settle(self, String, r.clone(), String),
+        (a, b) if is(a, &Int) && is(b, &Int) => return settle(self, Int, Int, Int),
+        _ => ("Add", "+"),
+      },
+      Operation::Bin(Opcode::Mul) => match (&l, &r) {
+        (String, b) if is(b, &Int) => return settle(self, String, Int, String),
+        (a, b) if is(a, &Int) && is(b, &Int) => return settle(self, Int, Int, Int),
+        _ => ("Multiplication", "*"),
+      },
+      Operation::Bin(op @ (Opcode::Sub | Opcode::Div | Opcode::Mod)) => {
+        if is(&l, &Int) && is(&r, &Int) {
+          return settle(self, Int, Int, Int);
+        }
+        match op {
+          Opcode::Sub => ("Subtract", "-"),
+          Opcode::Div => ("Division", "/"),
+          _ => ("Modulo", "%"),
+        }
+      }
+      Operation::Bin(op @ (Opcode::Lt | Opcode::Gt | Opcode::Leq | Opcode::Geq)) => {
+        if is(&l, &Int) && is(&r, &Int) {
+          return settle(self, Int, Int, Bool);
+        }
+        let symbol = match op {
+          Opcode::Lt => "<",
+          Opcode::Gt => ">",
+          Opcode::Leq => "<=",
+          _ => ">=",
+        };
+        let [l, r] = self.show([&l, &r]);
+        return Err((
+          o.start,
+          format!("Comparison of types not supported {} {} {}.", l, symbol, r),
+          o.end,
+        ));
+      }
+      Operation::Bin(op @ (Opcode::And | Opcode::Or)) => {
+        if is(&l, &Bool) && is(&r, &Bool) {
+          return settle(self, Bool, Bool, Bool);
+        }
+        match op {
+          Opcode::And => ("And", "&&"),
+          _ => ("Or", "||"),
+        }
+      }
+      Operation::Bin(Opcode::Eq | Opcode::Neq) | Operation::Match => {
+        if self.unify(&l, &r) {
+          return Ok(true);
+        }
+        let [l, r] = self.show([&l, &r]);
+        let message = match o.op {
+          Operation::Match => format!(
+            "This pattern has type {}, but the argument has type {}.",
+            r, l
+          ),
+          _ => format!("Non matching types for equality: {} == {}", l, r),
+        };
+        return Err((o.start, message, o.end));
+      }
+      Operation::Not | Operation::Neg | Operation::Guard => {
+        let (want, message) = match o.op {
+          Operation::Not => (Bool, "Tried to apply '!' to a value of type {}."),
+          Operation::Neg => (Int, "Tried to apply '-' to a value of type {}."),
+          _ => (Bool, "This guard has type {}, but guards must be Bool."),
+        };
+        if is(&l, &want) {
+          let res = want.clone();
+          return settle(self, want, Tuple(vec![]), res);
+        }
+        let [l] = self.show([&l]);
+        return Err((o.start, message.replace("{}", &l), o.end));
+      }
+    };
+
+    let [l, r] = self.show([&l, &r]);
+    Err((
+      o.start,
+      format!("{} operator not defined for {} {} {}.", name, l, symbol, r),
+      o.end,
+    ))
+  }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+use crate::analysis::exhaustive::{arity_mismatches, missing_cases};
+use crate::analysis::{children, pattern_bindings, pattern_values};
+use crate::ast::{Expr, ExprInner, Pattern, Program};
+use crate::errors::Span;
+
+/// The kinds of problem found by `Script::lints`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LintKind {
+  /// A pattern binds a variable that is never used.
+  UnusedVariable,
+  /// A pattern binds a variable with the same name as a function, builtin, global or a variable
+  /// of an enclosing pattern.
+  ShadowedName,
+  /// A pattern can never match as an earlier pattern matches everything.
+  UnreachablePattern,
+  /// A guard does not use any variables, so it always passes or always fails.
+  ConstantGuard,
+  /// A call is made to a name that is not a function, builtin, global or variable.
+  UnknownFunction,
+  /// Some arguments to a function are not matched by any of its patterns.
+  NonExhaustive,
+  /// A pattern takes a tuple of a different length to an earlier pattern of the same function.
+  ArityMismatch,
+}
+
+impl LintKind {
+  /// A stable code for the kind of lint, which does not change between versions.
+  pub fn code(&self) -> &'static str {
+    match self {
+      LintKind::UnusedVariable => "W001",
+      LintKind::ShadowedName => "W002",
+      LintKind::UnreachablePattern => "W003",
+      LintKind::ConstantGuard => "W004",
+      LintKind::UnknownFunction => "W005",
+      LintKind::NonExhaustive => "W006",
+      LintKind::ArityMismatch => "W007",
+    }
+  }
+}
+
+impl Display for LintKind {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LintKind::UnusedVariable => write!(fmt, "unused variable"),
+      LintKind::ShadowedName => write!(fmt, "shadowed name"),
+      LintKind::UnreachablePattern => write!(fmt, "unreachable pattern"),
+      LintKind::ConstantGuard => write!(fmt, "constant guard"),
+      LintKind::UnknownFunction => write!(fmt, "unknown function"),
+      LintKind::NonExhaustive => write!(fmt, "non-exhaustive patterns"),
+      LintKind::ArityMismatch => write!(fmt, "arity mismatch"),
+    }
+  }
+}
+
+/// A warning about code that parses but is likely to be a mistake.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+  /// The kind of problem.
+  pub kind: LintKind,
+  /// A description of the problem.
+  pub message: String,
+  /// The section of code with the problem.
+  pub span: Span,
+}
+
+impl Lint {
+  /// The stable code for the kind of lint, such as `W001`.
+  pub fn code(&self) -> &'static str {
+    self.kind.code()
+  }
+}
+
+impl Display for Lint {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      fmt,
+      "warning[{}]: {} at line {}, column {}",
+      self.code(),
+      self.message,
+      self.span.start_line,
+      self.span.start_col
+    )
+  }
+}
+
+// A variable bound by a pattern, and whether it has been used
+struct Binding<'a> {
+  name: &'a str,
+  expr: &'a Expr,
+  used: bool,
+}
+
+// Walks a program collecting lints
+struct Linter<'a> {
+  program: &'a Program,
+  // Names other than functions and pattern variables that can be used, such as builtins
+  known: &'a HashSet<&'a str>,
+  source: &'a str,
+  lints: Vec<Lint>,
+}
+
+// Finds the lints in a program, in the order they are in the code
+// `known` has the names of the builtins, host functions and globals that can be used
+pub fn lint(program: &Program, known: &HashSet<&str>, source: &str) -> Vec<Lint> {
+  let mut linter = Linter {
+    program,
+    known,
+    source,
+    lints: vec![],
+  };
+
+  for (name, patterns) in &program.env {
+    let missing = missing_cases(patterns, 3);
+    if let Some(p) = patterns.first().filter(|_| !missing.is_empty()) {
+      linter.push(
+        LintKind::NonExhaustive,
+        format!(
+          "Patterns of \"{}\" do not cover every argument, for example `{}`.",
+          name,
+          missing.join("`, `")
+        ),
+        pattern_expr(p),
+      );
+    }
+    for (e, arity, expected) in arity_mismatches(patterns) {
+      linter.push(
+        LintKind::ArityMismatch,
+        format!(
+          "This pattern takes {} items, but an earlier pattern takes {}.",
+          arity, expected
+        ),
+        e,
+      );
+    }
+
+    let mut catch_all = false;
+    for p in patterns {
+      if catch_all {
+        linter.push(
+          LintKind::UnreachablePattern,
+          "This pattern can never match, as an earlier pattern matches everything.".to_string(),
+          pattern_expr(p),
+        );
+      }
+      catch_all |= p.guards.is_empty() && matches!(p.start.val, ExprInner::Var(_));
+      linter.pattern(p, &mut vec![]);
+    }
+  }
+
+  let mut lints = linter.lints;
+  lints.sort_by_key(|l| (l.span.start, l.span.end, l.kind.code()));
+  lints
+}
+
+impl<'a> Linter<'a> {
+  fn push(&mut self, kind: LintKind, message: String, e: &Expr) {
+    self.lints.push(Lint {
+      kind,
+      message,
+      span: Span::new(self.source, e.start, e.end),
+    })
+  }
+
+  // Checks a name is bound by an enclosing pattern
+  fn in_scope(scopes: &[Vec<Binding>], name: &str) -> bool {
+    scopes.iter().any(|s| s.iter().any(|b| b.name == name))
+  }
+
+  // Lints a pattern, with the variables of any enclosing patterns in scope
+  fn pattern(&mut self, p: &'a Pattern, scopes: &mut Vec<Vec<Binding<'a>>>) {
+    for v in pattern_values(&p.start) {
+      self.expr(v, scopes);
+    }
+
+    let mut scope = vec![];
+    for e in pattern_bindings(&p.start) {
+      if let ExprInner::Var(name) = &e.val {
+        let shadowed = if Self::in_scope(scopes, name) {
+          Some("a variable of an enclosing pattern")
+        } else if self.program.env.contains_key(name) {
+          Some("a function")
+        } else if self.known.contains(name.as_str()) {
+          Some("a builtin or global")
+        } else {
+          None
+        };
+        if let Some(s) = shadowed {
+          self.push(
+            LintKind::ShadowedName,
+            format!("\"{}\" shadows {} with the same name.", name, s),
+            e,
+          );
+        }
+        scope.push(Binding {
+          name,
+          expr: e,
+          used: false,
+        });
+      }
+    }
+
+    scopes.push(scope);
+    for g in &p.guards {
+      if is_constant(&g.expr) {
+        self.push(
+          LintKind::ConstantGuard,
+          "This guard does not use any variables, so its result never changes.".to_string(),
+          &g.expr,
+        );
+      }
+      self.expr(&g.expr, scopes);
+    }
+    self.expr(&p.result, scopes);
+
+    for b in scopes.pop().unwrap_or_default() {
+      if !b.used {
+        self.push(
+          LintKind::UnusedVariable,
+          format!("\"{}\" is never used.", b.name),
+          b.expr,
+        );
+      }
+    }
+  }
+
+  // Lints an expression, marking the variables it uses
+  fn expr(&mut self, e: &'a Expr, scopes: &mut Vec<Vec<Binding<'a>>>) {
+    match &e.val {
+      ExprInner::Var(name) => {
+        if let Some(b) = scopes
+          .iter_mut()
+          .rev()
+          .find_map(|s| s.iter_mut().find(|b| b.name == name))
+        {
+          b.used = true;
+        }
+      }
+      ExprInner::Lambda(p) => self.pattern(p, scopes),
+      ExprInner::FuncCall(f, a) => {
+        if let ExprInner::Var(name) = &f.val {
+          if !Self::in_scope(scopes, name)
+            && !self.program.env.contains_key(name)
+            && !self.known.contains(name.as_str())
+          {
+            self.push(
+              LintKind::UnknownFunction,
+              format!(
+                "\"{}\" is not a function, builtin, global or variable.",
+                name
+              ),
+              f,
+            );
+          }
+        }
+        self.expr(f, scopes);
+        self.expr(a, scopes);
+      }
+      _ => {
+        for c in children(e) {
+          self.expr(c, scopes);
+        }
+      }
+    }
+  }
+}
+
+// The expression to point at for a whole pattern, its start unless the start is empty
+fn pattern_expr(p: &Pattern) -> &Expr {
+  if p.start.start != p.start.end {
+    &p.start
+  } else {
+    &p.result
+  }
+}
+
+// Checks if an expression uses no variables, so always has the same value
+fn is_constant(e: &Expr) -> bool {
+  match &e.val {
+    ExprInner::Var(s) => s == "true" || s == "false",
+    ExprInner::Lambda(_) => false,
+    _ => children(e).into_iter().all(is_constant),
+  }
+}
@@ -0,0 +1,115 @@
+use crate::ast::{Expr, ExprInner, InterpolationPart, Pattern};
+
+mod exhaustive;
+mod infer;
+mod lints;
+mod navigate;
+mod sandbox;
+mod suggest;
+
+pub use infer::{builtin_type, infer_types};
+pub use lints::{lint, Lint, LintKind};
+pub use navigate::{name_at, scope_at, Found, Reference, ReferenceKind, Symbol, Target};
+pub use sandbox::check_sandbox;
+pub use suggest::{did_you_mean, undefined_operator};
+
+// Finds the variables bound by the start of a pattern
+// Underscores match anything but are not bound
+pub fn pattern_bindings(start: &Expr) -> Vec<&Expr> {
+  match &start.val {
+    ExprInner::Var(s) if s != "_" => vec![start],
+    ExprInner::Tuple(v) => v.iter().flat_map(pattern_bindings).collect(),
+    _ => vec![],
+  }
+}
+
+// Finds the expressions in the start of a pattern that are evaluated and compared to the argument
+//  rather than bound
+pub fn pattern_values(start: &Expr) -> Vec<&Expr> {
+  match &start.val {
+    ExprInner::Var(_) => vec![],
+    ExprInner::Tuple(v) => v.iter().flat_map(pattern_values).collect(),
+    _ => vec![start],
+  }
+}
+
+// Finds the sub expressions directly within an expression
+pub fn children(expr: &Expr) -> Vec<&Expr> {
+  use ExprInner::*;
+  match &expr.val {
+    Number(_) | Var(_) | Str(_) => vec![],
+    Op(l, _, r) | CustomBinOp(l, _, r) | FuncCall(l, r) => vec![l, r],
+    Unary(_, e) | CustomUnaryOp(_, e) => vec![e],
+    Tuple(v) => v.iter().collect(),
+    InterpolationString(v) => v
+      .iter()
+      .filter_map(|p| match p {
+        InterpolationPart::Expr(e) => Some(e),
+        InterpolationPart::String(_) => None,
+      })
+      .collect(),
+    Lambda(p) => pattern_exprs(p),
+  }
+}
+
+// Finds the expressions that make up a pattern, its start, guards and result
+pub fn pattern_exprs(p: &Pattern) -> Vec<&Expr> {
+  let mut v = vec![&p.start];
+  v.extend(p.guards.iter().map(|g| &g.expr));
+  v.push(&p.result);
+  v
+}
+
+// Calls `f` on an expression and every expression within it
+pub fn walk_exprs<'a>(expr: &'a Expr, f: &mut dyn FnMut(&'a Expr)) {
+  f(expr);
+  for c in children(expr) {
+    walk_exprs(c, f);
+  }
+}
+
+// Calls `f` on every variable used in an expression, along with whether the variable is bound by a
+//  pattern in `scope` or within the expression
+// Variables in the start of a pattern are bindings, not uses
+pub fn walk_vars<'a>(
+  expr: &'a Expr,
+  scope: &mut Vec<Vec<&'a str>>,
+  f: &mut dyn FnMut(&'a Expr, &'a str, bool),
+) {
+  match &expr.val {
+    ExprInner::Var(s) => f(expr, s, scope.iter().any(|l| l.contains(&s.as_str()))),
+    ExprInner::Lambda(p) => walk_pattern_vars(p, scope, f),
+    _ => {
+      for c in children(expr) {
+        walk_vars(c, scope, f);
+      }
+    }
+  }
+}
+
+// Calls `f` on every variable used in a pattern, the variables bound by the start of the pattern
+//  are in scope for its guards and result
+pub fn walk_pattern_vars<'a>(
+  p: &'a Pattern,
+  scope: &mut Vec<Vec<&'a str>>,
+  f: &mut dyn FnMut(&'a Expr, &'a str, bool),
+) {
+  for v in pattern_values(&p.start) {
+    walk_vars(v, scope, f);
+  }
+
+  scope.push(
+    pattern_bindings(&p.start)
+      .into_iter()
+      .filter_map(|e| match &e.val {
+        ExprInner::Var(s) => Some(s.as_str()),
+        _ => None,
+      })
+      .collect(),
+  );
+  for g in &p.guards {
+    walk_vars(&g.expr, scope, f);
+  }
+  walk_vars(&p.result, scope, f);
+  scope.pop();
+}
@@ -0,0 +1,203 @@
+use std::collections::HashSet;
+
+use crate::analysis::{children, pattern_bindings, pattern_values};
+use crate::ast::{Expr, ExprInner, Pattern, Program};
+use crate::Span;
+
+/// A function defined in a script, see `Script::symbols`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+  /// The name of the function.
+  pub name: String,
+  /// The section of code with the function's `#name`.
+  pub name_span: Span,
+  /// The section of code with the whole function, from its name to its last pattern.
+  pub span: Span,
+  /// The code of each pattern, from its arguments to its last guard, in the order they are tried.
+  pub patterns: Vec<String>,
+  /// The function's doc comment, the `///` lines just before its name, without the `///`s.
+  pub doc: Option<String>,
+}
+
+/// A name in a script and what it refers to, see `Script::reference_at`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reference {
+  /// The name.
+  pub name: String,
+  /// The section of code with the name.
+  pub span: Span,
+  /// What kind of value the name refers to.
+  pub kind: ReferenceKind,
+  /// Where the name is defined, the `#name` of a function or the variable in the pattern or
+  /// lambda that binds it. `None` for builtins, globals and unknown names.
+  pub definition: Option<Span>,
+}
+
+/// What kind of value a name refers to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ReferenceKind {
+  /// A function defined in the script.
+  Function,
+  /// A variable bound by a pattern or lambda.
+  Variable,
+  /// A builtin function, or a custom function added by the host.
+  Builtin,
+  /// A global value set by the host.
+  Global,
+  /// A name that is not defined.
+  Unknown,
+}
+
+// What a name in the code refers to, before builtins and globals are told apart
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+  // A function in the program
+  Function,
+  // A variable, bound by the pattern or lambda variable at the location
+  Variable(usize, usize),
+  // Anything else, a builtin, global or unknown name
+  Other,
+}
+
+// A name found in the code, with its location and what it refers to
+#[derive(Clone, Debug, PartialEq)]
+pub struct Found {
+  pub name: String,
+  pub start: usize,
+  pub end: usize,
+  pub target: Target,
+}
+
+// Finds the name at a byte offset in the code, either a function's `#name` or a variable
+// An offset just after a name counts as being on the name, as it is where an editor's cursor is
+pub fn name_at(program: &Program, offset: usize) -> Option<Found> {
+  for (name, (start, end)) in &program.names {
+    if (*start..=*end).contains(&offset) && !name.is_empty() {
+      return Some(Found {
+        name: name.clone(),
+        start: *start,
+        end: *end,
+        target: Target::Function,
+      });
+    }
+  }
+
+  let p = program
+    .env
+    .values()
+    .flatten()
+    .find(|p| (p.span().0..=p.span().1).contains(&offset))?;
+  find_in_pattern(program, p, offset, &mut vec![])
+}
+
+// Finds the variables in scope at a byte offset, innermost first
+pub fn scope_at(program: &Program, offset: usize) -> Vec<&str> {
+  let mut res = vec![];
+  if let Some(p) = program
+    .env
+    .values()
+    .flatten()
+    .find(|p| (p.span().0..=p.span().1).contains(&offset))
+  {
+    pattern_scope(p, offset, &mut res);
+  }
+  res.reverse();
+  let mut seen = HashSet::new();
+  res.retain(|n| seen.insert(*n));
+  res
+}
+
+// Adds the variables of a pattern, and of the lambdas within it around the offset, to `scope`
+fn pattern_scope<'a>(p: &'a Pattern, offset: usize, scope: &mut Vec<&'a str>) {
+  scope.extend(pattern_bindings(&p.start).into_iter().map(var_name));
+  let mut exprs: Vec<&Expr> = p.guards.iter().map(|g| &g.expr).collect();
+  exprs.push(&p.result);
+  while let Some(e) = exprs.pop() {
+    if !(e.start..=e.end).contains(&offset) {
+      continue;
+    }
+    match &e.val {
+      ExprInner::Lambda(l) => return pattern_scope(l, offset, scope),
+      _ => exprs.extend(children(e)),
+    }
+  }
+}
+
+// Finds the name at an offset within a pattern, with the variables bound by the enclosing
+//  patterns in `scope`, innermost last
+fn find_in_pattern<'a>(
+  program: &Program,
+  p: &'a Pattern,
+  offset: usize,
+  scope: &mut Vec<&'a Expr>,
+) -> Option<Found> {
+  let bindings = pattern_bindings(&p.start);
+  if let Some(b) = bindings
+    .iter()
+    .find(|b| (b.start..=b.end).contains(&offset))
+  {
+    return Some(found(b, Target::Variable(b.start, b.end)));
+  }
+  for v in pattern_values(&p.start) {
+    if let Some(f) = find_in_expr(program, v, offset, scope) {
+      return Some(f);
+    }
+  }
+
+  let len = scope.len();
+  scope.extend(bindings);
+  let res = p
+    .guards
+    .iter()
+    .map(|g| &g.expr)
+    .chain([&p.result])
+    .find_map(|e| find_in_expr(program, e, offset, scope));
+  scope.truncate(len);
+  res
+}
+
+// Finds the name at an offset within an expression
+fn find_in_expr<'a>(
+  program: &Program,
+  e: &'a Expr,
+  offset: usize,
+  scope: &mut Vec<&'a Expr>,
+) -> Option<Found> {
+  if !(e.start..=e.end).contains(&offset) {
+    return None;
+  }
+  match &e.val {
+    ExprInner::Var(name) if matches!(name.as_str(), "true" | "false" | "_") => None,
+    ExprInner::Var(name) => {
+      let target = match scope.iter().rev().find(|b| var_name(b) == name) {
+        Some(b) => Target::Variable(b.start, b.end),
+        None if program.env.contains_key(name) => Target::Function,
+        None => Target::Other,
+      };
+      Some(found(e, target))
+    }
+    ExprInner::Lambda(p) => find_in_pattern(program, p, offset, scope),
+    _ => children(e)
+      .into_iter()
+      .find_map(|c| find_in_expr(program, c, offset, scope)),
+  }
+}
+
+// The name of a variable
+fn var_name(e: &Expr) -> &str {
+  match &e.val {
+    ExprInner::Var(s) => s,
+    _ => "",
+  }
+}
+
+// A variable found in the code
+fn found(e: &Expr, target: Target) -> Found {
+  Found {
+    name: var_name(e).to_string(),
+    start: e.start,
+    end: e.end,
+    target,
+  }
+}
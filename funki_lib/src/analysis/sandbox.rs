@@ -0,0 +1,94 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::analysis::{pattern_exprs, walk_exprs, walk_pattern_vars};
+use crate::ast::{Expr, ExprInner, Program};
+use crate::Feature;
+
+// Checks a program only uses the builtins and features allowed by a language
+// Returns every violation in the program with its location, in the order they are in the code
+// Recursion is found from the functions that call each other by name, so a function passed to
+//  itself can still recurse at runtime
+pub fn check_sandbox(
+  program: &Program,
+  removed_builtins: &HashSet<String>,
+  disabled: &HashSet<Feature>,
+) -> Result<(), Vec<(usize, String, usize)>> {
+  let mut errors = vec![];
+
+  // References from each function to the functions and builtins it uses
+  let mut refs: HashMap<&str, Vec<(&Expr, &str)>> = HashMap::new();
+  for (name, patterns) in &program.env {
+    let mut found = vec![];
+    for p in patterns {
+      walk_pattern_vars(p, &mut vec![], &mut |e, s, bound| {
+        if !bound {
+          found.push((e, s));
+        }
+      });
+    }
+    refs.insert(name.as_str(), found);
+  }
+
+  for (name, found) in &refs {
+    for (e, s) in found {
+      if removed_builtins.contains(*s) && !program.env.contains_key(*s) {
+        errors.push((
+          e.start,
+          format!("Builtin \"{}\" is not available.", s),
+          e.end,
+        ));
+      }
+
+      if disabled.contains(&Feature::Recursion)
+        && program.env.contains_key(*s)
+        && (s == name || reaches(&refs, s, name))
+      {
+        errors.push((
+          e.start,
+          format!(
+            "Recursion is disabled, but \"{}\" can call itself through \"{}\".",
+            name, s
+          ),
+          e.end,
+        ));
+      }
+    }
+  }
+
+  if disabled.contains(&Feature::Lambdas) {
+    for patterns in program.env.values() {
+      for p in patterns {
+        for e in pattern_exprs(p) {
+          walk_exprs(e, &mut |e| {
+            if let ExprInner::Lambda(_) = e.val {
+              errors.push((e.start, "Lambdas are disabled.".to_string(), e.end));
+            }
+          });
+        }
+      }
+    }
+  }
+
+  errors.sort_by_key(|(l, _, r)| (*l, *r));
+  match errors.is_empty() {
+    true => Ok(()),
+    false => Err(errors),
+  }
+}
+
+// Checks if the function `from` can reach the function `to` through the functions it references
+fn reaches(refs: &HashMap<&str, Vec<(&Expr, &str)>>, from: &str, to: &str) -> bool {
+  let mut seen = HashSet::new();
+  let mut stack = vec![from];
+  while let Some(f) = stack.pop() {
+    if f == to {
+      return true;
+    }
+    if seen.insert(f) {
+      if let Some(r) = refs.get(f) {
+        stack.extend(r.iter().map(|(_, s)| *s));
+      }
+    }
+  }
+  false
+}
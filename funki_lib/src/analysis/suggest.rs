@@ -0,0 +1,72 @@
+use crate::ast::ParserState;
+use crate::external_operators::OperatorChars;
+
+// Finds the candidate closest to a name, if it is close enough to be a likely typo
+// Ties are broken alphabetically so suggestions do not depend on hash map order
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+  let max = name.chars().count().div_ceil(3).max(1);
+  candidates
+    .into_iter()
+    .filter(|c| *c != name)
+    .map(|c| (edit_distance(name, c), c))
+    .filter(|(d, _)| *d <= max)
+    .min()
+    .map(|(_, c)| c)
+}
+
+// A hint to add to the end of an error message, empty if no candidate is close to the name
+pub fn did_you_mean<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+  match closest(name, candidates) {
+    Some(c) => format!(" Did you mean \"{}\"?", c),
+    None => String::new(),
+  }
+}
+
+// The message for a custom operator that is not defined, pointing at the operators that are
+pub fn undefined_operator(op: &OperatorChars, unary: bool, state: &ParserState) -> String {
+  let (kind, defined, other_kind, other) = if unary {
+    ("unary", &state.unary_ops, "binary", &state.binary_ops)
+  } else {
+    ("binary", &state.binary_ops, "unary", &state.unary_ops)
+  };
+
+  let mut message = format!("This {} operator is not defined", kind);
+  if other.contains(op) {
+    message += &format!(", `{}` is only defined as a {} operator", op, other_kind);
+  } else if !defined.is_empty() {
+    let mut ops: Vec<String> = defined.iter().map(|o| format!("`{}`", o)).collect();
+    ops.sort();
+    message += &match ops.as_slice() {
+      [o] => format!(". Did you mean {}?", o),
+      _ => format!(". Did you mean one of {}?", ops.join(", ")),
+    };
+  }
+  message
+}
+
+// The number of single character insertions, deletions, substitutions and swaps of adjacent
+//  characters needed to turn one string into another
+fn edit_distance(a: &str, b: &str) -> usize {
+  let a: Vec<char> = a.chars().collect();
+  let b: Vec<char> = b.chars().collect();
+  let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+  for (i, row) in d.iter_mut().enumerate() {
+    row[0] = i;
+  }
+  for (j, cell) in d[0].iter_mut().enumerate() {
+    *cell = j;
+  }
+
+  for i in 1..=a.len() {
+    for j in 1..=b.len() {
+      let cost = usize::from(a[i - 1] != b[j - 1]);
+      d[i][j] = (d[i - 1][j] + 1)
+        .min(d[i][j - 1] + 1)
+        .min(d[i - 1][j - 1] + cost);
+      if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+        d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+      }
+    }
+  }
+  d[a.len()][b.len()]
+}
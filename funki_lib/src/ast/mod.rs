@@ -1,21 +1,34 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::{Debug, Error, Formatter};
 
 use itertools::Itertools;
+use lalrpop_util::ParseError;
 
 use crate::external_operators::OperatorChars;
 
+// An error from the parser, with its tokens replaced by empty strings
+pub type SyntaxError = ParseError<usize, String, (usize, String, usize)>;
+
 // Parser state
 pub struct ParserState {
   pub unary_ops: Vec<OperatorChars>,
   pub binary_ops: Vec<OperatorChars>,
+  // Syntax errors the parser has recovered from
+  pub errors: RefCell<Vec<SyntaxError>>,
 }
 
 impl ParserState {
   pub fn new() -> Self {
+    Self::with_ops(vec![], vec![])
+  }
+
+  // Parser state for a language with custom operators
+  pub fn with_ops(unary_ops: Vec<OperatorChars>, binary_ops: Vec<OperatorChars>) -> Self {
     Self {
-      unary_ops: vec![],
-      binary_ops: vec![],
+      unary_ops,
+      binary_ops,
+      errors: RefCell::new(vec![]),
     }
   }
 }
@@ -23,6 +36,10 @@ impl ParserState {
 // Script
 pub struct Program {
   pub env: HashMap<String, Vec<Pattern>>,
+  // The location of each function's `#name`
+  pub names: HashMap<String, (usize, usize)>,
+  // The doc comment of each function that has one, without the `///`s
+  pub docs: HashMap<String, String>,
 }
 
 // Patterns within a function
@@ -33,6 +50,23 @@ pub struct Pattern {
   pub guards: Vec<Guard>,
 }
 
+impl Pattern {
+  // Whether the pattern has arguments, patterns without are written without `->`
+  pub fn has_args(&self) -> bool {
+    self.start.start != self.start.end
+  }
+
+  // The start and end of the pattern in the code, from its arguments to its last guard
+  pub fn span(&self) -> (usize, usize) {
+    let start = match self.has_args() {
+      true => self.start.start,
+      false => self.result.start,
+    };
+    let end = self.guards.last().map_or(self.result.end, |g| g.expr.end);
+    (start, end)
+  }
+}
+
 // Guard for a function
 #[derive(Clone, PartialEq)]
 pub struct Guard {
@@ -146,7 +180,7 @@ impl Expr {
         end: r,
       } => {
         if s.len() == 1 {
-          s.get(0).unwrap().clone()
+          s.first().unwrap().clone()
         } else {
           Expr {
             val: ExprInner::Tuple(s),
@@ -212,9 +246,9 @@ impl Debug for Expr {
       ),
       ExprInner::Lambda(ref p) => write!(fmt, "|{:?}|", p),
       ExprInner::CustomBinOp(ref l, ref o, ref r) => {
-        write!(fmt, "CustomOp({:?} {} {:?})", l, o.to_string(), r)
+        write!(fmt, "CustomOp({:?} {} {:?})", l, o, r)
       }
-      ExprInner::CustomUnaryOp(ref o, ref r) => write!(fmt, "CustomOp({} {:?})", o.to_string(), r),
+      ExprInner::CustomUnaryOp(ref o, ref r) => write!(fmt, "CustomOp({} {:?})", o, r),
     }
   }
 }
@@ -268,7 +302,7 @@ impl Debug for Pattern {
 
 impl Debug for Program {
   fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
-    let mut funcs: Vec<&String> = self.env.keys().into_iter().collect();
+    let mut funcs: Vec<&String> = self.env.keys().collect();
     funcs.sort();
     write!(
       fmt,
@@ -1,37 +1,98 @@
-use std::cell::RefCell;
-use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::fmt::{Debug, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 use std::ops::Add;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use itertools::Itertools;
 
 use crate::ast::Pattern;
 use crate::external_operators::CustomType;
-use crate::{Argument, Customs, Program, ReturnVal};
+use crate::limits::Limit;
+use crate::types::Type;
+use crate::{Argument, Customs, ErrorKind, Program, ReturnVal};
 
 /// Errors from the interpreter, can optionally have location information added
 #[derive(Clone)]
 pub struct InterpretError {
   pub message: String,
   pub location: Option<(usize, usize)>,
+  pub kind: ErrorKind,
+  // Other locations related to the error, with a message for each
+  pub labels: Vec<((usize, usize), String)>,
+  // The calls the error unwound through, innermost first
+  pub stack: Vec<CallFrame>,
+  // The argument of a call to a builtin the error is about, used to locate the error at it
+  pub arg: Option<usize>,
+}
+
+// A call an error unwound through
+#[derive(Clone, Debug)]
+pub struct CallFrame {
+  pub name: String,
+  // Where the call was made, none if it was made by the host or a builtin
+  pub call_site: Option<(usize, usize)>,
+  // The pattern being evaluated when the error happened
+  pub pattern: Option<usize>,
+  // The start of the argument the function was called with
+  pub arg: String,
 }
 
 impl InterpretError {
   // Creates an interpret error with no location
   pub fn new(name: &str) -> Self {
+    Self::new_kind(ErrorKind::RuntimeError, name)
+  }
+
+  // Creates an interpret error of a given kind with no location
+  pub fn new_kind(kind: ErrorKind, name: &str) -> Self {
     Self {
       message: name.to_string(),
       location: None,
+      kind,
+      labels: vec![],
+      stack: vec![],
+      arg: None,
     }
   }
 
   // Errors when they come from custom string
   pub fn from_custom(name: Box<dyn ToString>) -> Self {
-    Self {
-      message: name.to_string(),
-      location: None,
+    Self::new_kind(ErrorKind::HostError, &name.to_string())
+  }
+
+  // Errors when an evaluation limit is exceeded
+  pub fn new_limit(limit: Limit, message: &str) -> Self {
+    Self::new_kind(ErrorKind::LimitExceeded(limit), message)
+  }
+
+  // Errors when the call is cancelled
+  pub fn new_cancelled() -> Self {
+    Self::new_kind(ErrorKind::Cancelled, "Call was cancelled.")
+  }
+
+  // Adds a label pointing at another location related to the error
+  pub fn with_label(mut self, start: usize, end: usize, message: &str) -> Self {
+    self.labels.push(((start, end), message.to_string()));
+    self
+  }
+
+  // Records a call the error unwound through
+  pub fn push_frame(mut self, name: &str, pattern: Option<usize>, arg: String) -> Self {
+    self.stack.push(CallFrame {
+      name: name.to_string(),
+      call_site: None,
+      pattern,
+      arg,
+    });
+    self
+  }
+
+  // Sets where the most recent call in the stack was made, if it is not already set
+  pub fn add_call_site(&mut self, start: usize, end: usize) {
+    if let Some(f) = self.stack.last_mut() {
+      if f.call_site.is_none() {
+        f.call_site = Some((start, end));
+      }
     }
   }
 
@@ -43,6 +104,18 @@ impl InterpretError {
   }
 }
 
+// Signature of the functions that implement builtins
+pub type BuiltInFn<C> = fn(
+  InterpretVal<C>,
+  &mut Frame<C>,
+  &Customs<C>,
+  String,
+) -> Result<InterpretVal<C>, InterpretError>;
+
+// Converts function values into return values
+pub type CallableConverter<'a, C> =
+  dyn Fn(&InterpretVal<C>) -> Result<ReturnVal<C>, InterpretError> + 'a;
+
 // Values within the interpreter
 // Cant use default implementations as CustomType cannot implement those types
 #[derive(Clone)]
@@ -50,19 +123,11 @@ pub enum InterpretVal<C: CustomType> {
   Int(i32),
   Bool(bool),
   String(String),
-  Function(Vec<Pattern>),
+  Function(String, Vec<Pattern>),
   Tuple(Vec<InterpretVal<C>>),
   List(Vec<InterpretVal<C>>),
   Lambda(Pattern, Frame<C>),
-  BuiltIn(
-    String,
-    fn(
-      InterpretVal<C>,
-      &mut Frame<C>,
-      &Customs<C>,
-      String,
-    ) -> Result<InterpretVal<C>, InterpretError>,
-  ),
+  BuiltIn(String, BuiltInFn<C>),
   Custom(C),
 }
 
@@ -85,7 +150,7 @@ impl<C: CustomType> Debug for InterpretVal<C> {
       InterpretVal::Int(n) => write!(f, "Int({:?})", n),
       InterpretVal::Bool(b) => write!(f, "Bool({:?})", b),
       InterpretVal::String(s) => write!(f, "String({:?})", s),
-      InterpretVal::Function(fun) => write!(f, "Function({:?})", fun),
+      InterpretVal::Function(_, fun) => write!(f, "Function({:?})", fun),
       InterpretVal::Tuple(t) => write!(f, "Tuple({:?})", t),
       InterpretVal::List(l) => write!(f, "List({:?})", l),
       InterpretVal::Lambda(l, _) => write!(f, "Lambda({:?})", l),
@@ -95,20 +160,95 @@ impl<C: CustomType> Debug for InterpretVal<C> {
   }
 }
 
-impl<C: CustomType> ToString for InterpretVal<C> {
+// The longest argument preview kept in a call stack
+const PREVIEW_LEN: usize = 40;
+
+impl<C: CustomType> InterpretVal<C> {
+  // The start of a value as it would be written in a script, stops once it is too long so large
+  //  lists are cheap to preview
+  // The arguments of a call with one argument are shown without the tuple around them
+  pub fn preview(&self) -> String {
+    struct Bounded(String);
+    impl std::fmt::Write for Bounded {
+      fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        for c in s.chars() {
+          if self.0.len() >= PREVIEW_LEN {
+            return Err(std::fmt::Error);
+          }
+          self.0.push(c);
+        }
+        Ok(())
+      }
+    }
+
+    let mut b = Bounded(String::new());
+    let res = match self {
+      InterpretVal::Tuple(t) if t.len() == 1 => t[0].write_syntax(&mut b),
+      v => v.write_syntax(&mut b),
+    };
+    if res.is_err() {
+      b.0.push_str("...");
+    }
+    b.0
+  }
+
+  // Writes a value as it would be written in a script
+  // Functions are written as their names, and lambdas as `<lambda>`
+  pub fn write_syntax(&self, w: &mut dyn std::fmt::Write) -> std::fmt::Result {
+    let items = |w: &mut dyn std::fmt::Write, open: &str, items: &[InterpretVal<C>]| {
+      w.write_str(open)?;
+      for (i, v) in items.iter().enumerate() {
+        if i > 0 {
+          w.write_str(", ")?;
+        }
+        v.write_syntax(w)?;
+      }
+      w.write_str(")")
+    };
+    match self {
+      InterpretVal::Int(i) => write!(w, "{}", i),
+      InterpretVal::Bool(b) => write!(w, "{}", b),
+      InterpretVal::String(s) => write!(w, "{:?}", s),
+      InterpretVal::Tuple(t) => items(w, "(", t),
+      InterpretVal::List(l) => items(w, "list(", l),
+      InterpretVal::Function(n, _) | InterpretVal::BuiltIn(n, _) => w.write_str(n),
+      InterpretVal::Lambda(..) => w.write_str("<lambda>"),
+      InterpretVal::Custom(c) => w.write_str(&c.to_string()),
+    }
+  }
+}
+
+impl<C: CustomType> Display for InterpretVal<C> {
   // Used to convert values into strings for when they are added in interpolation strings
-  fn to_string(&self) -> String {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     match self {
-      InterpretVal::Int(i) => i.to_string(),
-      InterpretVal::String(s) => s.to_string(),
-      InterpretVal::Bool(t) => t.to_string(),
-      InterpretVal::Tuple(t) => format!("({})", t.iter().map(|v| v.to_string()).join(", ")),
-      InterpretVal::List(t) => format!("[{}]", t.iter().map(|v| v.to_string()).join(", ")),
+      InterpretVal::Int(i) => write!(f, "{}", i),
+      InterpretVal::String(s) => write!(f, "{}", s),
+      InterpretVal::Bool(t) => write!(f, "{}", t),
+      InterpretVal::Tuple(t) => write!(f, "({})", t.iter().map(|v| v.to_string()).join(", ")),
+      InterpretVal::List(t) => write!(f, "[{}]", t.iter().map(|v| v.to_string()).join(", ")),
       _ => panic!("Type not found"),
     }
   }
 }
 
+// The result of an integer operation, or an error if it divides by zero or the result does not
+//  fit in an int
+fn checked<C: CustomType>(
+  l: i32,
+  op: &str,
+  r: i32,
+  res: Option<i32>,
+) -> Result<InterpretVal<C>, InterpretError> {
+  res.map(InterpretVal::Int).ok_or_else(|| {
+    let message = match (op, r) {
+      ("/" | "%", 0) => format!("Division by zero, {} {} {}.", l, op, r),
+      _ => format!("Integer overflow, {} {} {}.", l, op, r),
+    };
+    InterpretError::new_kind(ErrorKind::RuntimeError, &message)
+  })
+}
+
 impl<C: CustomType> InterpretVal<C> {
   // Unwraps a tuple of length 1 to its enclosed value
   pub fn unwrap_tuple(self) -> InterpretVal<C> {
@@ -123,11 +263,32 @@ impl<C: CustomType> InterpretVal<C> {
     }
   }
 
+  // Checks if a value has a type, type variables match any value
+  pub fn has_type(&self, t: &Type) -> bool {
+    match (self, t) {
+      (_, Type::Var(_)) => true,
+      (InterpretVal::Int(_), Type::Int)
+      | (InterpretVal::Bool(_), Type::Bool)
+      | (InterpretVal::String(_), Type::String)
+      | (InterpretVal::Custom(_), Type::Custom) => true,
+      (InterpretVal::Tuple(v), Type::Tuple(ts)) => {
+        v.len() == ts.len() && v.iter().zip(ts).all(|(v, t)| v.has_type(t))
+      }
+      (InterpretVal::List(v), Type::List(t)) => v.iter().all(|v| v.has_type(t)),
+      (
+        InterpretVal::Function(..) | InterpretVal::Lambda(..) | InterpretVal::BuiltIn(..),
+        Type::Function(..),
+      ) => true,
+      _ => false,
+    }
+  }
+
   // Creates a Interpret val from a interpret val
   pub fn from_arg(arg: &Argument<C>) -> Self {
     match arg {
       Argument::Int(x) => InterpretVal::Int(*x),
       Argument::String(s) => InterpretVal::String(s.clone()),
+      Argument::Bool(b) => InterpretVal::Bool(*b),
       Argument::Tuple(v) => InterpretVal::Tuple(v.iter().map(InterpretVal::from_arg).collect()),
       Argument::List(v) => InterpretVal::List(v.iter().map(InterpretVal::from_arg).collect()),
       Argument::Custom(c) => InterpretVal::Custom(c.clone()),
@@ -140,7 +301,7 @@ impl<C: CustomType> InterpretVal<C> {
       (InterpretVal::String(l), r) => {
         Ok(InterpretVal::String(l.clone().add(r.to_string().as_str())))
       }
-      (InterpretVal::Int(l), InterpretVal::Int(r)) => Ok(InterpretVal::Int(l + r)),
+      (InterpretVal::Int(l), InterpretVal::Int(r)) => checked(*l, "+", *r, l.checked_add(*r)),
       (InterpretVal::Custom(l), r) => l
         .pre_add(r.to_return_val()?)
         .map(|v| InterpretVal::from_arg(&v))
@@ -149,7 +310,8 @@ impl<C: CustomType> InterpretVal<C> {
         .post_add(l.to_return_val()?)
         .map(|v| InterpretVal::from_arg(&v))
         .map_err(InterpretError::from_custom),
-      (l, r) => Err(InterpretError::new(
+      (l, r) => Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         format!("Add operator not defined for {:?} + {:?}.", l, r).as_str(),
       )),
     }
@@ -158,7 +320,7 @@ impl<C: CustomType> InterpretVal<C> {
   // Subtracts v from this value
   pub fn sub_op(&self, v: &InterpretVal<C>) -> Result<InterpretVal<C>, InterpretError> {
     match (self, v) {
-      (InterpretVal::Int(l), InterpretVal::Int(r)) => Ok(InterpretVal::Int(l - r)),
+      (InterpretVal::Int(l), InterpretVal::Int(r)) => checked(*l, "-", *r, l.checked_sub(*r)),
       (InterpretVal::Custom(l), r) => l
         .pre_sub(r.to_return_val()?)
         .map(|v| InterpretVal::from_arg(&v))
@@ -167,7 +329,8 @@ impl<C: CustomType> InterpretVal<C> {
         .post_sub(l.to_return_val()?)
         .map(|v| InterpretVal::from_arg(&v))
         .map_err(InterpretError::from_custom),
-      (l, r) => Err(InterpretError::new(
+      (l, r) => Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         format!("Subtract operator not defined for {:?} - {:?}.", l, r).as_str(),
       )),
     }
@@ -176,10 +339,17 @@ impl<C: CustomType> InterpretVal<C> {
   // Multiplies this value by v
   pub fn mult_op(&self, v: &InterpretVal<C>) -> Result<InterpretVal<C>, InterpretError> {
     match (self, v) {
-      (InterpretVal::Int(l), InterpretVal::Int(r)) => Ok(InterpretVal::Int(l * r)),
-      (InterpretVal::String(l), InterpretVal::Int(r)) => {
-        Ok(InterpretVal::String(l.repeat(*r as usize)))
-      }
+      (InterpretVal::Int(l), InterpretVal::Int(r)) => checked(*l, "*", *r, l.checked_mul(*r)),
+      (InterpretVal::String(l), InterpretVal::Int(r)) => match usize::try_from(*r) {
+        Ok(r) => Ok(InterpretVal::String(l.repeat(r))),
+        Err(_) => Err(InterpretError::new_kind(
+          ErrorKind::RuntimeError,
+          &format!(
+            "Can't repeat a string a negative number of times, {:?} * {}.",
+            l, r
+          ),
+        )),
+      },
       (InterpretVal::Custom(l), r) => l
         .pre_mult(r.to_return_val()?)
         .map(|v| InterpretVal::from_arg(&v))
@@ -188,15 +358,17 @@ impl<C: CustomType> InterpretVal<C> {
         .post_mult(l.to_return_val()?)
         .map(|v| InterpretVal::from_arg(&v))
         .map_err(InterpretError::from_custom),
-      (l, r) => Err(InterpretError::new(
+      (l, r) => Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         format!("Multiplication operator not defined for {:?} * {:?}.", l, r).as_str(),
       )),
     }
   }
 
+  // Divides this value by v, rounding towards zero
   pub fn div_op(&self, v: &InterpretVal<C>) -> Result<InterpretVal<C>, InterpretError> {
     match (self, v) {
-      (InterpretVal::Int(l), InterpretVal::Int(r)) => Ok(InterpretVal::Int(l / r)),
+      (InterpretVal::Int(l), InterpretVal::Int(r)) => checked(*l, "/", *r, l.checked_div(*r)),
       (InterpretVal::Custom(l), r) => l
         .pre_div(r.to_return_val()?)
         .map(|v| InterpretVal::from_arg(&v))
@@ -205,7 +377,8 @@ impl<C: CustomType> InterpretVal<C> {
         .post_div(l.to_return_val()?)
         .map(|v| InterpretVal::from_arg(&v))
         .map_err(InterpretError::from_custom),
-      (l, r) => Err(InterpretError::new(
+      (l, r) => Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         format!("Division operator not defined for {:?} / {:?}.", l, r).as_str(),
       )),
     }
@@ -214,7 +387,7 @@ impl<C: CustomType> InterpretVal<C> {
   // Finds the value of this value modulo v
   pub fn modulo_op(&self, v: &InterpretVal<C>) -> Result<InterpretVal<C>, InterpretError> {
     match (self, v) {
-      (InterpretVal::Int(l), InterpretVal::Int(r)) => Ok(InterpretVal::Int(l % r)),
+      (InterpretVal::Int(l), InterpretVal::Int(r)) => checked(*l, "%", *r, l.checked_rem(*r)),
       (InterpretVal::Custom(l), r) => l
         .pre_mod(r.to_return_val()?)
         .map(|v| InterpretVal::from_arg(&v))
@@ -223,7 +396,8 @@ impl<C: CustomType> InterpretVal<C> {
         .post_mod(l.to_return_val()?)
         .map(|v| InterpretVal::from_arg(&v))
         .map_err(InterpretError::from_custom),
-      (l, r) => Err(InterpretError::new(
+      (l, r) => Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         format!("Modulo operator not defined for {:?} % {:?}.", l, r).as_str(),
       )),
     }
@@ -243,9 +417,10 @@ impl<C: CustomType> InterpretVal<C> {
             .map(|(l, r)| l.eq(&r))
             .fold_ok(true, |l, r| l && r)?,
       ),
-      (InterpretVal::Function(_), InterpretVal::Function(_)) => {
-        Err(InterpretError::new("Cannot compare functions."))
-      }
+      (InterpretVal::Function(..), InterpretVal::Function(..)) => Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
+        "Cannot compare functions.",
+      )),
       (InterpretVal::List(l), InterpretVal::List(r)) => Ok(
         l.len() == r.len()
           && l
@@ -256,11 +431,12 @@ impl<C: CustomType> InterpretVal<C> {
       ),
       (InterpretVal::Custom(l), r) => l
         .pre_eq(r.to_return_val()?)
-        .map_err(|s| InterpretError::from_custom(s)),
+        .map_err(InterpretError::from_custom),
       (l, InterpretVal::Custom(r)) => r
         .post_eq(l.to_return_val()?)
-        .map_err(|s| InterpretError::from_custom(s)),
-      (l, r) => Err(InterpretError::new(
+        .map_err(InterpretError::from_custom),
+      (l, r) => Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         format!("Non matching types for equality: {:?} == {:?}", l, r).as_str(),
       )),
     }
@@ -286,7 +462,8 @@ impl<C: CustomType> InterpretVal<C> {
       (l, InterpretVal::Custom(r)) => r
         .post_lt(l.to_return_val()?)
         .map_err(InterpretError::from_custom),
-      (l, r) => Err(InterpretError::new(
+      (l, r) => Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         format!("Comparison of types not supported {:?} < {:?}.", l, r).as_str(),
       )),
     }
@@ -302,7 +479,8 @@ impl<C: CustomType> InterpretVal<C> {
       (l, InterpretVal::Custom(r)) => r
         .post_gt(l.to_return_val()?)
         .map_err(InterpretError::from_custom),
-      (l, r) => Err(InterpretError::new(
+      (l, r) => Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         format!("Comparison of types not supported {:?} > {:?}.", l, r).as_str(),
       )),
     }
@@ -318,7 +496,8 @@ impl<C: CustomType> InterpretVal<C> {
       (l, InterpretVal::Custom(r)) => r
         .post_leq(l.to_return_val()?)
         .map_err(InterpretError::from_custom),
-      (l, r) => Err(InterpretError::new(
+      (l, r) => Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         format!("Comparison of types not supported {:?} <= {:?}.", l, r).as_str(),
       )),
     }
@@ -334,7 +513,8 @@ impl<C: CustomType> InterpretVal<C> {
       (l, InterpretVal::Custom(r)) => r
         .post_geq(l.to_return_val()?)
         .map_err(InterpretError::from_custom),
-      (l, r) => Err(InterpretError::new(
+      (l, r) => Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         format!("Comparison of types not supported {:?} >= {:?}.", l, r).as_str(),
       )),
     }
@@ -350,7 +530,8 @@ impl<C: CustomType> InterpretVal<C> {
       (l, InterpretVal::Custom(r)) => r
         .post_and(l.to_return_val()?)
         .map_err(InterpretError::from_custom),
-      (l, r) => Err(InterpretError::new(
+      (l, r) => Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         format!("And operator not supported for {:?} && {:?}.", l, r).as_str(),
       )),
     }
@@ -366,7 +547,8 @@ impl<C: CustomType> InterpretVal<C> {
       (l, InterpretVal::Custom(r)) => r
         .post_or(l.to_return_val()?)
         .map_err(InterpretError::from_custom),
-      (l, r) => Err(InterpretError::new(
+      (l, r) => Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         format!("And operator not supported for {:?} && {:?}.", l, r).as_str(),
       )),
     }
@@ -374,29 +556,42 @@ impl<C: CustomType> InterpretVal<C> {
 
   // Converts an interpret value to a return val that can be returned through the API
   pub fn to_return_val(&self) -> Result<ReturnVal<C>, InterpretError> {
+    self.to_return_val_with(&|v| match v {
+      InterpretVal::Function(..) => Err(InterpretError::new(
+        "Cannot have function return type to root.",
+      )),
+      InterpretVal::Lambda(_, _) => Err(InterpretError::new(
+        "Cannot have lambda return type to root.",
+      )),
+      _ => Err(InterpretError::new(
+        "Cannot have built-in return type to root.",
+      )),
+    })
+  }
+
+  // Converts an interpret value to a return val, using `callable` to convert any functions,
+  //  lambdas or builtins within the value
+  pub fn to_return_val_with(
+    &self,
+    callable: &CallableConverter<'_, C>,
+  ) -> Result<ReturnVal<C>, InterpretError> {
     match self {
       InterpretVal::Int(i) => Ok(ReturnVal::Int(*i)),
       InterpretVal::Bool(b) => Ok(ReturnVal::Bool(*b)),
       InterpretVal::String(s) => Ok(ReturnVal::String(s.clone())),
       InterpretVal::Tuple(v) => Ok(ReturnVal::Tuple(
         v.iter()
-          .map(|x| x.to_return_val())
+          .map(|x| x.to_return_val_with(callable))
           .collect::<Result<Vec<ReturnVal<C>>, InterpretError>>()?,
       )),
       InterpretVal::List(v) => Ok(ReturnVal::List(
         v.iter()
-          .map(|x| x.to_return_val())
+          .map(|x| x.to_return_val_with(callable))
           .collect::<Result<Vec<ReturnVal<C>>, InterpretError>>()?,
       )),
-      InterpretVal::Function(_) => Err(InterpretError::new(
-        "Cannot have function return type to root.",
-      )),
-      InterpretVal::Lambda(_, _) => Err(InterpretError::new(
-        "Cannot have lambda return type to root.",
-      )),
-      InterpretVal::BuiltIn(_, _) => Err(InterpretError::new(
-        "Cannot have built-in return type to root.",
-      )),
+      InterpretVal::Function(..) | InterpretVal::Lambda(_, _) | InterpretVal::BuiltIn(_, _) => {
+        callable(self)
+      }
       InterpretVal::Custom(c) => Ok(ReturnVal::Custom((*c).clone())),
     }
   }
@@ -419,15 +614,16 @@ impl Debug for InterpretError {
 // Frame for holding the environment in an execution of a program
 #[derive(Debug, Clone, PartialEq)]
 pub struct Frame<C: CustomType> {
-  pub(crate) frame: Rc<HashMap<String, InterpretVal<C>>>,
-  next: Option<RefCell<Box<Frame<C>>>>,
+  pub(crate) frame: Arc<HashMap<String, InterpretVal<C>>>,
+  next: Option<Arc<Frame<C>>>,
 }
 
 impl<C: CustomType> Frame<C> {
   // Creates a new blank frame
+  #[cfg(test)]
   pub fn new() -> Self {
     Self {
-      frame: Rc::new(HashMap::new()),
+      frame: Arc::new(HashMap::new()),
       next: None,
     }
   }
@@ -435,21 +631,37 @@ impl<C: CustomType> Frame<C> {
   // Builds a new frame from a template
   pub fn from_template(t: &Program) -> Self {
     Self {
-      frame: Rc::new(
+      frame: Arc::new(
         t.env
           .iter()
-          .map(|(a, b)| (a.clone(), InterpretVal::Function(b.clone())))
+          .map(|(a, b)| (a.clone(), InterpretVal::Function(a.clone(), b.clone())))
           .collect(),
       ),
       next: None,
     }
   }
 
+  // Builds a new frame from a template, with a frame of global values below it
+  // Globals are only found if a name is not bound by a pattern or a function
+  pub fn from_template_with_globals(
+    t: &Program,
+    globals: HashMap<String, InterpretVal<C>>,
+  ) -> Self {
+    let mut frame = Self::from_template(t);
+    if !globals.is_empty() {
+      frame.next = Some(Arc::new(Self {
+        frame: Arc::new(globals),
+        next: None,
+      }));
+    }
+    frame
+  }
+
   // Creates a new frame from the required values
   pub fn new_from_vals(vals: HashMap<String, InterpretVal<C>>, next: Frame<C>) -> Self {
     Self {
-      frame: Rc::new(vals),
-      next: Some(RefCell::new(Box::new(next.clone()))),
+      frame: Arc::new(vals),
+      next: Some(Arc::new(next)),
     }
   }
 
@@ -462,22 +674,31 @@ impl<C: CustomType> Frame<C> {
         if let Some(r) = self.frame.get(name) {
           Ok(r.clone())
         } else if let Some(n) = &self.next {
-          n.borrow().find(name)
+          n.find(name)
         } else {
-          Err(InterpretError::new(&*format!(
-            "Cannot find value {}.",
-            name
-          )))
+          Err(InterpretError::new_kind(
+            ErrorKind::UnknownVariable,
+            &format!("Cannot find value {}.", name),
+          ))
         }
       }
     }
   }
 
+  // The names bound in this frame and every frame below it
+  pub fn names(&self) -> Vec<&str> {
+    let mut names: Vec<&str> = self.frame.keys().map(|k| k.as_str()).collect();
+    if let Some(n) = &self.next {
+      names.extend(n.names());
+    }
+    names
+  }
+
   // Sets the next frame in the linked list of frames
   // Note the clone, this can be done as the pure functional nature of the language prevents the
   //  higher frames being mutated while values in a lower function are modified
-  // Could be replaced with a Rc for less data copying
+  // Frames are shared through an Arc so a frame can be captured and used from other threads
   pub fn set_next(&mut self, next: &Frame<C>) {
-    self.next = Some(RefCell::new(Box::new((*next).clone())))
+    self.next = Some(Arc::new(next.clone()))
   }
 }
@@ -0,0 +1,116 @@
+mod test;
+
+/// The format of the reference pages made by `Script::reference`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocFormat {
+  /// A Markdown document.
+  Markdown,
+  /// A standalone HTML page.
+  Html,
+}
+
+// A function on a reference page, from the script, a builtin or added by the host
+pub struct Entry {
+  pub name: String,
+  // The type of the function, if it is known
+  pub signature: Option<String>,
+  pub doc: String,
+  // The code of the function, with each pattern and its guards on their own line
+  pub code: Option<String>,
+}
+
+// A reference page, with a heading for each section of functions
+pub struct Page {
+  pub title: String,
+  pub sections: Vec<(String, Vec<Entry>)>,
+}
+
+// Describes what a builtin does
+pub fn builtin_doc(name: &str) -> &'static str {
+  match name {
+    "list" => "Makes a list of its arguments.",
+    "get" => "The item of a list at an index, counting from 0.",
+    "map" => "Calls a function with each item of a list, returning the list of results.",
+    "filter" => "The items of a list a function returns true for.",
+    "len" => "The number of items in a list.",
+    "any" => "Whether a function returns true for any item of a list.",
+    "all" => "Whether a function returns true for every item of a list.",
+    "fold" => {
+      "Combines the items of a list, calling a function with the result so far, starting with \
+       the second argument, and each item."
+    }
+    "assert_eq" => {
+      "Returns true if its two arguments are equal, otherwise fails with where the first, the \
+       actual value, differs from the second, the expected value."
+    }
+    _ => "",
+  }
+}
+
+impl Page {
+  // Renders the page, leaving out empty sections
+  pub fn render(&self, format: DocFormat) -> String {
+    match format {
+      DocFormat::Markdown => self.markdown(),
+      DocFormat::Html => self.html(),
+    }
+  }
+
+  // Renders the page as Markdown, docs are already Markdown so are kept as they are
+  fn markdown(&self) -> String {
+    let mut res = format!("# {}\n", self.title);
+    for (heading, entries) in self.sections.iter().filter(|(_, e)| !e.is_empty()) {
+      res.push_str(&format!("\n## {}\n", heading));
+      for e in entries {
+        res.push_str(&format!("\n### `{}`\n", e.name));
+        if let Some(s) = &e.signature {
+          res.push_str(&format!("\n`{}: {}`\n", e.name, s));
+        }
+        if !e.doc.is_empty() {
+          res.push_str(&format!("\n{}\n", e.doc));
+        }
+        if let Some(c) = &e.code {
+          res.push_str(&format!("\n```funki\n{}\n```\n", c));
+        }
+      }
+    }
+    res
+  }
+
+  // Renders the page as HTML, each blank line in a doc starts a new paragraph
+  fn html(&self) -> String {
+    let title = escape(&self.title);
+    let mut res = format!(
+      "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n\
+       <body>\n<h1>{}</h1>\n",
+      title, title
+    );
+    for (heading, entries) in self.sections.iter().filter(|(_, e)| !e.is_empty()) {
+      res.push_str(&format!("<h2>{}</h2>\n", escape(heading)));
+      for e in entries {
+        let name = escape(&e.name);
+        res.push_str(&format!("<h3 id=\"{}\"><code>{}</code></h3>\n", name, name));
+        if let Some(s) = &e.signature {
+          res.push_str(&format!("<p><code>{}: {}</code></p>\n", name, escape(s)));
+        }
+        for p in e.doc.split("\n\n").filter(|p| !p.trim().is_empty()) {
+          res.push_str(&format!("<p>{}</p>\n", escape(p.trim())));
+        }
+        if let Some(c) = &e.code {
+          res.push_str(&format!("<pre><code>{}</code></pre>\n", escape(c)));
+        }
+      }
+    }
+    res.push_str("</body>\n</html>\n");
+    res
+  }
+}
+
+// Escapes the characters with a meaning in HTML
+fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
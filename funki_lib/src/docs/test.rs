@@ -0,0 +1,150 @@
+#[cfg(test)]
+use crate::{BlankCustom, DocFormat, Script};
+
+// A script with doc comments on some of its functions
+#[cfg(test)]
+const CODE: &str = "/// Adds up a list
+///
+/// Empty lists add up to 0.
+#sum xs -> fold(xs, 0, |(a, b) => a + b|);
+
+// Not a doc comment
+#sign n -> 1 | n > 0;
+  n -> 0;
+
+/// Compares `a < b` & more
+#less (a, b) -> a < b;
+";
+
+// Tests doc comments are attached to the function just after them
+#[test]
+fn test_doc_comments() {
+  let script = Script::<BlankCustom>::from_text(CODE).unwrap();
+  assert_eq!(
+    script.doc("sum"),
+    Some("Adds up a list\n\nEmpty lists add up to 0.")
+  );
+  assert_eq!(script.doc("sign"), None);
+  assert_eq!(script.doc("less"), Some("Compares `a < b` & more"));
+  assert_eq!(
+    script.symbols()[2].doc.as_deref(),
+    Some("Compares `a < b` & more")
+  );
+
+  // A doc comment must be on its own line, just before the function
+  let script =
+    Script::<BlankCustom>::from_text("#a 1; /// trailing\n#b 2;\n/// gap\n\n#c 3;").unwrap();
+  assert_eq!(script.doc("b"), None);
+  assert_eq!(script.doc("c"), None);
+}
+
+// Tests reference pages list functions with their docs, types and patterns, then the builtins
+#[test]
+fn test_reference_markdown() {
+  let script = Script::<BlankCustom>::from_text(CODE).unwrap();
+  let page = script.reference("numbers", DocFormat::Markdown);
+  assert!(page.starts_with(
+    "# numbers
+
+## Functions
+
+### `sum`
+
+`sum: [Int] -> Int`
+
+Adds up a list
+
+Empty lists add up to 0.
+
+```funki
+#sum xs -> fold(xs, 0, |(a, b) => a + b|);
+```
+
+### `sign`
+
+`sign: Int -> Int`
+
+```funki
+#sign n -> 1 | n > 0;
+  n -> 0;
+```
+"
+  ));
+  assert!(page.contains(
+    "## Builtins
+
+### `list`
+
+Makes a list of its arguments.
+
+### `get`
+
+`get: ([a], Int) -> a`
+"
+  ));
+  assert!(!page.contains("## Host functions"));
+}
+
+// Tests HTML reference pages escape the code and docs
+#[test]
+fn test_reference_html() {
+  let script = Script::<BlankCustom>::from_text(CODE).unwrap();
+  let page = script.reference("<numbers>", DocFormat::Html);
+  assert!(page.starts_with("<!DOCTYPE html>"));
+  assert!(page.contains("<title>&lt;numbers&gt;</title>"));
+  assert!(page.contains(
+    "<h3 id=\"less\"><code>less</code></h3>
+<p><code>less: (Int, Int) -&gt; Bool</code></p>
+<p>Compares `a &lt; b` &amp; more</p>
+<pre><code>#less (a, b) -&gt; a &lt; b;</code></pre>
+"
+  ));
+  assert!(page.contains("<p>Adds up a list</p>\n<p>Empty lists add up to 0.</p>\n"));
+  assert!(page.ends_with("</body>\n</html>\n"));
+}
+
+// Tests reference pages list the functions added by the host, which replace builtins
+#[test]
+fn test_reference_host_functions() {
+  use crate::external_operators::{CustomBuiltIn, TypedBuiltIn};
+  use crate::types::Type;
+  use crate::{Argument, Language};
+  let mut lang = Language::<BlankCustom>::new();
+  lang.add_typed_function(
+    "pad".to_string(),
+    TypedBuiltIn {
+      params: vec![
+        ("text".to_string(), Type::String),
+        ("width".to_string(), Type::Int),
+      ],
+      result: Type::String,
+      function: |_| Ok(Argument::String(String::new())),
+    },
+  );
+  lang.add_custom_function(
+    "len".to_string(),
+    CustomBuiltIn {
+      function: |_| Ok(Argument::Int(0)),
+    },
+  );
+  lang.remove_builtin("fold");
+  let page = lang
+    .parse("#main 1;".to_string())
+    .unwrap()
+    .reference("host", DocFormat::Markdown);
+
+  assert!(page.ends_with(
+    "## Host functions
+
+### `len`
+
+### `pad`
+
+`pad: (String, Int) -> String`
+
+Takes `text: String`, `width: Int`.
+"
+  ));
+  assert!(!page.contains("### `fold`"));
+  assert_eq!(page.matches("### `len`").count(), 1);
+}
@@ -0,0 +1,434 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::Range;
+use std::sync::Arc;
+
+use lalrpop_util::ParseError;
+
+use crate::ast::SyntaxError;
+use crate::data_types::InterpretError;
+use crate::limits::Limit;
+
+mod render;
+
+/// The kind of failure a `LanguageErr` represents.
+///
+/// ## Example
+/// ```
+/// use funki_lang::{Argument, Script, BlankCustom, ErrorKind};
+/// let x = Script::<BlankCustom>::from_text("#main x -> x + 1;").unwrap();
+/// let err = x.function("main").unwrap().arg(Argument::List(vec![])).call().unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::TypeMismatch);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+  /// The code could not be parsed.
+  ParseError,
+  /// The code ended before a definition was finished.
+  UnexpectedEof,
+  /// A function that does not exist was requested.
+  UnknownFunction,
+  /// A name used in the code is not a variable, function, global or builtin.
+  UnknownVariable,
+  /// A value had the wrong type for an operator or builtin.
+  TypeMismatch,
+  /// No pattern of a function or lambda matched its argument.
+  NoMatchingPattern,
+  /// A custom operator or function provided by the host returned an error.
+  HostError,
+  /// A limit set with `Limits` was exceeded.
+  LimitExceeded(Limit),
+  /// The call was cancelled with the token set by `LangFunc::cancel_token`.
+  Cancelled,
+  /// The code uses a builtin or feature the language does not allow.
+  SandboxViolation,
+  /// A global could not be set.
+  InvalidGlobal,
+  /// The values given to `assert_eq` were not equal.
+  AssertionFailed,
+  /// Any other error from evaluating the code.
+  RuntimeError,
+}
+
+impl Display for ErrorKind {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ErrorKind::ParseError => write!(fmt, "parse error"),
+      ErrorKind::UnexpectedEof => write!(fmt, "unexpected end of file"),
+      ErrorKind::UnknownFunction => write!(fmt, "unknown function"),
+      ErrorKind::UnknownVariable => write!(fmt, "unknown variable"),
+      ErrorKind::TypeMismatch => write!(fmt, "type mismatch"),
+      ErrorKind::NoMatchingPattern => write!(fmt, "no matching pattern"),
+      ErrorKind::HostError => write!(fmt, "host error"),
+      ErrorKind::LimitExceeded(l) => write!(fmt, "{} exceeded", l),
+      ErrorKind::Cancelled => write!(fmt, "cancelled"),
+      ErrorKind::SandboxViolation => write!(fmt, "sandbox violation"),
+      ErrorKind::InvalidGlobal => write!(fmt, "invalid global"),
+      ErrorKind::AssertionFailed => write!(fmt, "assertion failed"),
+      ErrorKind::RuntimeError => write!(fmt, "runtime error"),
+    }
+  }
+}
+
+/// A section of the code an error refers to.
+/// Lines and columns start at 1, and columns count characters rather than bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Span {
+  /// The byte offset of the start of the section.
+  pub start: usize,
+  /// The byte offset just after the end of the section.
+  pub end: usize,
+  /// The line the section starts on.
+  pub start_line: usize,
+  /// The column the section starts at.
+  pub start_col: usize,
+  /// The line the section ends on.
+  pub end_line: usize,
+  /// The column just after the end of the section.
+  pub end_col: usize,
+}
+
+impl Span {
+  // Finds the lines and columns of a byte range in the code
+  pub(crate) fn new(lang: &str, start: usize, end: usize) -> Self {
+    let (start_line, start_col) = line_col(lang, start);
+    let (end_line, end_col) = line_col(lang, end);
+    Self {
+      start,
+      end,
+      start_line,
+      start_col,
+      end_line,
+      end_col,
+    }
+  }
+
+  /// The byte range of the section.
+  pub fn range(&self) -> Range<usize> {
+    self.start..self.end
+  }
+}
+
+// Finds the 1 based line and character column of a byte offset
+fn line_col(lang: &str, pos: usize) -> (usize, usize) {
+  let before = &lang[..pos];
+  let line = before.matches('\n').count() + 1;
+  let col = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+  (line, col)
+}
+
+/// A secondary location related to an error, such as a pattern that did not match
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Label {
+  /// The section of code the label points at.
+  pub span: Span,
+  /// What the label says about the section.
+  pub message: String,
+}
+
+/// A call a runtime error unwound through
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackFrame {
+  /// The name of the function, builtin, or `<lambda>` for lambdas.
+  pub function: String,
+  /// Where the call was made, `None` if it was made by the host or from within a builtin.
+  pub call_site: Option<Span>,
+  /// The index of the pattern being evaluated when the error happened, `None` if the error
+  /// happened outside of a pattern, such as when no pattern matched.
+  pub pattern: Option<usize>,
+  /// The start of the debug output of the argument the function was called with.
+  pub argument: String,
+}
+
+/// An error from parsing or running code
+///
+/// ## Example
+/// ```
+/// use funki_lang::{Script, BlankCustom, ErrorKind};
+/// let err = Script::<BlankCustom>::from_text("#main x -> x +").unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+/// assert_eq!(err.line_col(), Some((1, 15)));
+/// ```
+pub struct LanguageErr {
+  kind: ErrorKind,
+  message: String,
+  // Boxed to keep results with this error small
+  location: Option<Box<ErrLocation>>,
+  stack: Vec<StackFrame>,
+  // Further errors found along with this one, such as other syntax errors in the same code
+  others: Vec<LanguageErr>,
+}
+
+// Where an error is in the code
+struct ErrLocation {
+  span: Span,
+  section: String,
+  labels: Vec<Label>,
+  // The code the error is in, kept for rendering, shared by the errors found in the same code
+  source: Arc<str>,
+  // Positions as shown by the Debug output
+  lines: (usize, usize),
+  char: (usize, usize),
+}
+
+impl LanguageErr {
+  /// The kind of error
+  pub fn kind(&self) -> ErrorKind {
+    self.kind
+  }
+
+  /// The error message, without location information
+  pub fn message(&self) -> &str {
+    &self.message
+  }
+
+  /// The section of code the error refers to, if it has one
+  pub fn span(&self) -> Option<Span> {
+    self.location.as_ref().map(|l| l.span)
+  }
+
+  /// The byte range of the code the error refers to, if it has one
+  pub fn range(&self) -> Option<Range<usize>> {
+    self.span().map(|s| s.range())
+  }
+
+  /// Other sections of code related to the error
+  pub fn labels(&self) -> &[Label] {
+    self.location.as_ref().map_or(&[], |l| &l.labels)
+  }
+
+  /// The calls a runtime error unwound through, innermost first
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Argument, Script, BlankCustom};
+  /// let x = Script::<BlankCustom>::from_text("#main xs -> map(xs, inc);\n#inc x -> x + 1;").unwrap();
+  /// let err = x
+  ///   .function("main")
+  ///   .unwrap()
+  ///   .arg(Argument::List(vec![Argument::List(vec![])]))
+  ///   .call()
+  ///   .unwrap_err();
+  /// let names: Vec<_> = err.stack().iter().map(|f| f.function.as_str()).collect();
+  /// assert_eq!(names, vec!["inc", "map", "main"]);
+  /// ```
+  pub fn stack(&self) -> &[StackFrame] {
+    &self.stack
+  }
+
+  /// Every error found, starting with this one
+  /// Parsing reports all the syntax errors it can recover from, and all the type errors when type
+  /// checking is enabled, so there can be more than one
+  pub fn errors(&self) -> impl Iterator<Item = &LanguageErr> {
+    std::iter::once(self).chain(self.others.iter())
+  }
+
+  /// The line and column the error starts at, if it has a location
+  pub fn line_col(&self) -> Option<(usize, usize)> {
+    self.span().map(|s| (s.start_line, s.start_col))
+  }
+
+  // The code the error is in, if it has a location
+  #[cfg(test)]
+  pub(crate) fn source(&self) -> Option<&str> {
+    self.location.as_ref().map(|l| &*l.source)
+  }
+
+  // Creates a language error with location information
+  // Adds in the original language string so the line numbers and string section can be found
+  pub(crate) fn new_loc(
+    kind: ErrorKind,
+    message: String,
+    location: (usize, usize),
+    lang: &Arc<str>,
+  ) -> Self {
+    Self::new_section(
+      kind,
+      message,
+      location,
+      lang[location.0..location.1].to_string(),
+      lang,
+    )
+  }
+
+  // Creates a language error with location information and a given section of code
+  fn new_section(
+    kind: ErrorKind,
+    message: String,
+    location: (usize, usize),
+    section: String,
+    lang: &Arc<str>,
+  ) -> Self {
+    let (start_line, start_char) = get_lang_pos(lang, location.0);
+    let (end_line, end_char) = get_lang_pos(lang, location.1);
+    Self {
+      kind,
+      message,
+      stack: vec![],
+      others: vec![],
+      location: Some(Box::new(ErrLocation {
+        span: Span::new(lang, location.0, location.1),
+        section,
+        labels: vec![],
+        source: lang.clone(),
+        lines: (start_line, end_line),
+        char: (start_char, end_char),
+      })),
+    }
+  }
+
+  // Creates a language error with no location data
+  pub(crate) fn new_no_loc(kind: ErrorKind, message: String) -> Self {
+    Self {
+      kind,
+      message,
+      location: None,
+      stack: vec![],
+      others: vec![],
+    }
+  }
+
+  // Creates a language error from an interpretation error
+  pub(crate) fn new_from_int_err(err: InterpretError, lang: &str) -> Self {
+    let stack = err
+      .stack
+      .into_iter()
+      .map(|f| StackFrame {
+        function: f.name,
+        call_site: f.call_site.map(|(start, end)| Span::new(lang, start, end)),
+        pattern: f.pattern,
+        argument: f.arg,
+      })
+      .collect();
+    let mut e = match err.location {
+      Some(location) if err.kind != ErrorKind::Cancelled => {
+        let mut e = Self::new_loc(err.kind, err.message, location, &Arc::from(lang));
+        if let Some(l) = &mut e.location {
+          l.labels = err
+            .labels
+            .into_iter()
+            .map(|((start, end), message)| Label {
+              span: Span::new(lang, start, end),
+              message,
+            })
+            .collect();
+        }
+        e
+      }
+      _ => Self::new_no_loc(err.kind, err.message),
+    };
+    e.stack = stack;
+    e
+  }
+
+  // Creates a language error from a set of parser errors, in the order they are in the code
+  pub(crate) fn new_from_parser_errs(errs: Vec<SyntaxError>, lang: &str) -> Self {
+    let lang = Arc::from(lang);
+    let errs: Vec<Self> = errs
+      .into_iter()
+      .map(|e| Self::new_from_parser_err(e, &lang))
+      .collect();
+    Self::new_multiple(errs)
+  }
+
+  // Combines a non-empty set of errors into one, in the order they are in the code
+  pub(crate) fn new_multiple(mut errs: Vec<Self>) -> Self {
+    errs.sort_by_key(|e| e.range().map(|r| (r.start, r.end)));
+    let mut first = errs.remove(0);
+    first.others = errs;
+    first
+  }
+
+  // Creates a language error from a parser error
+  fn new_from_parser_err(err: SyntaxError, lang: &Arc<str>) -> Self {
+    match err {
+      ParseError::InvalidToken { location } => Self::new_section(
+        ErrorKind::ParseError,
+        "Invalid token".to_string(),
+        (location, location),
+        lang[location..].chars().take(10).collect(),
+        lang,
+      ),
+      ParseError::UnrecognizedEOF { location, .. } => Self::new_section(
+        ErrorKind::UnexpectedEof,
+        "Unexpected End of File".to_string(),
+        (location, location),
+        lang[..location].to_string(),
+        lang,
+      ),
+      ParseError::UnrecognizedToken {
+        token: (l, _, r), ..
+      } => Self::new_loc(
+        ErrorKind::ParseError,
+        "Unrecognised token".to_string(),
+        (l, r),
+        lang,
+      ),
+      ParseError::ExtraToken {
+        token: (l, _, r), ..
+      } => Self::new_loc(
+        ErrorKind::ParseError,
+        "Extra token".to_string(),
+        (l, r),
+        lang,
+      ),
+      ParseError::User { error: (l, m, r) } => {
+        Self::new_loc(ErrorKind::ParseError, m, (l, r), lang)
+      }
+    }
+  }
+}
+
+fn get_lang_pos(lang: &str, pos: usize) -> (usize, usize) {
+  let mut new_lines = lang.as_bytes()[0..pos]
+    .iter()
+    .enumerate()
+    .filter(|(_, c)| **c == b'\n');
+  let line_num = new_lines.clone().count();
+  let char = pos - new_lines.next_back().unwrap_or((0, &b'x')).0;
+
+  (line_num, char)
+}
+
+impl Debug for LanguageErr {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+    match &self.location {
+      Some(l) => write!(
+        fmt,
+        "Error: \"{}\"\nAt lines: {}:{} - {}:{}\nCode: `{}`",
+        self.message,
+        l.lines.0 + 1,
+        l.char.0,
+        l.lines.1 + 1,
+        l.char.1,
+        l.section
+      ),
+      None => write!(fmt, "Error: {}", self.message),
+    }?;
+    for e in &self.others {
+      write!(fmt, "\n\n{:?}", e)?;
+    }
+    Ok(())
+  }
+}
+
+impl Display for LanguageErr {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+    match self.span() {
+      Some(s) => write!(
+        fmt,
+        "{} at line {}, column {}",
+        self.message, s.start_line, s.start_col
+      ),
+      None => write!(fmt, "{}", self.message),
+    }?;
+    match self.others.len() {
+      0 => Ok(()),
+      1 => write!(fmt, " (and 1 more error)"),
+      n => write!(fmt, " (and {} more errors)", n),
+    }
+  }
+}
+
+impl std::error::Error for LanguageErr {}
@@ -0,0 +1,168 @@
+use std::collections::BTreeSet;
+
+use itertools::Itertools;
+
+use crate::errors::{LanguageErr, Span};
+
+// ANSI codes used when rendering with colours
+const RED: &str = "1;31";
+const BLUE: &str = "1;34";
+const BOLD: &str = "1";
+
+impl LanguageErr {
+  /// Renders the error with the lines of code it refers to, underlining the section of code with
+  /// `^` and any labels with `-`, followed by the calls it unwound through.
+  /// With `colours` set the output uses ANSI colour codes, otherwise it is plain text for logs.
+  /// When there is more than one error, see `LanguageErr::errors`, each is rendered in turn.
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Argument, Script, BlankCustom};
+  /// let x = Script::<BlankCustom>::from_text("#main x ->\n  x + 1;").unwrap();
+  /// let err = x.function("main").unwrap().arg(Argument::List(vec![])).call().unwrap_err();
+  /// assert_eq!(
+  ///   err.render(false),
+  ///   "error[type mismatch]: Add operator not defined for List([]) + Int(1).
+  ///  --> 2:3
+  ///   |
+  /// 2 |   x + 1;
+  ///   |   ^^^^^
+  ///   = in `main`, pattern 0, with list()
+  /// "
+  /// );
+  /// ```
+  pub fn render(&self, colours: bool) -> String {
+    self
+      .errors()
+      .map(|e| e.render_one(colours))
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+
+  // Renders this error on its own
+  fn render_one(&self, colours: bool) -> String {
+    let paint = |code: &str, s: &str| {
+      if colours {
+        format!("\x1b[{}m{}\x1b[0m", code, s)
+      } else {
+        s.to_string()
+      }
+    };
+
+    let mut out = format!(
+      "{}: {}\n",
+      paint(RED, &format!("error[{}]", self.kind)),
+      paint(BOLD, &self.message)
+    );
+    let loc = match &self.location {
+      Some(l) => l,
+      None => return out + &self.render_stack(0, &paint),
+    };
+
+    // The primary section is underlined first, then each label
+    let marks: Vec<(Span, &str, &str, char)> = std::iter::once((loc.span, "", RED, '^'))
+      .chain(
+        loc
+          .labels
+          .iter()
+          .map(|l| (l.span, l.message.as_str(), BLUE, '-')),
+      )
+      .collect();
+
+    let lines: Vec<&str> = loc
+      .source
+      .split('\n')
+      .map(|l| l.strip_suffix('\r').unwrap_or(l))
+      .collect();
+    let shown: BTreeSet<usize> = marks
+      .iter()
+      .flat_map(|(s, ..)| s.start_line..=last_line(s))
+      .collect();
+    let width = shown.iter().max().unwrap_or(&1).to_string().len();
+    let gutter = paint(BLUE, &format!("{} |", " ".repeat(width)));
+
+    out += &format!(
+      "{}{} {}:{}\n",
+      " ".repeat(width),
+      paint(BLUE, "-->"),
+      loc.span.start_line,
+      loc.span.start_col
+    );
+    out += &format!("{}\n", gutter);
+
+    let mut prev = None;
+    for n in shown {
+      if matches!(prev, Some(p) if n > p + 1) {
+        out += &format!("{}\n", paint(BLUE, "..."));
+      }
+      prev = Some(n);
+
+      let text = lines.get(n - 1).copied().unwrap_or("");
+      out += &format!(
+        "{} {}\n",
+        paint(BLUE, &format!("{:>width$} |", n, width = width)),
+        text
+      );
+
+      for (span, message, colour, c) in &marks {
+        if n < span.start_line || n > last_line(span) {
+          continue;
+        }
+        let from = if n == span.start_line {
+          span.start_col
+        } else {
+          1
+        };
+        let to = if n == span.end_line {
+          span.end_col
+        } else {
+          text.chars().count() + 1
+        };
+        let mut underline = format!(
+          "{}{}",
+          " ".repeat(from - 1),
+          c.to_string().repeat(to.saturating_sub(from).max(1))
+        );
+        if n == last_line(span) && !message.is_empty() {
+          underline = format!("{} {}", underline, message);
+        }
+        out += &format!("{} {}\n", gutter, paint(colour, &underline));
+      }
+    }
+
+    out + &self.render_stack(width, &paint)
+  }
+
+  // Renders a note for each call in the stack, lined up with the gutter of the code
+  // Runs of the same call, as in a function that recurses forever, are shown once with a count
+  fn render_stack(&self, width: usize, paint: &dyn Fn(&str, &str) -> String) -> String {
+    let notes = self.stack.iter().map(|f| {
+      let mut note = format!("in `{}`", f.function);
+      if let Some(p) = f.pattern {
+        note += &format!(", pattern {}", p);
+      }
+      note += &format!(", with {}", f.argument);
+      if let Some(s) = f.call_site {
+        note += &format!(", called at {}:{}", s.start_line, s.start_col);
+      }
+      note
+    });
+    let line = |note: &str| format!("{} {} {}\n", " ".repeat(width), paint(BLUE, "="), note);
+    notes
+      .dedup_with_count()
+      .map(|(count, note)| match count {
+        1 => line(&note),
+        n => line(&note) + &line(&format!("... repeated {} more times", n - 1)),
+      })
+      .collect()
+  }
+}
+
+// The last line a span covers, ignoring a final line it only reaches the start of
+fn last_line(span: &Span) -> usize {
+  if span.end_line > span.start_line && span.end_col == 1 {
+    span.end_line - 1
+  } else {
+    span.end_line
+  }
+}
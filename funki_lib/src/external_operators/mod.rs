@@ -1,5 +1,6 @@
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
 
+use crate::types::Type;
 use crate::{Argument, ReturnVal};
 
 /// The available characters for custom operators to be assigned.
@@ -23,9 +24,9 @@ pub enum OperatorChars {
   Tilda,
 }
 
-impl ToString for OperatorChars {
-  fn to_string(&self) -> String {
-    match self {
+impl Display for OperatorChars {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+    let c = match self {
       OperatorChars::At => "@",
       OperatorChars::Carat => "^",
       OperatorChars::And => "&",
@@ -34,17 +35,21 @@ impl ToString for OperatorChars {
       OperatorChars::QuestionMark => "?",
       OperatorChars::Backslash => "\\",
       OperatorChars::Tilda => "~",
-    }
-    .to_string()
+    };
+    write!(fmt, "{}", c)
   }
 }
 
+/// The result of a custom operator or builtin function.
+/// Errors are converted to strings and reported as interpretation errors.
+pub type CustomResult<C> = Result<Argument<C>, Box<dyn ToString>>;
+
 /// A custom binary operator.
 #[derive(Clone, Debug)]
 pub struct CustomBinOp<C: CustomType> {
   /// The function that gets called to evaluate this operator.
   /// Both the arguments are evaluated before the function is called.
-  pub function: fn(ReturnVal<C>, ReturnVal<C>) -> Result<Argument<C>, Box<dyn ToString>>,
+  pub function: fn(ReturnVal<C>, ReturnVal<C>) -> CustomResult<C>,
 }
 
 /// A custom unary operator.
@@ -52,7 +57,7 @@ pub struct CustomBinOp<C: CustomType> {
 pub struct CustomUnaryOp<C: CustomType> {
   /// The function that gets called to evaluate this operator.
   /// The argument is evaluated before the function is called.
-  pub function: fn(ReturnVal<C>) -> Result<Argument<C>, Box<dyn ToString>>,
+  pub function: fn(ReturnVal<C>) -> CustomResult<C>,
 }
 
 /// A custom builtin function.
@@ -61,7 +66,39 @@ pub struct CustomBuiltIn<C: CustomType> {
   /// The function that gets called to evaluate a function call with this builtin.
   /// The argument is evaluated before the function is called.
   /// If multiple arguments are provided they are wrapped in a tuple.
-  pub function: fn(ReturnVal<C>) -> Result<Argument<C>, Box<dyn ToString>>,
+  pub function: fn(ReturnVal<C>) -> CustomResult<C>,
+}
+
+/// A custom builtin function with declared parameters, added with `Language::add_typed_function`.
+/// The arguments are checked against the parameters before the function is called, so it is
+/// always called with one argument of the declared type for each parameter.
+/// Calls with the wrong arguments fail with an error naming the builtin and the parameter.
+#[derive(Clone, Debug)]
+pub struct TypedBuiltIn<C: CustomType> {
+  /// The name and type of each parameter, in order.
+  /// Type variables, `Type::Var`, accept any value.
+  pub params: Vec<(String, Type)>,
+  /// The type of the result, used when checking the types of scripts.
+  pub result: Type,
+  /// The function that gets called with the arguments, one for each parameter.
+  pub function: fn(Vec<ReturnVal<C>>) -> CustomResult<C>,
+}
+
+impl<C: CustomType> TypedBuiltIn<C> {
+  /// The type of the builtin as a function, from its parameters to its result
+  pub fn signature(&self) -> Type {
+    Type::function(
+      Type::tuple(self.params.iter().map(|(_, t)| t.clone()).collect()),
+      self.result.clone(),
+    )
+  }
+}
+
+// A builtin function added by the host, with or without declared parameters
+#[derive(Clone, Debug)]
+pub(crate) enum HostBuiltIn<C: CustomType> {
+  Untyped(CustomBuiltIn<C>),
+  Typed(TypedBuiltIn<C>),
 }
 
 // Helper function
@@ -0,0 +1,109 @@
+// A document to be laid out within a line width, following Wadler's "A prettier printer"
+// Each group is printed on one line if it fits, otherwise its lines are broken
+#[derive(Clone, Debug)]
+pub enum Doc {
+  Text(String),
+  // A space, or a new line when the enclosing group is broken
+  Line,
+  // Nothing, or a new line when the enclosing group is broken
+  SoftLine,
+  // Always a new line, the enclosing groups are always broken
+  HardLine,
+  // Indents the new lines in a document
+  Nest(usize, Box<Doc>),
+  Group(Box<Doc>),
+  Concat(Vec<Doc>),
+}
+
+impl Doc {
+  pub fn text(s: &str) -> Doc {
+    Doc::Text(s.to_string())
+  }
+
+  pub fn nest(indent: usize, doc: Doc) -> Doc {
+    Doc::Nest(indent, Box::new(doc))
+  }
+
+  pub fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+  }
+
+  // Joins documents with a separator between each
+  pub fn join(docs: Vec<Doc>, sep: Vec<Doc>) -> Doc {
+    let mut res = vec![];
+    for (i, d) in docs.into_iter().enumerate() {
+      if i > 0 {
+        res.extend(sep.iter().cloned());
+      }
+      res.push(d);
+    }
+    Doc::Concat(res)
+  }
+
+  // Lays out the document within a line width
+  pub fn render(&self, width: usize) -> String {
+    let mut out = String::new();
+    let mut col = 0;
+    // Documents still to print, with their indent and whether they are printed flat
+    let mut stack = vec![(0, false, self)];
+
+    while let Some((indent, flat, doc)) = stack.pop() {
+      match doc {
+        Doc::Text(s) => {
+          out.push_str(s);
+          col = match s.rfind('\n') {
+            Some(i) => s.len() - i - 1,
+            None => col + s.chars().count(),
+          };
+        }
+        Doc::Line if flat => {
+          out.push(' ');
+          col += 1;
+        }
+        Doc::SoftLine if flat => (),
+        Doc::Line | Doc::SoftLine | Doc::HardLine => {
+          out.truncate(out.trim_end_matches(' ').len());
+          out.push('\n');
+          out.push_str(&" ".repeat(indent));
+          col = indent;
+        }
+        Doc::Nest(i, d) => stack.push((indent + i, flat, d)),
+        Doc::Group(d) => {
+          let flat = flat || fits(width as isize - col as isize, d, &stack);
+          stack.push((indent, flat, d));
+        }
+        Doc::Concat(v) => stack.extend(v.iter().rev().map(|d| (indent, flat, d))),
+      }
+    }
+    out
+  }
+}
+
+// Whether a group fits in the remaining width when printed flat, along with what follows it up to
+//  the next line break
+fn fits(mut width: isize, doc: &Doc, rest: &[(usize, bool, &Doc)]) -> bool {
+  let mut stack = vec![(true, doc)];
+  let mut rest = rest.iter().rev();
+  while width >= 0 {
+    let (flat, doc) = match stack.pop() {
+      Some(d) => d,
+      None => match rest.next() {
+        Some((_, flat, d)) => (*flat, *d),
+        None => return true,
+      },
+    };
+    match doc {
+      Doc::Text(s) => match s.find('\n') {
+        Some(i) => return width >= s[..i].chars().count() as isize,
+        None => width -= s.chars().count() as isize,
+      },
+      Doc::Line if flat => width -= 1,
+      Doc::SoftLine if flat => (),
+      Doc::HardLine if flat => return false,
+      Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+      Doc::Nest(_, d) | Doc::Group(d) => stack.push((flat, d)),
+      Doc::Concat(v) => stack.extend(v.iter().rev().map(|d| (flat, d))),
+    }
+  }
+  false
+}
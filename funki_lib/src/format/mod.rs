@@ -0,0 +1,273 @@
+use crate::ast::{Expr, ExprInner, InterpolationPart, Opcode, Pattern, Program, UnaryOp};
+use crate::format::doc::Doc;
+use crate::parser::comments::{comments, Comment};
+
+mod doc;
+mod test;
+
+// The width lines are kept within where possible
+const WIDTH: usize = 100;
+// The indent of a function's patterns after the first, and of broken lines
+const INDENT: usize = 2;
+
+// How tightly each kind of expression binds, following the grammar from loosest to tightest
+const LOGIC: u8 = 0;
+const EQUALITY: u8 = 1;
+const ADD_SUB: u8 = 2;
+const FACTOR: u8 = 3;
+const CUSTOM_BIN: u8 = 4;
+const CUSTOM_UNARY: u8 = 5;
+const UNARY: u8 = 6;
+const CALL: u8 = 7;
+const TERM: u8 = 8;
+
+// Formats a program in the canonical style, keeping the comments in its code
+// Functions keep their order in the code, with the first pattern on the same line as the name and
+//  the rest on their own lines, indented.
+// Comments between patterns stay where they are, and comments inside a pattern are moved to the
+//  line before it.
+pub fn format_program(program: &Program, code: &str) -> String {
+  let mut functions: Vec<_> = program.env.iter().collect();
+  functions.sort_by_key(|(name, _)| program.names.get(*name));
+  let mut comments = comments(code).into_iter().peekable();
+  let mut docs = vec![];
+
+  for (name, patterns) in functions {
+    let mut function = vec![];
+    for (i, p) in patterns.iter().enumerate() {
+      let end = p.span().1;
+      let leading: Vec<Comment> =
+        std::iter::from_fn(|| comments.next_if(|c| c.start < end)).collect();
+      let mut line = vec![];
+      for c in leading {
+        line.push(Doc::Text(c.text));
+        line.push(Doc::HardLine);
+      }
+      if i == 0 {
+        docs.push(Doc::Concat(line));
+        line = vec![Doc::Text(format!("#{} ", name))];
+      }
+      line.push(pattern_doc(p, code));
+      line.push(Doc::text(";"));
+
+      // Comments on the same line as the end of the pattern stay after it
+      let trailing =
+        std::iter::from_fn(|| comments.next_if(|c| !code[end..c.start].contains(['\n', '\r'])));
+      for c in trailing {
+        line.push(Doc::Text(format!(" {}", c.text)));
+      }
+
+      match i {
+        0 => function.push(Doc::Concat(line)),
+        _ => function.push(Doc::Concat(vec![Doc::HardLine, Doc::Concat(line)])),
+      }
+    }
+    docs.push(Doc::Concat(vec![
+      Doc::nest(INDENT, Doc::Concat(function)),
+      Doc::HardLine,
+      Doc::HardLine,
+    ]));
+  }
+
+  for c in comments {
+    docs.push(Doc::Text(c.text));
+    docs.push(Doc::HardLine);
+  }
+  let mut res = Doc::Concat(docs).render(WIDTH);
+  res.truncate(res.trim_end().len());
+  res.push('\n');
+  res
+}
+
+// Lays out a pattern, with the result and each guard on their own line if it is too long
+fn pattern_doc(p: &Pattern, code: &str) -> Doc {
+  let mut docs = vec![];
+  if p.has_args() {
+    docs.push(expr_doc(&p.start, LOGIC, code));
+    docs.push(Doc::text(" ->"));
+    docs.push(Doc::nest(
+      INDENT,
+      Doc::Concat(vec![Doc::Line, expr_doc(&p.result, LOGIC, code)]),
+    ));
+  } else {
+    docs.push(expr_doc(&p.result, LOGIC, code));
+  }
+  for g in &p.guards {
+    docs.push(Doc::nest(
+      INDENT,
+      Doc::Concat(vec![
+        Doc::Line,
+        Doc::text("| "),
+        expr_doc(&g.expr, LOGIC, code),
+      ]),
+    ));
+  }
+  Doc::group(Doc::Concat(docs))
+}
+
+// How tightly an expression binds
+fn level(e: &Expr) -> u8 {
+  match &e.val {
+    ExprInner::Op(_, Opcode::And | Opcode::Or, _) => LOGIC,
+    ExprInner::Op(
+      _,
+      Opcode::Eq | Opcode::Neq | Opcode::Lt | Opcode::Leq | Opcode::Gt | Opcode::Geq,
+      _,
+    ) => EQUALITY,
+    ExprInner::Op(_, Opcode::Add | Opcode::Sub, _) => ADD_SUB,
+    ExprInner::Op(_, Opcode::Mul | Opcode::Div | Opcode::Mod, _) => FACTOR,
+    ExprInner::CustomBinOp(..) => CUSTOM_BIN,
+    ExprInner::CustomUnaryOp(..) => CUSTOM_UNARY,
+    ExprInner::Unary(..) => UNARY,
+    ExprInner::FuncCall(..) => CALL,
+    _ => TERM,
+  }
+}
+
+// Whether an expression, laid out where it needs to bind at least as tightly as `min`, ends with the
+//  `|` of a lambda
+fn ends_with_bar(e: &Expr, min: u8) -> bool {
+  if level(e) < min {
+    return false;
+  }
+  match &e.val {
+    ExprInner::Lambda(_) => true,
+    ExprInner::Op(_, _, r) => ends_with_bar(r, op_levels(level(e)).1),
+    ExprInner::CustomBinOp(_, _, r) => ends_with_bar(r, CUSTOM_UNARY),
+    ExprInner::CustomUnaryOp(_, r) => ends_with_bar(r, UNARY),
+    ExprInner::Unary(_, r) => ends_with_bar(r, CALL),
+    _ => false,
+  }
+}
+
+// Lays out an expression, in brackets if it binds more loosely than `min`
+fn expr_doc(e: &Expr, min: u8, code: &str) -> Doc {
+  let doc = match &e.val {
+    ExprInner::Number(n) => Doc::Text(n.to_string()),
+    ExprInner::Var(v) => Doc::text(v),
+    ExprInner::Str(_) => Doc::text(&code[e.start..e.end]),
+    ExprInner::InterpolationString(parts) => interpolation_doc(e, parts, code),
+    ExprInner::Op(l, o, r) => {
+      let (left, right) = op_levels(level(e));
+      bin_op_doc(
+        expr_doc(l, left, code),
+        &format!("{:?}", o),
+        expr_doc(r, right, code),
+      )
+    }
+    ExprInner::CustomBinOp(l, o, r) => bin_op_doc(
+      expr_doc(l, CUSTOM_BIN, code),
+      &o.to_string(),
+      expr_doc(r, CUSTOM_UNARY, code),
+    ),
+    ExprInner::CustomUnaryOp(o, r) => {
+      Doc::Concat(vec![Doc::Text(o.to_string()), expr_doc(r, UNARY, code)])
+    }
+    ExprInner::Unary(o, r) => {
+      let op = match o {
+        UnaryOp::Not => "!",
+        UnaryOp::Neg => "-",
+      };
+      Doc::Concat(vec![Doc::text(op), expr_doc(r, CALL, code)])
+    }
+    ExprInner::FuncCall(f, args) => {
+      let args = match &args.val {
+        ExprInner::Tuple(v) => v.iter().collect(),
+        _ => vec![&**args],
+      };
+      Doc::Concat(vec![
+        expr_doc(f, CALL, code),
+        list_doc("(", &args, ")", code),
+      ])
+    }
+    ExprInner::Tuple(v) => list_doc("(", &v.iter().collect::<Vec<_>>(), ")", code),
+    ExprInner::Lambda(p) => {
+      let args = match &p.start.val {
+        ExprInner::Tuple(v) => v.iter().map(|a| expr_doc(a, LOGIC, code)).collect(),
+        _ => vec![expr_doc(&p.start, LOGIC, code)],
+      };
+      let end = match ends_with_bar(&p.result, LOGIC) {
+        true => " |",
+        false => "|",
+      };
+      Doc::group(Doc::Concat(vec![
+        Doc::text("|"),
+        Doc::join(args, vec![Doc::text(", ")]),
+        Doc::text(" =>"),
+        Doc::nest(
+          INDENT,
+          Doc::Concat(vec![Doc::Line, expr_doc(&p.result, LOGIC, code)]),
+        ),
+        Doc::text(end),
+      ]))
+    }
+  };
+  match level(e) < min {
+    true => Doc::Concat(vec![Doc::text("("), doc, Doc::text(")")]),
+    false => doc,
+  }
+}
+
+// How tightly the left and right operands of a binary operator must bind
+// Logic operators can't be chained without brackets
+fn op_levels(level: u8) -> (u8, u8) {
+  match level {
+    LOGIC => (EQUALITY, ADD_SUB),
+    l => (l, l + 1),
+  }
+}
+
+// Lays out a binary operator, breaking the line after the operator if it is too long
+fn bin_op_doc(left: Doc, op: &str, right: Doc) -> Doc {
+  Doc::group(Doc::Concat(vec![
+    left,
+    Doc::Text(format!(" {}", op)),
+    Doc::nest(INDENT, Doc::Concat(vec![Doc::Line, right])),
+  ]))
+}
+
+// Lays out a bracketed list of expressions, with each on its own line if it is too long
+fn list_doc(open: &str, items: &[&Expr], close: &str, code: &str) -> Doc {
+  let items = items.iter().map(|i| expr_doc(i, LOGIC, code)).collect();
+  Doc::group(Doc::Concat(vec![
+    Doc::text(open),
+    Doc::nest(
+      INDENT,
+      Doc::Concat(vec![
+        Doc::SoftLine,
+        Doc::join(items, vec![Doc::text(","), Doc::Line]),
+      ]),
+    ),
+    Doc::SoftLine,
+    Doc::text(close),
+  ]))
+}
+
+// Lays out an interpolation string, keeping the text between its expressions as it is in the code
+//  so escapes and new lines are unchanged
+fn interpolation_doc(e: &Expr, parts: &[InterpolationPart], code: &str) -> Doc {
+  let exprs: Vec<&Expr> = parts
+    .iter()
+    .filter_map(|p| match p {
+      InterpolationPart::Expr(e) => Some(e),
+      InterpolationPart::String(_) => None,
+    })
+    .collect();
+  if exprs.is_empty() {
+    return Doc::text(&code[e.start..e.end]);
+  }
+
+  // The text up to and including each `{`, and from each `}`
+  // Between an expression and its braces there can only be whitespace and brackets
+  let mut docs = vec![];
+  let mut pos = e.start;
+  for ex in &exprs {
+    let text = &code[pos..ex.start];
+    let text = &text[..=text.rfind('{').unwrap()];
+    docs.push(Doc::text(text));
+    docs.push(expr_doc(ex, LOGIC, code));
+    pos = ex.end + code[ex.end..].find('}').unwrap();
+  }
+  docs.push(Doc::text(&code[pos..e.end]));
+  Doc::Concat(docs)
+}
@@ -0,0 +1,115 @@
+#[cfg(test)]
+use crate::{BlankCustom, Script};
+
+// Formats code, checking the result parses to the same program and formats to itself
+#[cfg(test)]
+fn format(code: &str) -> String {
+  let script = Script::<BlankCustom>::from_text(code).unwrap();
+  let res = script.format();
+  let again = Script::<BlankCustom>::from_text(&res).unwrap();
+  assert_eq!(format!("{:?}", script.temp), format!("{:?}", again.temp));
+  assert_eq!(again.format(), res);
+  res
+}
+
+// Tests functions and patterns are laid out consistently
+#[test]
+fn test_format_layout() {
+  assert_eq!(
+    format("#main   x ->  double( x ) ;#double x ->x*2;\n\n\n#five 5;"),
+    "#main x -> double(x);\n\n#double x -> x * 2;\n\n#five 5;\n"
+  );
+  assert_eq!(
+    format("#fib 0->1;1 -> 1;n -> fib(n - 1) + fib(n - 2);"),
+    "#fib 0 -> 1;\n  1 -> 1;\n  n -> fib(n - 1) + fib(n - 2);\n"
+  );
+  assert_eq!(
+    format("#main (b,x)->x*2|b|x>1;(b, x) -> x;"),
+    "#main (b, x) -> x * 2 | b | x > 1;\n  (b, x) -> x;\n"
+  );
+  assert_eq!(
+    format("#main xs ->map(xs,|x=>x+1|);#pair (a, (b, c)) -> (a, ());"),
+    "#main xs -> map(xs, |x => x + 1|);\n\n#pair (a, (b, c)) -> (a, ());\n"
+  );
+}
+
+// Tests brackets are only kept where they are needed
+#[test]
+fn test_format_brackets() {
+  assert_eq!(
+    format("#main (x,y) -> ((x + 1) * (y)) - (-(x)) + (x - (y - 1));"),
+    "#main (x, y) -> (x + 1) * y - -x + (x - (y - 1));\n"
+  );
+  assert_eq!(
+    format("#main x -> ((x == 1) && (x == 2)) || !(x > 3);"),
+    "#main x -> (x == 1 && (x == 2)) || !(x > 3);\n"
+  );
+  assert_eq!(
+    format("#main x -> (|y => |z => z| |)(x);"),
+    "#main x -> |y => |z => z| |(x);\n"
+  );
+}
+
+// Tests strings keep their escapes and new lines
+#[test]
+fn test_format_strings() {
+  assert_eq!(
+    format("#main x -> \"a\\n\"+f\"<p>\n  {( x + 1 )} \\{ok\\}</p>\"f;"),
+    "#main x -> \"a\\n\" + f\"<p>\n  {x + 1} \\{ok\\}</p>\"f;\n"
+  );
+  assert_eq!(format("#main f\"plain\"f;"), "#main f\"plain\"f;\n");
+}
+
+// Tests comments are kept next to the code they were written by
+#[test]
+fn test_format_comments() {
+  let code = "// The entry point
+#main x -> helper(x); // calls helper
+/* Helps */
+#helper 0 -> 0;
+  // Any other number
+  x -> x /* inner */ + 1;
+// The end";
+  assert_eq!(
+    format(code),
+    "// The entry point
+#main x -> helper(x); // calls helper
+
+/* Helps */
+#helper 0 -> 0;
+  // Any other number
+  /* inner */
+  x -> x + 1;
+
+// The end
+"
+  );
+
+  // Comment markers in strings are not comments
+  assert_eq!(
+    format("#main f\"// {\"/*\"} */\"f + \"//\";"),
+    "#main f\"// {\"/*\"} */\"f + \"//\";\n"
+  );
+}
+
+// Tests long lines are broken
+#[test]
+fn test_format_long_lines() {
+  let code = format!(
+    "#main (first, second) -> combine(first, second, {}, another_long_argument, third) | first > second;",
+    "a_very_long_argument_name_that_takes_up_space"
+  );
+  assert_eq!(
+    format(&code),
+    "#main (first, second) ->
+    combine(
+      first,
+      second,
+      a_very_long_argument_name_that_takes_up_space,
+      another_long_argument,
+      third
+    )
+    | first > second;
+"
+  );
+}
@@ -1,10 +1,28 @@
 use crate::interpreter::{interpret_function, interpret_lambda, Customs, Frame};
-use crate::{CustomType, InterpretError, InterpretVal};
+use crate::{CustomType, ErrorKind, InterpretError, InterpretVal};
+
+// The names of the builtin functions
+pub const BUILTINS: [&str; 9] = [
+  "list",
+  "get",
+  "map",
+  "filter",
+  "len",
+  "any",
+  "all",
+  "fold",
+  "assert_eq",
+];
 
 // Checks if the token refers to an inbuilt function
 // If it does, executes that function and returns Some() with the result of the function
 // Otherwise, returns none
+// Custom builtins from the host are checked first, so they can override the builtins
 pub fn built_in<C: CustomType>(name: &str, customs: &Customs<C>) -> Option<InterpretVal<C>> {
+  if customs.built_ins.contains_key(name) {
+    return Some(InterpretVal::BuiltIn(name.to_string(), eval_custom));
+  }
+
   match name {
     "list" => Some(InterpretVal::BuiltIn(name.to_string(), list_func)),
     "get" => Some(InterpretVal::BuiltIn(name.to_string(), get_func)),
@@ -14,13 +32,8 @@ pub fn built_in<C: CustomType>(name: &str, customs: &Customs<C>) -> Option<Inter
     "any" => Some(InterpretVal::BuiltIn(name.to_string(), any_func)),
     "all" => Some(InterpretVal::BuiltIn(name.to_string(), all_func)),
     "fold" => Some(InterpretVal::BuiltIn(name.to_string(), fold_func)),
-    n => {
-      if customs.built_ins.contains_key(n) {
-        Some(InterpretVal::BuiltIn(n.to_string(), eval_custom))
-      } else {
-        None
-      }
-    }
+    "assert_eq" => Some(InterpretVal::BuiltIn(name.to_string(), assert_eq_func)),
+    _ => None,
   }
 }
 
@@ -32,7 +45,7 @@ fn eval_custom<C: CustomType>(
   n: String,
 ) -> Result<InterpretVal<C>, InterpretError> {
   let a = arg.unwrap_tuple();
-  customs.built_ins.get(&n).unwrap().call_func(&a)
+  customs.built_ins.get(&n).unwrap().call_func(&n, &a)
 }
 
 // Executes the builtin list function, which converts a tuple into a list.
@@ -61,7 +74,8 @@ fn length_func<C: CustomType>(
   if let InterpretVal::List(t) = arg.unwrap_tuple() {
     Ok(InterpretVal::Int(t.len() as i32))
   } else {
-    Err(InterpretError::new(
+    Err(InterpretError::new_kind(
+      ErrorKind::TypeMismatch,
       "Wrong argument type for `len` function.",
     ))
   }
@@ -85,17 +99,20 @@ fn get_func<C: CustomType>(
           Err(InterpretError::new("Index out of range."))
         }
       } else {
-        Err(InterpretError::new(
+        Err(InterpretError::new_kind(
+          ErrorKind::TypeMismatch,
           format!("Wrong arguments for `get` ({:?}, {:?})", v[0], v[1]).as_str(),
         ))
       }
     } else {
-      Err(InterpretError::new(
+      Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         "Wrong number of arguments provided for `get`.",
       ))
     }
   } else {
-    Err(InterpretError::new(
+    Err(InterpretError::new_kind(
+      ErrorKind::TypeMismatch,
       "Wrong number of arguments provided for `get`.",
     ))
   }
@@ -110,10 +127,10 @@ fn map_func<C: CustomType>(
 ) -> Result<InterpretVal<C>, InterpretError> {
   if let InterpretVal::Tuple(t) = arg {
     if t.len() == 2 {
-      match (t.get(0).unwrap(), t.get(1).unwrap()) {
-        (InterpretVal::List(v), InterpretVal::Function(f)) => Ok(InterpretVal::List(
+      match (t.first().unwrap(), t.get(1).unwrap()) {
+        (InterpretVal::List(v), InterpretVal::Function(n, f)) => Ok(InterpretVal::List(
           v.iter()
-            .map(|i| interpret_function(f, frame, i.clone(), customs))
+            .map(|i| interpret_function(n, f, frame, i.clone(), customs))
             .collect::<Result<Vec<InterpretVal<C>>, InterpretError>>()?,
         )),
         (InterpretVal::List(v), InterpretVal::Lambda(f, e)) => {
@@ -125,22 +142,25 @@ fn map_func<C: CustomType>(
               .collect::<Result<Vec<InterpretVal<C>>, InterpretError>>()?,
           ))
         }
-        _ => Err(InterpretError::new(
+        _ => Err(InterpretError::new_kind(
+          ErrorKind::TypeMismatch,
           format!(
             "Wrong argument types provided to map: {:?}, {:?}",
-            t.get(0),
+            t.first(),
             t.get(1)
           )
           .as_str(),
         )),
       }
     } else {
-      Err(InterpretError::new(
+      Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         "Wrong number of arguments provided to map.",
       ))
     }
   } else {
-    Err(InterpretError::new(
+    Err(InterpretError::new_kind(
+      ErrorKind::TypeMismatch,
       "Wrong number of arguments provided to map.",
     ))
   }
@@ -155,14 +175,17 @@ fn filter_func<C: CustomType>(
 ) -> Result<InterpretVal<C>, InterpretError> {
   if let InterpretVal::Tuple(t) = arg {
     if t.len() == 2 {
-      match (t.get(0).unwrap(), t.get(1).unwrap()) {
-        (InterpretVal::List(v), InterpretVal::Function(f)) => Ok(InterpretVal::List(
+      match (t.first().unwrap(), t.get(1).unwrap()) {
+        (InterpretVal::List(v), InterpretVal::Function(n, f)) => Ok(InterpretVal::List(
           v.iter()
             .map(|v| {
-              if let InterpretVal::Bool(b) = interpret_function(f, frame, v.clone(), customs)? {
+              if let InterpretVal::Bool(b) = interpret_function(n, f, frame, v.clone(), customs)? {
                 Ok((v.clone(), b))
               } else {
-                Err(InterpretError::new("Filter function was not a bool."))
+                Err(InterpretError::new_kind(
+                  ErrorKind::TypeMismatch,
+                  "Filter function was not a bool.",
+                ))
               }
             })
             .collect::<Result<Vec<(InterpretVal<C>, bool)>, InterpretError>>()?
@@ -183,7 +206,10 @@ fn filter_func<C: CustomType>(
                 {
                   Ok((v.clone(), b))
                 } else {
-                  Err(InterpretError::new("Filter function was not a bool."))
+                  Err(InterpretError::new_kind(
+                    ErrorKind::TypeMismatch,
+                    "Filter function was not a bool.",
+                  ))
                 }
               })
               .collect::<Result<Vec<(InterpretVal<C>, bool)>, InterpretError>>()?
@@ -193,22 +219,25 @@ fn filter_func<C: CustomType>(
               .collect(),
           ))
         }
-        _ => Err(InterpretError::new(
+        _ => Err(InterpretError::new_kind(
+          ErrorKind::TypeMismatch,
           format!(
             "Wrong argument types provided to filter: {:?}, {:?}",
-            t.get(0),
+            t.first(),
             t.get(1)
           )
           .as_str(),
         )),
       }
     } else {
-      Err(InterpretError::new(
+      Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         "Wrong number of arguments provided to filter.",
       ))
     }
   } else {
-    Err(InterpretError::new(
+    Err(InterpretError::new_kind(
+      ErrorKind::TypeMismatch,
       "Wrong number of arguments provided to filter.",
     ))
   }
@@ -223,14 +252,17 @@ fn any_func<C: CustomType>(
 ) -> Result<InterpretVal<C>, InterpretError> {
   if let InterpretVal::Tuple(t) = arg {
     if t.len() == 2 {
-      match (t.get(0).unwrap(), t.get(1).unwrap()) {
-        (InterpretVal::List(v), InterpretVal::Function(f)) => Ok(InterpretVal::Bool(
+      match (t.first().unwrap(), t.get(1).unwrap()) {
+        (InterpretVal::List(v), InterpretVal::Function(n, f)) => Ok(InterpretVal::Bool(
           v.iter()
             .map(|v| {
-              if let InterpretVal::Bool(b) = interpret_function(f, frame, v.clone(), customs)? {
+              if let InterpretVal::Bool(b) = interpret_function(n, f, frame, v.clone(), customs)? {
                 Ok(b)
               } else {
-                Err(InterpretError::new("Any function result was not a bool."))
+                Err(InterpretError::new_kind(
+                  ErrorKind::TypeMismatch,
+                  "Any function result was not a bool.",
+                ))
               }
             })
             .collect::<Result<Vec<bool>, InterpretError>>()?
@@ -248,7 +280,10 @@ fn any_func<C: CustomType>(
                 {
                   Ok(b)
                 } else {
-                  Err(InterpretError::new("Any function result was not a bool."))
+                  Err(InterpretError::new_kind(
+                    ErrorKind::TypeMismatch,
+                    "Any function result was not a bool.",
+                  ))
                 }
               })
               .collect::<Result<Vec<bool>, InterpretError>>()?
@@ -256,22 +291,25 @@ fn any_func<C: CustomType>(
               .any(|v| *v),
           ))
         }
-        _ => Err(InterpretError::new(
+        _ => Err(InterpretError::new_kind(
+          ErrorKind::TypeMismatch,
           format!(
             "Wrong argument types provided to any: {:?}, {:?}",
-            t.get(0),
+            t.first(),
             t.get(1)
           )
           .as_str(),
         )),
       }
     } else {
-      Err(InterpretError::new(
+      Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         "Wrong number of arguments provided to any.",
       ))
     }
   } else {
-    Err(InterpretError::new(
+    Err(InterpretError::new_kind(
+      ErrorKind::TypeMismatch,
       "Wrong number of arguments provided to any.",
     ))
   }
@@ -286,14 +324,17 @@ fn all_func<C: CustomType>(
 ) -> Result<InterpretVal<C>, InterpretError> {
   if let InterpretVal::Tuple(t) = arg {
     if t.len() == 2 {
-      match (t.get(0).unwrap(), t.get(1).unwrap()) {
-        (InterpretVal::List(v), InterpretVal::Function(f)) => Ok(InterpretVal::Bool(
+      match (t.first().unwrap(), t.get(1).unwrap()) {
+        (InterpretVal::List(v), InterpretVal::Function(n, f)) => Ok(InterpretVal::Bool(
           v.iter()
             .map(|v| {
-              if let InterpretVal::Bool(b) = interpret_function(f, frame, v.clone(), customs)? {
+              if let InterpretVal::Bool(b) = interpret_function(n, f, frame, v.clone(), customs)? {
                 Ok(b)
               } else {
-                Err(InterpretError::new("Any function result was not a bool."))
+                Err(InterpretError::new_kind(
+                  ErrorKind::TypeMismatch,
+                  "Any function result was not a bool.",
+                ))
               }
             })
             .collect::<Result<Vec<bool>, InterpretError>>()?
@@ -312,7 +353,10 @@ fn all_func<C: CustomType>(
                 {
                   Ok(b)
                 } else {
-                  Err(InterpretError::new("Any function result was not a bool."))
+                  Err(InterpretError::new_kind(
+                    ErrorKind::TypeMismatch,
+                    "Any function result was not a bool.",
+                  ))
                 }
               })
               .collect::<Result<Vec<bool>, InterpretError>>()?
@@ -320,22 +364,25 @@ fn all_func<C: CustomType>(
               .all(|v| *v),
           ))
         }
-        _ => Err(InterpretError::new(
+        _ => Err(InterpretError::new_kind(
+          ErrorKind::TypeMismatch,
           format!(
             "Wrong argument types provided to any: {:?}, {:?}",
-            t.get(0),
+            t.first(),
             t.get(1)
           )
           .as_str(),
         )),
       }
     } else {
-      Err(InterpretError::new(
+      Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         "Wrong number of arguments provided to any.",
       ))
     }
   } else {
-    Err(InterpretError::new(
+    Err(InterpretError::new_kind(
+      ErrorKind::TypeMismatch,
       "Wrong number of arguments provided to any.",
     ))
   }
@@ -350,41 +397,124 @@ fn fold_func<C: CustomType>(
 ) -> Result<InterpretVal<C>, InterpretError> {
   if let InterpretVal::Tuple(t) = arg {
     if t.len() == 3 {
-      match (t.get(0).unwrap(), t.get(1).unwrap(), t.get(2).unwrap()) {
-        (InterpretVal::List(v), s, InterpretVal::Function(f)) => {
-          v.iter().fold(Ok(s.clone()), |acc, x| {
+      match (t.first().unwrap(), t.get(1).unwrap(), t.get(2).unwrap()) {
+        (InterpretVal::List(v), s, InterpretVal::Function(n, f)) => {
+          v.iter().try_fold(s.clone(), |acc, x| {
             interpret_function(
+              n,
               f,
               frame,
-              InterpretVal::Tuple(vec![acc?, x.clone()]),
+              InterpretVal::Tuple(vec![acc, x.clone()]),
               customs,
             )
           })
         }
         (InterpretVal::List(v), s, InterpretVal::Lambda(f, e)) => {
-          v.iter().fold(Ok(s.clone()), |acc, x| {
+          v.iter().try_fold(s.clone(), |acc, x| {
             let mut l = e.clone();
             l.set_next(frame);
             interpret_lambda(
               f.clone(),
               &mut l,
-              InterpretVal::Tuple(vec![acc?, x.clone()]),
+              InterpretVal::Tuple(vec![acc, x.clone()]),
               customs,
             )
           })
         }
-        _ => Err(InterpretError::new(
+        _ => Err(InterpretError::new_kind(
+          ErrorKind::TypeMismatch,
           "Wrong arguments types provided to fold.",
         )),
       }
     } else {
-      Err(InterpretError::new(
+      Err(InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
         "Wrong number of arguments provided to fold.",
       ))
     }
   } else {
-    Err(InterpretError::new(
+    Err(InterpretError::new_kind(
+      ErrorKind::TypeMismatch,
       "Wrong number of arguments provided to fold.",
     ))
   }
 }
+
+// Executes the builtin assert_eq function, which returns true if its two arguments are equal
+// Otherwise fails with where the first argument, the actual value, differs from the expected one
+fn assert_eq_func<C: CustomType>(
+  arg: InterpretVal<C>,
+  _: &mut Frame<C>,
+  _: &Customs<C>,
+  _: String,
+) -> Result<InterpretVal<C>, InterpretError> {
+  match arg {
+    InterpretVal::Tuple(t) if t.len() == 2 => match difference(&t[0], &t[1], String::new())? {
+      None => Ok(InterpretVal::Bool(true)),
+      Some((path, actual, expected)) => {
+        let at = match path.is_empty() {
+          true => String::new(),
+          false => format!(" at `{}`", path),
+        };
+        let mut e = InterpretError::new_kind(
+          ErrorKind::AssertionFailed,
+          &format!(
+            "Assertion failed{}, expected {} but found {}.",
+            at,
+            show(&expected),
+            show(&actual)
+          ),
+        );
+        e.arg = Some(0);
+        Err(e)
+      }
+    },
+    _ => Err(InterpretError::new_kind(
+      ErrorKind::TypeMismatch,
+      "Wrong number of arguments provided to assert_eq.",
+    )),
+  }
+}
+
+// Where two values differ, with the actual and expected values there
+type Difference<C> = (String, InterpretVal<C>, InterpretVal<C>);
+
+// Finds the first place two values differ, with the path to it, such as `[1].0` for the first
+//  item of a tuple that is the second item of a list
+// Lists and tuples of the same length are compared item by item, anything else as a whole
+fn difference<C: CustomType>(
+  actual: &InterpretVal<C>,
+  expected: &InterpretVal<C>,
+  path: String,
+) -> Result<Option<Difference<C>>, InterpretError> {
+  match (actual, expected) {
+    (InterpretVal::List(l), InterpretVal::List(r)) if l.len() == r.len() => {
+      for (i, (l, r)) in l.iter().zip(r).enumerate() {
+        if let Some(d) = difference(l, r, format!("{}[{}]", path, i))? {
+          return Ok(Some(d));
+        }
+      }
+      Ok(None)
+    }
+    (InterpretVal::Tuple(l), InterpretVal::Tuple(r)) if l.len() == r.len() => {
+      for (i, (l, r)) in l.iter().zip(r).enumerate() {
+        if let Some(d) = difference(l, r, format!("{}.{}", path, i))? {
+          return Ok(Some(d));
+        }
+      }
+      Ok(None)
+    }
+    (l, r) => match l.eq_op(r)? {
+      true => Ok(None),
+      false => Ok(Some((path, l.clone(), r.clone()))),
+    },
+  }
+}
+
+// Shows a value as it would be written in a script
+fn show<C: CustomType>(val: &InterpretVal<C>) -> String {
+  let mut s = String::new();
+  // Writing to a string can't fail
+  let _ = val.write_syntax(&mut s);
+  s
+}
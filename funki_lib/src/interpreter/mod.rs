@@ -1,23 +1,32 @@
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::ops::Add;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use itertools::Itertools;
 
+use crate::analysis::did_you_mean;
 use crate::ast::*;
 use crate::data_types::*;
-use crate::external_operators::CustomBuiltIn;
+use crate::external_operators::HostBuiltIn;
 use crate::interpreter::builtins::built_in;
-use crate::{CustomBinOp, CustomType, CustomUnaryOp, OperatorChars, ReturnVal};
+pub use crate::interpreter::builtins::BUILTINS;
+use crate::limits::{LimitTracker, Limits};
+use crate::types::Type;
+use crate::{CustomBinOp, CustomType, CustomUnaryOp, ErrorKind, OperatorChars};
 
 mod builtins;
 mod test;
 
 // Stores the current custom operators in the language
+// Also tracks the resources used by the current call against its limits
 pub struct Customs<C: CustomType> {
   bin_ops: HashMap<OperatorChars, CustomBinOp<C>>,
   unary_ops: HashMap<OperatorChars, CustomUnaryOp<C>>,
-  built_ins: HashMap<String, CustomBuiltIn<C>>,
+  built_ins: HashMap<String, HostBuiltIn<C>>,
+  limits: LimitTracker,
+  cancel: Option<Arc<AtomicBool>>,
 }
 
 impl<C: CustomType> Customs<C> {
@@ -27,45 +36,125 @@ impl<C: CustomType> Customs<C> {
       bin_ops: Default::default(),
       unary_ops: Default::default(),
       built_ins: Default::default(),
+      limits: LimitTracker::new(Default::default()),
+      cancel: None,
     }
   }
 
   pub fn new_from_hash(
     bin: HashMap<OperatorChars, CustomBinOp<C>>,
     unary: HashMap<OperatorChars, CustomUnaryOp<C>>,
-    builtins: HashMap<String, CustomBuiltIn<C>>,
+    builtins: HashMap<String, HostBuiltIn<C>>,
+    limits: Limits,
   ) -> Self {
     Self {
       bin_ops: bin,
       unary_ops: unary,
       built_ins: builtins,
+      limits: LimitTracker::new(limits),
+      cancel: None,
     }
   }
+
+  // Sets a token that cancels the call when it is set to true
+  pub fn set_cancel(&mut self, token: Arc<AtomicBool>) {
+    self.cancel = Some(token);
+  }
+
+  // Checks if the call has been cancelled
+  // Polled whenever a function, lambda or builtin is called
+  fn check_cancelled(&self) -> Result<(), InterpretError> {
+    match &self.cancel {
+      Some(c) if c.load(Ordering::Relaxed) => Err(InterpretError::new_cancelled()),
+      _ => Ok(()),
+    }
+  }
+
+  // Copies these customs for a new call, so the limits are tracked from zero
+  // The cancellation token is kept, so cancelling a call also stops the functions it returned
+  pub fn fresh(&self) -> Self {
+    let mut customs = Self::new_from_hash(
+      self.bin_ops.clone(),
+      self.unary_ops.clone(),
+      self.built_ins.clone(),
+      self.limits.limits().clone(),
+    );
+    customs.cancel = self.cancel.clone();
+    customs
+  }
 }
 
 // Interprets a specific top-level function in a template
+#[cfg(test)]
 pub fn interpret<C: CustomType>(
   temp: &Program,
   name: &str,
   arg: InterpretVal<C>,
   customs: &Customs<C>,
-) -> Result<ReturnVal<C>, InterpretError> {
-  let res = {
-    let frame = Frame::<C>::from_template(temp);
-    if let Ok(func) = frame.find(name) {
-      if let InterpretVal::Function(p) = func {
-        interpret_function(&p, &mut Frame::from_template(temp), arg, customs)
-      } else {
-        panic!("Should be impossible to have top level expr with non function expr");
-      }
+) -> Result<crate::ReturnVal<C>, InterpretError> {
+  evaluate(temp, name, arg, HashMap::new(), customs)?
+    .0
+    .to_return_val()
+}
+
+// Evaluates a specific top-level function in a template, with a set of global values visible
+// Returns the resulting value along with the root frame it was evaluated in, so any functions
+//  in the result can be called later
+pub fn evaluate<C: CustomType>(
+  temp: &Program,
+  name: &str,
+  arg: InterpretVal<C>,
+  globals: HashMap<String, InterpretVal<C>>,
+  customs: &Customs<C>,
+) -> Result<(InterpretVal<C>, Frame<C>), InterpretError> {
+  let mut frame = Frame::<C>::from_template_with_globals(temp, globals);
+  let res = if let Ok(func) = frame.find(name) {
+    if let InterpretVal::Function(n, p) = func {
+      interpret_function(&n, &p, &mut frame, arg, customs)
     } else {
-      return Err(InterpretError::new(
-        format!("Could not find {}", name).as_str(),
-      ));
+      panic!("Should be impossible to have top level expr with non function expr");
     }
+  } else {
+    return Err(InterpretError::new_kind(
+      ErrorKind::UnknownFunction,
+      &format!(
+        "Could not find {}.{}",
+        name,
+        did_you_mean(name, temp.env.keys().map(|k| k.as_str()))
+      ),
+    ));
   }?;
 
-  res.to_return_val()
+  Ok((res, frame))
+}
+
+// Calls a function value (a function, lambda or builtin) with an argument
+pub fn call_value<C: CustomType>(
+  val: InterpretVal<C>,
+  arg: InterpretVal<C>,
+  env: &mut Frame<C>,
+  customs: &Customs<C>,
+) -> Result<InterpretVal<C>, InterpretError> {
+  match val {
+    InterpretVal::Function(n, p) => interpret_function(&n, &p, env, arg, customs),
+    InterpretVal::Lambda(p, mut e) => interpret_lambda(p, &mut e, arg, customs),
+    InterpretVal::BuiltIn(n, f) => {
+      customs.check_cancelled()?;
+      let res = f(arg.clone(), env, customs, n.clone())
+        .map_err(|e| e.push_frame(&n, None, arg.preview()))?;
+      // Builtins are the only functions that can build lists or strings outside of expressions
+      match &res {
+        InterpretVal::List(l) => customs.limits.alloc(l.len())?,
+        InterpretVal::String(s) => customs.limits.alloc(s.len())?,
+        _ => (),
+      }
+      Ok(res)
+    }
+    _ => Err(InterpretError::new_kind(
+      ErrorKind::TypeMismatch,
+      "Called value that is not a function.",
+    )),
+  }
 }
 
 // Recursive function for evaluating an expression
@@ -75,34 +164,49 @@ fn interpret_recurse<C: CustomType>(
   customs: &Customs<C>,
 ) -> Result<InterpretVal<C>, InterpretError> {
   use crate::ast::ExprInner::*;
+  if let Err(mut e) = customs.limits.step() {
+    e.add_loc(expr.start, expr.end);
+    return Err(e);
+  }
+
   match &expr.val {
-    Str(s) => Ok(InterpretVal::String(s.to_string())),
+    Str(s) => {
+      customs.limits.alloc(s.len())?;
+      Ok(InterpretVal::String(s.to_string()))
+    }
     Number(n) => Ok(InterpretVal::Int(*n)),
     Unary(o, e) => match o {
       UnaryOp::Not => {
-        let res = interpret_recurse(&*e, env, customs)?;
+        let res = interpret_recurse(e, env, customs)?;
         if let InterpretVal::Bool(e) = res {
           Ok(InterpretVal::Bool(!e))
         } else if let InterpretVal::Custom(c) = res {
           c.pre_not()
             .map(|v| InterpretVal::from_arg(&v))
-            .map_err(|e| InterpretError::new(&e.to_string()))
+            .map_err(|e| InterpretError::new_kind(ErrorKind::HostError, &e.to_string()))
         } else {
-          Err(InterpretError::new(
+          Err(InterpretError::new_kind(
+            ErrorKind::TypeMismatch,
             "Tried to apply '!' to a non boolean value.",
           ))
         }
       }
       UnaryOp::Neg => {
-        let res = interpret_recurse(&*e, env, customs)?;
+        let res = interpret_recurse(e, env, customs)?;
         if let InterpretVal::Int(i) = res {
-          Ok(InterpretVal::Int(-i))
+          i.checked_neg().map(InterpretVal::Int).ok_or_else(|| {
+            InterpretError::new_kind(
+              ErrorKind::RuntimeError,
+              &format!("Integer overflow, -({}).", i),
+            )
+          })
         } else if let InterpretVal::Custom(c) = res {
           c.pre_neg()
             .map(|v| InterpretVal::from_arg(&v))
-            .map_err(|e| InterpretError::new(&e.to_string()))
+            .map_err(|e| InterpretError::new_kind(ErrorKind::HostError, &e.to_string()))
         } else {
-          Err(InterpretError::new(
+          Err(InterpretError::new_kind(
+            ErrorKind::TypeMismatch,
             "Tried to apply '-' to a non int value.",
           ))
         }
@@ -112,12 +216,16 @@ fn interpret_recurse<C: CustomType>(
       let arg = interpret_recurse(a, env, customs)?;
       let val = interpret_recurse(f, env, customs)?;
 
-      match val {
-        InterpretVal::Function(p) => interpret_function(&p, env, arg, customs),
-        InterpretVal::Lambda(p, mut e) => interpret_lambda(p, &mut e, arg, customs),
-        InterpretVal::BuiltIn(n, f) => f(arg, env, customs, n),
-        _ => Err(InterpretError::new("Called value that is not a function.")),
-      }
+      call_value(val, arg, env, customs).map_err(|mut e| {
+        e.add_call_site(expr.start, expr.end);
+        // Errors about one argument of a builtin are located at that argument
+        if let (Some(i), Tuple(args)) = (e.arg.take(), &a.val) {
+          if let Some(a) = args.get(i) {
+            e.add_loc(a.start, a.end);
+          }
+        }
+        e
+      })
     }
     Var(s) => {
       if let Ok(e) = env.find(s) {
@@ -125,18 +233,29 @@ fn interpret_recurse<C: CustomType>(
       } else if let Some(e) = built_in(s, customs) {
         Ok(e)
       } else {
-        Err(InterpretError::new("Cannot resolve variable {s}."))
+        let names = env
+          .names()
+          .into_iter()
+          .chain(BUILTINS)
+          .chain(customs.built_ins.keys().map(|k| k.as_str()));
+        Err(InterpretError::new_kind(
+          ErrorKind::UnknownVariable,
+          &format!("Cannot resolve variable {}.{}", s, did_you_mean(s, names)),
+        ))
       }
     }
-    InterpolationString(vs) => Ok(InterpretVal::String(
-      vs.iter()
+    InterpolationString(vs) => {
+      let s = vs
+        .iter()
         .map(|p| match p {
           InterpolationPart::String(s) => Ok(s.to_string()),
           InterpolationPart::Expr(e) => Ok(interpret_recurse(e, env, customs)?.to_string()),
         })
-        .fold_ok(String::new(), |s, p| s.add(p.as_str()))?,
-    )),
-    Op(l, o, r) => eval_op(&*l, o, &*r, env, customs),
+        .fold_ok(String::new(), |s, p| s.add(p.as_str()))?;
+      customs.limits.alloc(s.len())?;
+      Ok(InterpretVal::String(s))
+    }
+    Op(l, o, r) => eval_op(l, o, r, env, customs),
     Tuple(v) => Ok(InterpretVal::Tuple(
       v.iter()
         .map(|e| interpret_recurse(e, env, customs))
@@ -179,9 +298,23 @@ fn eval_op<C: CustomType>(
   let right = interpret_recurse(r, env, customs)?;
 
   match op {
-    Opcode::Add => left.add_op(&right),
+    Opcode::Add => {
+      let res = left.add_op(&right)?;
+      if let InterpretVal::String(s) = &res {
+        customs.limits.alloc(s.len())?;
+      }
+      Ok(res)
+    }
     Opcode::Sub => left.sub_op(&right),
-    Opcode::Mul => left.mult_op(&right),
+    Opcode::Mul => {
+      // Checked before multiplying so a huge string is never built
+      if let (InterpretVal::String(s), InterpretVal::Int(i)) = (&left, &right) {
+        customs
+          .limits
+          .alloc(s.len().saturating_mul((*i).max(0) as usize))?;
+      }
+      left.mult_op(&right)
+    }
     Opcode::Div => left.div_op(&right),
     Opcode::Mod => left.modulo_op(&right),
     Opcode::Eq => Ok(InterpretVal::Bool(left.eq_op(&right)?)),
@@ -244,6 +377,8 @@ fn pattern_match<C: CustomType>(
             {
               stack.push((p, a).clone())
             }
+          } else {
+            return Ok(None);
           }
         } else {
           return Ok(None);
@@ -265,28 +400,58 @@ fn pattern_match<C: CustomType>(
 
 // Interprets a function
 fn interpret_function<C: CustomType>(
+  name: &str,
   func: &[Pattern],
   env: &mut Frame<C>,
   arg: InterpretVal<C>,
   customs: &Customs<C>,
 ) -> Result<InterpretVal<C>, InterpretError> {
-  for p in func {
-    if let Some(mut r) = pattern_match(p.start.clone(), arg.clone(), env, customs)? {
-      if p
-        .guards
-        .iter()
-        .map(|p| {
-          let res = interpret_recurse(p.expr.borrow(), &mut r, customs)?;
-          res.eq_op(&InterpretVal::Bool(true))
-        })
-        .fold_ok(true, |l, r| l && r)?
-      {
-        return interpret_recurse(&p.result, &mut r, customs);
-      }
+  customs.check_cancelled()?;
+  let _depth = customs.limits.enter()?;
+  for (i, p) in func.iter().enumerate() {
+    let res = interpret_pattern(p, env, arg.clone(), customs)
+      .map_err(|e| e.push_frame(name, Some(i), arg.preview()))?;
+    if let Some(res) = res {
+      return Ok(res);
     }
   }
 
-  Err(InterpretError::new("Cannot find applicable pattern."))
+  Err(
+    func
+      .iter()
+      .filter(|p| p.start.start != p.start.end)
+      .fold(
+        InterpretError::new_kind(
+          ErrorKind::NoMatchingPattern,
+          "Cannot find applicable pattern.",
+        ),
+        |e, p| e.with_label(p.start.start, p.start.end, "this pattern did not match"),
+      )
+      .push_frame(name, None, arg.preview()),
+  )
+}
+
+// Interprets a pattern of a function, if the argument matches it and its guards pass
+fn interpret_pattern<C: CustomType>(
+  p: &Pattern,
+  env: &mut Frame<C>,
+  arg: InterpretVal<C>,
+  customs: &Customs<C>,
+) -> Result<Option<InterpretVal<C>>, InterpretError> {
+  if let Some(mut r) = pattern_match(p.start.clone(), arg, env, customs)? {
+    if p
+      .guards
+      .iter()
+      .map(|p| {
+        let res = interpret_recurse(p.expr.borrow(), &mut r, customs)?;
+        res.eq_op(&InterpretVal::Bool(true))
+      })
+      .fold_ok(true, |l, r| l && r)?
+    {
+      return interpret_recurse(&p.result, &mut r, customs).map(Some);
+    }
+  }
+  Ok(None)
 }
 
 // Interprets a lambda function
@@ -296,13 +461,24 @@ fn interpret_lambda<C: CustomType>(
   arg: InterpretVal<C>,
   customs: &Customs<C>,
 ) -> Result<InterpretVal<C>, InterpretError> {
-  if let Some(mut r) = pattern_match(func.start, arg, env, customs)? {
+  customs.check_cancelled()?;
+  let _depth = customs.limits.enter()?;
+  let (start, end) = (func.start.start, func.start.end);
+  if let Some(mut r) = pattern_match(func.start, arg.clone(), env, customs)
+    .map_err(|e| e.push_frame("<lambda>", Some(0), arg.preview()))?
+  {
     r.set_next(env);
     interpret_recurse(&func.result, &mut r, customs)
+      .map_err(|e| e.push_frame("<lambda>", Some(0), arg.preview()))
   } else {
-    Err(InterpretError::new(
-      "Lambda function did not match pattern.",
-    ))
+    Err(
+      InterpretError::new_kind(
+        ErrorKind::NoMatchingPattern,
+        "Lambda function did not match pattern.",
+      )
+      .with_label(start, end, "this pattern did not match")
+      .push_frame("<lambda>", None, arg.preview()),
+    )
   }
 }
 
@@ -316,7 +492,7 @@ impl<C: CustomType> CustomBinOp<C> {
     let arg2 = val2.clone().unwrap_tuple().to_return_val()?;
 
     (self.function)(arg1, arg2)
-      .map_err(|e| InterpretError::new(&e.to_string()))
+      .map_err(|e| InterpretError::new_kind(ErrorKind::HostError, &e.to_string()))
       .map(|v| InterpretVal::from_arg(&v))
   }
 }
@@ -326,17 +502,77 @@ impl<C: CustomType> CustomUnaryOp<C> {
     let arg1 = val1.clone().unwrap_tuple().to_return_val()?;
 
     (self.function)(arg1)
-      .map_err(|e| InterpretError::new(&e.to_string()))
+      .map_err(|e| InterpretError::new_kind(ErrorKind::HostError, &e.to_string()))
       .map(|v| InterpretVal::from_arg(&v))
   }
 }
 
-impl<C: CustomType> CustomBuiltIn<C> {
-  fn call_func(&self, val1: &InterpretVal<C>) -> Result<InterpretVal<C>, InterpretError> {
-    let arg1 = val1.clone().unwrap_tuple().to_return_val()?;
+impl<C: CustomType> HostBuiltIn<C> {
+  fn call_func(
+    &self,
+    name: &str,
+    val1: &InterpretVal<C>,
+  ) -> Result<InterpretVal<C>, InterpretError> {
+    let res = match self {
+      HostBuiltIn::Untyped(f) => (f.function)(val1.clone().unwrap_tuple().to_return_val()?),
+      HostBuiltIn::Typed(f) => {
+        let args = check_args(name, &f.params, val1.clone().unwrap_tuple())?
+          .iter()
+          .map(|a| a.to_return_val())
+          .collect::<Result<Vec<_>, _>>()?;
+        (f.function)(args)
+      }
+    };
 
-    (self.function)(arg1)
-      .map_err(|e| InterpretError::new(&e.to_string()))
+    res
+      .map_err(|e| InterpretError::new_kind(ErrorKind::HostError, &e.to_string()))
       .map(|v| InterpretVal::from_arg(&v))
   }
 }
+
+// Checks the arguments of a call to a typed builtin match its parameters, splitting them into one
+//  argument for each parameter
+fn check_args<C: CustomType>(
+  name: &str,
+  params: &[(String, Type)],
+  arg: InterpretVal<C>,
+) -> Result<Vec<InterpretVal<C>>, InterpretError> {
+  let args = match (arg, params) {
+    // A single parameter of tuple type takes the whole tuple
+    (a, [(_, Type::Tuple(_) | Type::Var(_))]) => vec![a],
+    (InterpretVal::Tuple(v), _) => v,
+    (a, _) => vec![a],
+  };
+
+  if args.len() != params.len() {
+    return Err(InterpretError::new_kind(
+      ErrorKind::TypeMismatch,
+      &format!(
+        "`{}` takes {} argument{} ({}), but was given {}.",
+        name,
+        params.len(),
+        if params.len() == 1 { "" } else { "s" },
+        params.iter().map(|(n, _)| n.as_str()).join(", "),
+        args.len()
+      ),
+    ));
+  }
+
+  for (i, (a, (p, t))) in args.iter().zip(params).enumerate() {
+    if !a.has_type(t) {
+      let mut e = InterpretError::new_kind(
+        ErrorKind::TypeMismatch,
+        &format!(
+          "`{}` expects `{}` to be {}, but was given {}.",
+          name,
+          p,
+          t,
+          a.preview()
+        ),
+      );
+      e.arg = Some(i);
+      return Err(e);
+    }
+  }
+  Ok(args)
+}
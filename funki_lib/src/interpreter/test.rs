@@ -356,10 +356,7 @@ fn test_builtin() {
   assert_eq!(
     format!(
       "{:?}",
-      crate::LanguageErr::new_from_int_err(
-        res.err().unwrap(),
-        "#main\nget(list(1, 4), 2);".to_string(),
-      )
+      crate::LanguageErr::new_from_int_err(res.err().unwrap(), "#main\nget(list(1, 4), 2);",)
     ),
     "Error: \"Index out of range.\"\nAt lines: 2:1 - 2:19\nCode: `get(list(1, 4), 2)`"
   );
@@ -622,10 +619,7 @@ fn test_custom_builtin_binary_operators() {
 
   let temp = ProgramParser::new()
     .parse(
-      &ParserState {
-        unary_ops: vec![],
-        binary_ops: vec![OperatorChars::Carat],
-      },
+      &ParserState::with_ops(vec![], vec![OperatorChars::Carat]),
       "#main 2 ^ 3;",
     )
     .unwrap();
@@ -648,6 +642,7 @@ fn test_custom_builtin_binary_operators() {
       )]),
       Default::default(),
       Default::default(),
+      Default::default(),
     ),
   );
   assert!(res.is_ok());
@@ -666,10 +661,7 @@ fn test_custom_builtin_unary_operators() {
 
   let temp = ProgramParser::new()
     .parse(
-      &ParserState {
-        unary_ops: vec![OperatorChars::Carat],
-        binary_ops: vec![],
-      },
+      &ParserState::with_ops(vec![OperatorChars::Carat], vec![]),
       "#main ^4;",
     )
     .unwrap();
@@ -692,6 +684,7 @@ fn test_custom_builtin_unary_operators() {
         },
       )]),
       Default::default(),
+      Default::default(),
     ),
   );
   assert!(res.is_ok());
@@ -701,6 +694,7 @@ fn test_custom_builtin_unary_operators() {
 // Tests builtin functions
 #[test]
 fn test_custom_builtin_functions() {
+  use crate::external_operators::HostBuiltIn;
   use crate::interpreter::interpret;
   use crate::{
     Argument, BlankCustom, CustomBuiltIn, Customs, ParserState, ProgramParser, ReturnVal,
@@ -708,13 +702,7 @@ fn test_custom_builtin_functions() {
   use std::collections::HashMap;
 
   let temp = ProgramParser::new()
-    .parse(
-      &ParserState {
-        unary_ops: vec![],
-        binary_ops: vec![],
-      },
-      "#main test(4);",
-    )
+    .parse(&ParserState::with_ops(vec![], vec![]), "#main test(4);")
     .unwrap();
   let res = interpret::<BlankCustom>(
     &temp,
@@ -725,7 +713,7 @@ fn test_custom_builtin_functions() {
       Default::default(),
       HashMap::from([(
         "test".to_string(),
-        CustomBuiltIn {
+        HostBuiltIn::Untyped(CustomBuiltIn {
           function: |a| {
             if let ReturnVal::Int(a) = a {
               Ok(Argument::Int(a + 5))
@@ -733,8 +721,9 @@ fn test_custom_builtin_functions() {
               panic!()
             }
           },
-        },
+        }),
       )]),
+      Default::default(),
     ),
   );
   assert!(res.is_ok());
@@ -744,6 +733,7 @@ fn test_custom_builtin_functions() {
 // Tests custom types
 #[test]
 fn test_custom_types() {
+  use crate::external_operators::HostBuiltIn;
   use crate::interpreter::interpret;
   use crate::{Argument, CustomBuiltIn, Customs, ParserState, ProgramParser, ReturnVal};
   use std::collections::HashMap;
@@ -754,9 +744,9 @@ fn test_custom_types() {
     denom: i32,
   }
 
-  impl ToString for Custom {
-    fn to_string(&self) -> String {
-      "custom".to_string()
+  impl std::fmt::Display for Custom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      write!(f, "custom")
     }
   }
 
@@ -775,10 +765,7 @@ fn test_custom_types() {
 
   let temp = ProgramParser::new()
     .parse(
-      &ParserState {
-        unary_ops: vec![],
-        binary_ops: vec![],
-      },
+      &ParserState::with_ops(vec![], vec![]),
       "#main frac(4, 3) + 2;",
     )
     .unwrap();
@@ -791,11 +778,11 @@ fn test_custom_types() {
       Default::default(),
       HashMap::from([(
         "frac".to_string(),
-        CustomBuiltIn {
+        HostBuiltIn::Untyped(CustomBuiltIn {
           function: |a| {
             if let ReturnVal::Tuple(v) = a {
               if v.len() == 2 {
-                let v1 = v.get(0).unwrap();
+                let v1 = v.first().unwrap();
                 let v2 = v.get(1).unwrap();
 
                 if let (ReturnVal::Int(n), ReturnVal::Int(d)) = (v1, v2) {
@@ -810,8 +797,9 @@ fn test_custom_types() {
               Err(Box::new("Err 3"))
             }
           },
-        },
+        }),
       )]),
+      Default::default(),
     ),
   );
   assert!(res.is_ok());
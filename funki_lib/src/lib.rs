@@ -2,52 +2,96 @@ extern crate core;
 #[macro_use]
 extern crate lalrpop_util;
 
-use std::cmp;
-use std::collections::HashMap;
-use std::fmt::{Debug, Formatter};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 
 use itertools::Itertools;
-use lalrpop_util::ParseError;
 
+use crate::analysis::{
+  builtin_type, check_sandbox, did_you_mean, infer_types, lint, name_at, scope_at, Found, Target,
+};
 use crate::ast::{ParserState, Program};
-use crate::data_types::{InterpretError, InterpretVal};
+use crate::data_types::{Frame, InterpretError, InterpretVal};
+use crate::docs::{builtin_doc, Entry, Page};
 use crate::external_operators::{
-  CustomBinOp, CustomBuiltIn, CustomType, CustomUnaryOp, OperatorChars,
+  CustomBinOp, CustomBuiltIn, CustomType, CustomUnaryOp, HostBuiltIn, OperatorChars, TypedBuiltIn,
 };
-use crate::interpreter::{interpret, Customs};
+use crate::format::format_program;
+use crate::interpreter::{call_value, evaluate, Customs, BUILTINS};
+use crate::limits::{Limits, StackStart};
+use crate::parser::comments::doc_comments;
 use crate::parser::language_definition::ProgramParser;
+use crate::types::Type;
 
+mod analysis;
 mod ast;
 mod data_types;
+mod docs;
+mod errors;
+mod format;
 mod interpreter;
 mod parser;
 mod test;
 
 pub mod external_operators;
+pub mod limits;
+pub mod syntax;
+pub mod types;
+
+pub use analysis::{Lint, LintKind, Reference, ReferenceKind, Symbol};
+pub use docs::DocFormat;
+pub use errors::{ErrorKind, Label, LanguageErr, Span, StackFrame};
 
 /// Represents a language to be parsed
 pub struct Language<C: CustomType> {
   unary_operators: HashMap<OperatorChars, CustomUnaryOp<C>>,
   binary_operators: HashMap<OperatorChars, CustomBinOp<C>>,
-  built_ins: HashMap<String, CustomBuiltIn<C>>,
+  built_ins: HashMap<String, HostBuiltIn<C>>,
+  limits: Limits,
+  removed_builtins: HashSet<String>,
+  disabled_features: HashSet<Feature>,
+  signatures: HashMap<String, Type>,
+  type_checking: bool,
+}
+
+/// Language features that can be disabled with `Language::disable_feature`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Feature {
+  /// Lambda expressions, `|x => x + 1|`
+  Lambdas,
+  /// Functions that call themselves, directly or through other functions
+  /// Only calls by name are checked, so a function passed to itself, as in `#app f -> f(f);`,
+  /// can still recurse. Calls like that are stopped by `Limits::max_depth`.
+  Recursion,
 }
 
 /// Represents a set of template functions
+/// A script is `Send + Sync` when its custom type is, so a parsed script can be shared between
+/// threads and have its functions called from each of them
 #[derive(Debug)]
 pub struct Script<C: CustomType> {
   lang: String,
   temp: Program,
   unary_operators: HashMap<OperatorChars, CustomUnaryOp<C>>,
   binary_operators: HashMap<OperatorChars, CustomBinOp<C>>,
-  built_ins: HashMap<String, CustomBuiltIn<C>>,
+  built_ins: HashMap<String, HostBuiltIn<C>>,
+  removed_builtins: HashSet<String>,
+  globals: HashMap<String, Argument<C>>,
+  limits: Limits,
+  signatures: HashMap<String, Type>,
 }
 
 /// Represents an argument being parsed in to a function call
+#[derive(Debug)]
 pub enum Argument<C: CustomType> {
   /// Basic integer type
   Int(i32),
   /// Basic String type
   String(String),
+  /// Boolean type, `true` or `false` in scripts
+  Bool(bool),
   /// Tuple type
   Tuple(Vec<Argument<C>>),
   /// List type
@@ -62,6 +106,7 @@ impl<C: CustomType> Clone for Argument<C> {
     match &self {
       Argument::Int(i) => Argument::Int(*i),
       Argument::String(s) => Argument::String(s.clone()),
+      Argument::Bool(b) => Argument::Bool(*b),
       Argument::Tuple(t) => Argument::Tuple(t.clone()),
       Argument::List(t) => Argument::List(t.clone()),
       Argument::Custom(c) => Argument::Custom(c.clone()),
@@ -75,6 +120,9 @@ pub struct LangFunc<'a, C: CustomType> {
   lang: &'a Script<C>,
   name: String,
   arg: Option<Argument<C>>,
+  globals: HashMap<String, Argument<C>>,
+  limits: Option<Limits>,
+  cancel: Option<Arc<AtomicBool>>,
   text: String,
 }
 
@@ -90,6 +138,11 @@ impl<C: CustomType> Language<C> {
       unary_operators: Default::default(),
       binary_operators: Default::default(),
       built_ins: Default::default(),
+      limits: Default::default(),
+      removed_builtins: Default::default(),
+      disabled_features: Default::default(),
+      signatures: Default::default(),
+      type_checking: false,
     }
   }
 
@@ -106,20 +159,202 @@ impl<C: CustomType> Language<C> {
   }
 
   /// Adds a custom builtin function to the Language
+  /// A custom function with the same name as a builtin, such as `map`, overrides the builtin
   pub fn add_custom_function(&mut self, name: String, func: CustomBuiltIn<C>) -> &Self {
-    self.built_ins.entry(name).or_insert(func);
+    self
+      .built_ins
+      .entry(name)
+      .or_insert(HostBuiltIn::Untyped(func));
+    self
+  }
+
+  /// Adds a custom builtin function with declared parameters to the Language
+  /// The arguments of each call are checked against the parameters before the function is
+  /// called, and the function is given one argument for each parameter. The types of the
+  /// parameters and result are also declared for type checking, see `declare_signature`.
+  /// Like `add_custom_function`, a name that is already added is ignored, keeping the first
+  /// function and its signature.
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Argument, ErrorKind, Language, ReturnVal, BlankCustom};
+  /// use funki_lang::external_operators::TypedBuiltIn;
+  /// use funki_lang::types::Type;
+  /// let mut lang = Language::<BlankCustom>::new();
+  /// lang.add_typed_function(
+  ///   "repeat".to_string(),
+  ///   TypedBuiltIn {
+  ///     params: vec![("text".to_string(), Type::String), ("times".to_string(), Type::Int)],
+  ///     result: Type::String,
+  ///     function: |args| match args.as_slice() {
+  ///       [ReturnVal::String(s), ReturnVal::Int(i)] => match usize::try_from(*i) {
+  ///         Ok(n) => Ok(Argument::String(s.repeat(n))),
+  ///         Err(_) => Err(Box::new("`times` can't be negative.")),
+  ///       },
+  ///       _ => unreachable!(),
+  ///     },
+  ///   },
+  /// );
+  /// let x = lang.parse("#main repeat(\"ab\", \"c\");".to_string()).unwrap();
+  /// let err = x.function("main").unwrap().call().unwrap_err();
+  /// assert_eq!(err.kind(), ErrorKind::TypeMismatch);
+  /// assert_eq!(err.message(), "`repeat` expects `times` to be Int, but was given \"c\".");
+  /// assert_eq!(err.range(), Some(19..22));
+  /// ```
+  pub fn add_typed_function(&mut self, name: String, func: TypedBuiltIn<C>) -> &Self {
+    if !self.built_ins.contains_key(&name) {
+      self.signatures.insert(name.clone(), func.signature());
+      self.built_ins.insert(name, HostBuiltIn::Typed(func));
+    }
+    self
+  }
+
+  /// Sets the limits used for every call to a function in scripts parsed by this Language
+  /// These can be overridden for a single call with `LangFunc::limits`
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Language, BlankCustom};
+  /// use funki_lang::limits::Limits;
+  /// let mut lang = Language::<BlankCustom>::new();
+  /// lang.set_limits(Limits { max_depth: Some(100), ..Default::default() });
+  /// let x = lang.parse("#main x -> main(x);".to_string()).unwrap();
+  /// assert!(x.function("main").unwrap().call().is_err());
+  /// ```
+  pub fn set_limits(&mut self, limits: Limits) -> &Self {
+    self.limits = limits;
+    self
+  }
+
+  /// Removes a builtin function, or a custom function added with `add_custom_function`
+  /// Scripts that use the builtin fail to parse
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Language, BlankCustom};
+  /// let mut lang = Language::<BlankCustom>::new();
+  /// lang.remove_builtin("fold");
+  /// assert!(lang.parse("#main x -> fold(x, 0, |(a, b) => a + b|);".to_string()).is_err());
+  /// ```
+  pub fn remove_builtin(&mut self, name: &str) -> &Self {
+    self.built_ins.remove(name);
+    self.removed_builtins.insert(name.to_string());
+    self
+  }
+
+  /// Lists the builtin functions scripts can use, including custom functions added by the host,
+  /// in alphabetical order
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Language, BlankCustom};
+  /// let mut lang = Language::<BlankCustom>::new();
+  /// lang.remove_builtin("fold");
+  /// lang.remove_builtin("assert_eq");
+  /// assert_eq!(lang.builtins(), vec!["all", "any", "filter", "get", "len", "list", "map"]);
+  /// ```
+  pub fn builtins(&self) -> Vec<String> {
+    let mut names: Vec<String> = BUILTINS
+      .iter()
+      .filter(|b| !self.removed_builtins.contains(**b))
+      .map(|b| b.to_string())
+      .chain(self.built_ins.keys().cloned())
+      .collect();
+    names.sort();
+    names.dedup();
+    names
+  }
+
+  /// Disables a language feature
+  /// Scripts that use the feature fail to parse
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Feature, Language, BlankCustom};
+  /// let mut lang = Language::<BlankCustom>::new();
+  /// lang.disable_feature(Feature::Recursion);
+  /// assert!(lang.parse("#main x -> main(x);".to_string()).is_err());
+  /// ```
+  pub fn disable_feature(&mut self, feature: Feature) -> &Self {
+    self.disabled_features.insert(feature);
+    self
+  }
+
+  /// Declares the type of a custom builtin added with `add_custom_function`, so type checking
+  /// knows what it takes and returns
+  /// Custom builtins without a declared type can be used as any type
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Language, BlankCustom};
+  /// use funki_lang::types::Type;
+  /// let mut lang = Language::<BlankCustom>::new();
+  /// lang.declare_signature("shout", Type::function(Type::String, Type::String));
+  /// ```
+  pub fn declare_signature(&mut self, name: &str, signature: Type) -> &Self {
+    self.signatures.insert(name.to_string(), signature);
+    self
+  }
+
+  /// Checks the types of scripts when they are parsed, so scripts that would fail with a type
+  /// error at runtime fail to parse instead
+  /// Types are inferred, Hindley–Milner style, so scripts do not need to declare any. Each function
+  /// takes one type of argument and gives one type of result across its patterns, and operators
+  /// on values of unknown type are taken to be on ints, or bools for logical operators.
+  /// Globals are not known when a script is parsed, so they can be used as any type.
+  /// The inferred types can be found with `Script::signatures`.
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{ErrorKind, Language, BlankCustom};
+  /// let mut lang = Language::<BlankCustom>::new();
+  /// lang.enable_type_checking();
+  /// let err = lang.parse("#main x -> len(x) + \"items\";".to_string()).unwrap_err();
+  /// assert_eq!(err.kind(), ErrorKind::TypeMismatch);
+  /// assert_eq!(err.message(), "Add operator not defined for Int + String.");
+  /// ```
+  pub fn enable_type_checking(&mut self) -> &Self {
+    self.type_checking = true;
     self
   }
 
   /// Parses a set of code into a template
+  /// Every syntax error in the code is reported, see `LanguageErr::errors`
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Language, BlankCustom};
+  /// let lang = Language::<BlankCustom>::new();
+  /// let err = lang.parse("#main x -> x +;\n#other x -> ) x;".to_string()).unwrap_err();
+  /// assert_eq!(err.errors().count(), 2);
+  /// ```
   pub fn parse(&self, code: String) -> Result<Script<C>, LanguageErr> {
-    let parser = ProgramParser::new();
-    let parser_state = ParserState {
-      unary_ops: self.unary_operators.keys().cloned().collect(),
-      binary_ops: self.binary_operators.keys().cloned().collect(),
-    };
-    let res: Result<Program, ParseError<usize, _, (usize, String, usize)>> =
-      parser.parse(&parser_state, &code);
+    let parser_state = ParserState::with_ops(
+      self.unary_operators.keys().cloned().collect(),
+      self.binary_operators.keys().cloned().collect(),
+    );
+
+    let res = parse_program(&code, &parser_state).and_then(|p| {
+      check_sandbox(&p, &self.removed_builtins, &self.disabled_features)
+        .map(|_| p)
+        .map_err(|errs| {
+          let source = Arc::from(code.as_str());
+          LanguageErr::new_multiple(
+            errs
+              .into_iter()
+              .map(|(l, m, r)| {
+                LanguageErr::new_loc(ErrorKind::SandboxViolation, m, (l, r), &source)
+              })
+              .collect(),
+          )
+        })
+    });
+    let res = res.and_then(|p| {
+      if self.type_checking {
+        infer_program(&p, &self.built_ins, &self.signatures, &code)?;
+      }
+      Ok(p)
+    });
 
     match res {
       Ok(l) => Ok(Script {
@@ -128,11 +363,12 @@ impl<C: CustomType> Language<C> {
         unary_operators: self.unary_operators.clone(),
         binary_operators: self.binary_operators.clone(),
         built_ins: self.built_ins.clone(),
+        removed_builtins: self.removed_builtins.clone(),
+        globals: Default::default(),
+        limits: self.limits.clone(),
+        signatures: self.signatures.clone(),
       }),
-      Err(e) => Err(LanguageErr::new_from_parser_err(
-        e.map_token(|_| "".to_string()),
-        code,
-      )),
+      Err(e) => Err(e),
     }
   }
 }
@@ -146,25 +382,56 @@ impl Script<BlankCustom> {
   /// let x = Script::<BlankCustom>::from_text("#main x -> x + 1;");
   /// ```
   pub fn from_text(lang: &str) -> Result<Self, LanguageErr> {
-    let parser: ProgramParser = ProgramParser::new();
-    let res = parser.parse(&ParserState::new(), lang);
-    match res {
-      Ok(l) => Ok(Self {
-        temp: l,
-        lang: lang.to_string(),
-        unary_operators: Default::default(),
-        binary_operators: Default::default(),
-        built_ins: Default::default(),
-      }),
-      Err(e) => {
-        // println!("{}", e);
-        Err(LanguageErr::new_from_parser_err(
-          e.map_token(|_| "".to_string()),
-          lang.to_string(),
-        ))
-      }
+    Ok(Self {
+      temp: parse_program(lang, &ParserState::new())?,
+      lang: lang.to_string(),
+      unary_operators: Default::default(),
+      binary_operators: Default::default(),
+      built_ins: Default::default(),
+      removed_builtins: Default::default(),
+      globals: Default::default(),
+      limits: Default::default(),
+      signatures: Default::default(),
+    })
+  }
+}
+
+// Parses code into a program, collecting every syntax error the parser recovers from along with
+//  the error it stopped at
+fn parse_program(code: &str, state: &ParserState) -> Result<Program, LanguageErr> {
+  let res = ProgramParser::new().parse(state, code);
+  let mut errors = state.errors.take();
+  match res {
+    Ok(mut p) if errors.is_empty() => {
+      p.docs = doc_comments(code, &p.names);
+      return Ok(p);
     }
+    Ok(_) => (),
+    Err(e) => errors.push(e.map_token(|_| "".to_string())),
   }
+  Err(LanguageErr::new_from_parser_errs(errors, code))
+}
+
+// Infers the types of the functions in a program, reporting every type error found
+fn infer_program<C: CustomType>(
+  program: &Program,
+  built_ins: &HashMap<String, HostBuiltIn<C>>,
+  signatures: &HashMap<String, Type>,
+  code: &str,
+) -> Result<BTreeMap<String, Type>, LanguageErr> {
+  let customs = built_ins
+    .keys()
+    .map(|n| (n.as_str(), signatures.get(n).cloned()))
+    .collect();
+  infer_types(program, &customs).map_err(|errs| {
+    let source = Arc::from(code);
+    LanguageErr::new_multiple(
+      errs
+        .into_iter()
+        .map(|(l, m, r)| LanguageErr::new_loc(ErrorKind::TypeMismatch, m, (l, r), &source))
+        .collect(),
+    )
+  })
 }
 
 impl<C: CustomType> Script<C> {
@@ -177,7 +444,7 @@ impl<C: CustomType> Script<C> {
   /// x.list(); // -> ["main"]
   /// ```
   pub fn list(&self) -> Vec<String> {
-    return self.temp.env.keys().map(|s| s.to_string()).collect();
+    self.temp.env.keys().map(|s| s.to_string()).collect()
   }
 
   /// Selects a function from the ParsedTemplatete
@@ -187,19 +454,341 @@ impl<C: CustomType> Script<C> {
   /// let x = Script::<BlankCustom>::from_text("#main x -> x + 1;").unwrap();
   /// let f = x.function("main");
   /// ```
-  pub fn function(&self, name: &str) -> Result<LangFunc<C>, LanguageErr> {
+  pub fn function(&self, name: &str) -> Result<LangFunc<'_, C>, LanguageErr> {
     if self.temp.env.contains_key(name) {
       Ok(LangFunc {
         lang: self,
         name: name.to_string(),
         arg: None,
+        globals: Default::default(),
+        limits: None,
+        cancel: None,
         text: self.lang.clone(),
       })
     } else {
-      Err(LanguageErr::new_no_loc(format!(
-        "Cannot find function \"{}\".",
-        name
-      )))
+      Err(LanguageErr::new_no_loc(
+        ErrorKind::UnknownFunction,
+        format!(
+          "Cannot find function \"{}\".{}",
+          name,
+          did_you_mean(name, self.temp.env.keys().map(|k| k.as_str()))
+        ),
+      ))
+    }
+  }
+
+  /// Sets a global value that is visible to every function in the script
+  /// Globals are resolved after pattern variables and function names, so a pattern variable with
+  /// the same name shadows the global. A global cannot share a name with a function.
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Argument, Script, BlankCustom};
+  /// let mut x = Script::<BlankCustom>::from_text("#main f\"Welcome to {site}\"f;").unwrap();
+  /// x.set_global("site", Argument::String("funki".to_string())).unwrap();
+  /// x.function("main").unwrap().call().unwrap(); // -> ReturnVal::String("Welcome to funki")
+  /// ```
+  pub fn set_global(&mut self, name: &str, val: Argument<C>) -> Result<&mut Self, LanguageErr> {
+    self.check_global_name(name)?;
+    self.globals.insert(name.to_string(), val);
+    Ok(self)
+  }
+
+  /// Formats the script's code in the canonical style, keeping its comments
+  /// Functions stay in the order they are written, with the first pattern on the same line as
+  /// the function's name and the rest indented on their own lines. Lines longer than 100
+  /// characters are broken after `->`, operators and commas.
+  /// A function defined more than once only keeps the definition that is used.
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Script, BlankCustom};
+  /// let code = "#fib 0->1; 1->1;\n  n ->fib(n - 1)+fib( n - 2 ); // slow";
+  /// let x = Script::<BlankCustom>::from_text(code).unwrap();
+  /// assert_eq!(
+  ///   x.format(),
+  ///   "#fib 0 -> 1;\n  1 -> 1;\n  n -> fib(n - 1) + fib(n - 2); // slow\n"
+  /// );
+  /// ```
+  pub fn format(&self) -> String {
+    format_program(&self.temp, &self.lang)
+  }
+
+  /// Finds code that is likely to be a mistake, such as unused variables, patterns that can never
+  /// match or arguments that no pattern matches, in the order it is in the code
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Script, BlankCustom, LintKind};
+  /// let x = Script::<BlankCustom>::from_text("#main (x, y) -> x + 1;").unwrap();
+  /// let lints = x.lints();
+  /// assert_eq!(lints[0].kind, LintKind::UnusedVariable);
+  /// assert_eq!(lints[0].code(), "W001");
+  /// assert_eq!(lints[0].span.range(), 10..11);
+  /// ```
+  pub fn lints(&self) -> Vec<Lint> {
+    let known = BUILTINS
+      .iter()
+      .copied()
+      .chain(self.built_ins.keys().map(|k| k.as_str()))
+      .chain(self.globals.keys().map(|k| k.as_str()))
+      .collect();
+    lint(&self.temp, &known, &self.lang)
+  }
+
+  /// Lists the functions in the script in the order they are written, for tooling such as editors
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Script, BlankCustom};
+  /// let x = Script::<BlankCustom>::from_text("#fib 0 -> 1;\n  n -> fib(n - 1) | n > 0;").unwrap();
+  /// let symbols = x.symbols();
+  /// assert_eq!(symbols[0].name, "fib");
+  /// assert_eq!(symbols[0].name_span.range(), 0..4);
+  /// assert_eq!(symbols[0].patterns, vec!["0 -> 1", "n -> fib(n - 1) | n > 0"]);
+  /// ```
+  pub fn symbols(&self) -> Vec<Symbol> {
+    let mut symbols: Vec<Symbol> = self
+      .temp
+      .env
+      .iter()
+      .filter_map(|(name, patterns)| {
+        let (start, name_end) = *self.temp.names.get(name)?;
+        let end = patterns.last().map_or(name_end, |p| p.span().1);
+        Some(Symbol {
+          name: name.clone(),
+          name_span: Span::new(&self.lang, start, name_end),
+          span: Span::new(&self.lang, start, end),
+          patterns: patterns
+            .iter()
+            .map(|p| self.lang[p.span().0..p.span().1].to_string())
+            .collect(),
+          doc: self.temp.docs.get(name).cloned(),
+        })
+      })
+      .collect();
+    symbols.sort_by_key(|s| s.span.start);
+    symbols
+  }
+
+  /// The tree of the script, with where each function, pattern and expression is in the code
+  /// See `syntax::Visitor` to walk through it.
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Script, BlankCustom};
+  /// use funki_lang::syntax::ExprKind;
+  /// let x = Script::<BlankCustom>::from_text("#main 1;\n#double x -> x * 2;").unwrap();
+  /// let tree = x.syntax();
+  /// let double = &tree.functions()[1];
+  /// assert_eq!(double.name(), "double");
+  /// let pattern = &double.patterns()[0];
+  /// assert_eq!(pattern.args().unwrap().kind(), &ExprKind::Var("x".to_string()));
+  /// assert_eq!(pattern.result().span().range(), 22..27);
+  /// ```
+  pub fn syntax(&self) -> syntax::Program {
+    syntax::from_program(&self.temp, &self.lang)
+  }
+
+  /// The doc comment of a function, the `///` lines just before its name, without the `///`s
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Script, BlankCustom};
+  /// let x = Script::<BlankCustom>::from_text("/// Doubles a number\n#double x -> x * 2;").unwrap();
+  /// assert_eq!(x.doc("double"), Some("Doubles a number"));
+  /// ```
+  pub fn doc(&self, name: &str) -> Option<&str> {
+    self.temp.docs.get(name).map(|d| d.as_str())
+  }
+
+  /// Makes a reference page for the script, listing each function with its type, doc comment
+  /// and patterns, followed by the builtins and the functions added by the host
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{DocFormat, Script, BlankCustom};
+  /// let x = Script::<BlankCustom>::from_text("/// Doubles a number\n#double x -> x * 2;").unwrap();
+  /// let page = x.reference("maths", DocFormat::Markdown);
+  /// assert!(page.starts_with("# maths\n\n## Functions\n\n### `double`\n\n`double: Int -> Int`\n"));
+  /// ```
+  pub fn reference(&self, title: &str, format: DocFormat) -> String {
+    let types = self.signatures().unwrap_or_default();
+    let functions = self
+      .symbols()
+      .into_iter()
+      .map(|s| Entry {
+        signature: types.get(&s.name).map(|t| t.to_string()),
+        doc: s.doc.unwrap_or_default(),
+        code: Some(format!("#{} {};", s.name, s.patterns.join(";\n  "))),
+        name: s.name,
+      })
+      .collect();
+
+    // Functions added by the host replace builtins with the same name
+    let builtins = BUILTINS
+      .iter()
+      .filter(|b| !self.removed_builtins.contains(**b) && !self.built_ins.contains_key(**b))
+      .map(|b| Entry {
+        name: b.to_string(),
+        signature: builtin_type(b).map(|t| t.to_string()),
+        doc: builtin_doc(b).to_string(),
+        code: None,
+      })
+      .collect();
+
+    let mut host: Vec<Entry> = self
+      .built_ins
+      .iter()
+      .map(|(name, b)| match b {
+        HostBuiltIn::Typed(t) => Entry {
+          name: name.clone(),
+          signature: Some(t.signature().to_string()),
+          doc: match t.params.is_empty() {
+            true => String::new(),
+            false => format!(
+              "Takes {}.",
+              t.params
+                .iter()
+                .map(|(p, t)| format!("`{}: {}`", p, t))
+                .join(", ")
+            ),
+          },
+          code: None,
+        },
+        HostBuiltIn::Untyped(_) => Entry {
+          name: name.clone(),
+          signature: self.signatures.get(name).map(|t| t.to_string()),
+          doc: String::new(),
+          code: None,
+        },
+      })
+      .collect();
+    host.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Page {
+      title: title.to_string(),
+      sections: vec![
+        ("Functions".to_string(), functions),
+        ("Builtins".to_string(), builtins),
+        ("Host functions".to_string(), host),
+      ],
+    }
+    .render(format)
+  }
+
+  /// Finds the name at a byte offset in the code and what it refers to, for tooling such as
+  /// editors. An offset just after a name counts as being on the name.
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Script, BlankCustom, ReferenceKind};
+  /// let x = Script::<BlankCustom>::from_text("#main x -> double(x);\n#double x -> x * 2;").unwrap();
+  /// let reference = x.reference_at(12).unwrap();
+  /// assert_eq!(reference.name, "double");
+  /// assert_eq!(reference.kind, ReferenceKind::Function);
+  /// assert_eq!(reference.definition.unwrap().range(), 22..29);
+  /// let reference = x.reference_at(19).unwrap();
+  /// assert_eq!(reference.kind, ReferenceKind::Variable);
+  /// assert_eq!(reference.definition.unwrap().range(), 6..7);
+  /// ```
+  pub fn reference_at(&self, offset: usize) -> Option<Reference> {
+    let Found {
+      name,
+      start,
+      end,
+      target,
+    } = name_at(&self.temp, offset)?;
+    let (kind, definition) = match target {
+      Target::Function => (ReferenceKind::Function, self.temp.names.get(&name).copied()),
+      Target::Variable(l, r) => (ReferenceKind::Variable, Some((l, r))),
+      Target::Other => (self.kind_of(&name), None),
+    };
+    Some(Reference {
+      span: Span::new(&self.lang, start, end),
+      definition: definition.map(|(l, r)| Span::new(&self.lang, l, r)),
+      name,
+      kind,
+    })
+  }
+
+  /// Lists the names that can be used at a byte offset in the code, for completion in editors
+  /// Variables in scope come first, innermost first, followed by the functions, globals and
+  /// builtins.
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Script, BlankCustom, ReferenceKind};
+  /// let x = Script::<BlankCustom>::from_text("#main x -> map(x, |y => y|);").unwrap();
+  /// let names = x.names_at(24);
+  /// assert_eq!(names[0], ("y".to_string(), ReferenceKind::Variable));
+  /// assert_eq!(names[1], ("x".to_string(), ReferenceKind::Variable));
+  /// assert!(names.contains(&("main".to_string(), ReferenceKind::Function)));
+  /// assert!(names.contains(&("map".to_string(), ReferenceKind::Builtin)));
+  /// ```
+  pub fn names_at(&self, offset: usize) -> Vec<(String, ReferenceKind)> {
+    let mut names: Vec<(String, ReferenceKind)> = scope_at(&self.temp, offset)
+      .into_iter()
+      .map(|n| (n.to_string(), ReferenceKind::Variable))
+      .collect();
+    let mut add = |found: Vec<&String>, kind| {
+      let mut found: Vec<_> = found.into_iter().cloned().collect();
+      found.sort();
+      names.extend(found.into_iter().map(|n| (n, kind)));
+    };
+    add(self.temp.env.keys().collect(), ReferenceKind::Function);
+    add(self.globals.keys().collect(), ReferenceKind::Global);
+    add(
+      self
+        .built_ins
+        .keys()
+        .chain(BUILTINS.map(String::from).iter())
+        .collect(),
+      ReferenceKind::Builtin,
+    );
+    names
+  }
+
+  // What kind of value a name that is not a function or variable refers to
+  fn kind_of(&self, name: &str) -> ReferenceKind {
+    if self.globals.contains_key(name) {
+      ReferenceKind::Global
+    } else if self.built_ins.contains_key(name) || BUILTINS.contains(&name) {
+      ReferenceKind::Builtin
+    } else {
+      ReferenceKind::Unknown
+    }
+  }
+
+  /// Infers the type of each function, for tooling such as editors
+  /// This works whether or not the Language checks types, and errors in the same way if the
+  /// script has type errors, see `Language::enable_type_checking`
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Script, BlankCustom};
+  /// let x = Script::<BlankCustom>::from_text("#count xs -> len(xs) * 2;").unwrap();
+  /// let types = x.signatures().unwrap();
+  /// assert_eq!(types["count"].to_string(), "[a] -> Int");
+  /// ```
+  pub fn signatures(&self) -> Result<BTreeMap<String, Type>, LanguageErr> {
+    infer_program(&self.temp, &self.built_ins, &self.signatures, &self.lang)
+  }
+
+  // Checks a global name does not collide with a function or a reserved name
+  fn check_global_name(&self, name: &str) -> Result<(), LanguageErr> {
+    if self.temp.env.contains_key(name) {
+      Err(LanguageErr::new_no_loc(
+        ErrorKind::InvalidGlobal,
+        format!("Global \"{}\" has the same name as a function.", name),
+      ))
+    } else if matches!(name, "true" | "false" | "_") {
+      Err(LanguageErr::new_no_loc(
+        ErrorKind::InvalidGlobal,
+        format!("Global \"{}\" uses a reserved name.", name),
+      ))
+    } else {
+      Ok(())
     }
   }
 }
@@ -212,6 +801,90 @@ pub enum ReturnVal<T: CustomType> {
   Tuple(Vec<ReturnVal<T>>),
   List(Vec<ReturnVal<T>>),
   Custom(T),
+  /// A function, lambda or builtin returned from the script
+  Callable(Callable<T>),
+}
+
+/// A function returned from a script that can be called from the host
+/// Keeps the environment it was created in alive, so it can be called after the script function
+/// that returned it, and the script itself, have been dropped
+#[derive(Clone)]
+pub struct Callable<C: CustomType> {
+  val: InterpretVal<C>,
+  root: Frame<C>,
+  customs: Arc<Customs<C>>,
+  text: Arc<String>,
+  cancel: Option<Arc<AtomicBool>>,
+}
+
+impl<C: CustomType> Callable<C> {
+  /// Calls this function with an argument
+  /// The limits of the call that returned this function apply to each call separately
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Argument, Script, BlankCustom, ReturnVal};
+  /// let x = Script::<BlankCustom>::from_text("#main n -> |x => x * n|;").unwrap();
+  /// let f = x.function("main").unwrap().arg(Argument::Int(3)).call().unwrap();
+  /// if let ReturnVal::Callable(c) = f {
+  ///   c.call(Argument::Int(5)).unwrap(); // -> ReturnVal::Int(15)
+  /// }
+  /// ```
+  pub fn call(&self, arg: Argument<C>) -> Result<ReturnVal<C>, LanguageErr> {
+    let mut customs = self.customs.fresh();
+    if let Some(c) = &self.cancel {
+      customs.set_cancel(c.clone());
+    }
+    let _stack = StackStart::enter();
+    call_value(
+      self.val.clone(),
+      InterpretVal::from_arg(&arg),
+      &mut self.root.clone(),
+      &customs,
+    )
+    .and_then(|v| to_host_val(&v, &self.root, &self.customs, &self.text))
+    .map_err(|e| LanguageErr::new_from_int_err(e, &self.text))
+  }
+
+  /// Sets a token that can be used to cancel calls of this function from another thread,
+  /// replacing the token of the call that returned it
+  /// Each call stops with an `ErrorKind::Cancelled` error at the next function or builtin call
+  /// after the token is set to `true`
+  ///
+  /// ## Example
+  /// ```
+  /// use std::sync::Arc;
+  /// use std::sync::atomic::AtomicBool;
+  /// use funki_lang::{Argument, Script, BlankCustom, ErrorKind, ReturnVal};
+  /// let x = Script::<BlankCustom>::from_text("#main n -> |x => x * n|;").unwrap();
+  /// let f = x.function("main").unwrap().arg(Argument::Int(3)).call().unwrap();
+  /// if let ReturnVal::Callable(c) = f {
+  ///   let c = c.cancel_token(Arc::new(AtomicBool::new(true)));
+  ///   assert_eq!(c.call(Argument::Int(5)).unwrap_err().kind(), ErrorKind::Cancelled);
+  /// }
+  /// ```
+  pub fn cancel_token(mut self, token: Arc<AtomicBool>) -> Self {
+    self.cancel = Some(token);
+    self
+  }
+}
+
+// Converts an interpreted value to a value for the host, wrapping any functions in callables
+fn to_host_val<C: CustomType>(
+  val: &InterpretVal<C>,
+  root: &Frame<C>,
+  customs: &Arc<Customs<C>>,
+  text: &Arc<String>,
+) -> Result<ReturnVal<C>, InterpretError> {
+  val.to_return_val_with(&|v| {
+    Ok(ReturnVal::Callable(Callable {
+      val: v.clone(),
+      root: root.clone(),
+      customs: customs.clone(),
+      text: text.clone(),
+      cancel: None,
+    }))
+  })
 }
 
 impl<'a, C: CustomType> LangFunc<'a, C> {
@@ -229,6 +902,57 @@ impl<'a, C: CustomType> LangFunc<'a, C> {
     self.arg = Some(arg);
     self
   }
+
+  /// Adds a global value for this call only
+  /// Shadows a global of the same name set on the script with `Script::set_global`
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Argument, Script, BlankCustom};
+  /// let x = Script::<BlankCustom>::from_text("#main x -> x * scale;").unwrap();
+  /// let f = x.function("main").unwrap();
+  /// let f = f.arg(Argument::Int(5)).with_global("scale", Argument::Int(3));
+  /// f.call().unwrap(); // -> ReturnVal::Int(15)
+  /// ```
+  pub fn with_global(mut self, name: &str, val: Argument<C>) -> Self {
+    self.globals.insert(name.to_string(), val);
+    self
+  }
+
+  /// Sets the limits for this call, replacing the limits set on the Language
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::{Argument, Script, BlankCustom};
+  /// use funki_lang::limits::Limits;
+  /// let x = Script::<BlankCustom>::from_text("#main x -> x * x;").unwrap();
+  /// let f = x.function("main").unwrap().arg(Argument::Int(5));
+  /// let f = f.limits(Limits { max_steps: Some(2), ..Default::default() });
+  /// assert!(f.call().is_err());
+  /// ```
+  pub fn limits(mut self, limits: Limits) -> Self {
+    self.limits = Some(limits);
+    self
+  }
+
+  /// Sets a token that can be used to cancel this call from another thread
+  /// The call stops with an `ErrorKind::Cancelled` error at the next function or builtin call
+  /// after the token is set to `true`
+  ///
+  /// ## Example
+  /// ```
+  /// use std::sync::Arc;
+  /// use std::sync::atomic::AtomicBool;
+  /// use funki_lang::{Argument, Script, BlankCustom, ErrorKind};
+  /// let x = Script::<BlankCustom>::from_text("#main x -> x + 1;").unwrap();
+  /// let token = Arc::new(AtomicBool::new(true));
+  /// let f = x.function("main").unwrap().arg(Argument::Int(5)).cancel_token(token);
+  /// assert_eq!(f.call().unwrap_err().kind(), ErrorKind::Cancelled);
+  /// ```
+  pub fn cancel_token(mut self, token: Arc<AtomicBool>) -> Self {
+    self.cancel = Some(token);
+    self
+  }
   /// Interprets this function
   /// Can return a language errParsedTemplatethe interpretation faiParsedTemplate//
   /// ## Example
@@ -239,164 +963,42 @@ impl<'a, C: CustomType> LangFunc<'a, C> {
   /// f.call().unwrap(); // -> ReturnVal::Int(5)
   /// ```
   pub fn call(&self) -> Result<ReturnVal<C>, LanguageErr> {
-    if let Some(x) = &self.arg {
-      interpret(
-        &self.lang.temp,
-        self.name.as_str(),
-        InterpretVal::from_arg(x),
-        &Customs::new_from_hash(
-          self.lang.binary_operators.clone(),
-          self.lang.unary_operators.clone(),
-          self.lang.built_ins.clone(),
-        ),
-      )
-    } else {
-      interpret(
-        &self.lang.temp,
-        self.name.as_str(),
-        InterpretVal::Tuple(vec![]),
-        &Customs::new_from_hash(Default::default(), Default::default(), Default::default()),
-      )
-    }
-    .map_err(|e| LanguageErr::new_from_int_err(e, self.text.clone()))
-  }
-}
-
-/// A language error with a location
-pub struct LocationLangErr {
-  message: String,
-  lines: (usize, usize),
-  char: (usize, usize),
-  section: String,
-}
-
-/// An enum for the possible types of error that can result from interpretation
-pub enum LanguageErr {
-  NoLoc(String),
-  Loc(LocationLangErr),
-}
-
-impl LanguageErr {
-  /// Creates a language error with location information
-  /// Adds in the original language string so the line numbers and string section can be found
-  fn new_loc(message: String, location: (usize, usize), lang: String) -> Self {
-    let (start_line, start_char) = get_lang_pos(&lang, location.0);
-    let (end_line, end_char) = get_lang_pos(&lang, location.1);
-    LanguageErr::Loc(LocationLangErr {
-      lines: (start_line, end_line),
-      section: lang[location.0..location.1].to_string(),
-      char: (start_char, end_char),
-      message,
-    })
-  }
-
-  /// Creates a location error with no location data
-  fn new_no_loc(message: String) -> Self {
-    LanguageErr::NoLoc(message)
-  }
-
-  /// Creates a location error from an interpretation error
-  fn new_from_int_err(err: InterpretError, lang: String) -> Self {
-    if err.location.is_some() {
-      Self::new_loc(err.message, err.location.unwrap(), lang)
-    } else {
-      Self::new_no_loc(err.message)
+    let mut globals: HashMap<String, InterpretVal<C>> = self
+      .lang
+      .globals
+      .iter()
+      .map(|(n, v)| (n.clone(), InterpretVal::from_arg(v)))
+      .collect();
+    for (n, v) in &self.globals {
+      self.lang.check_global_name(n)?;
+      globals.insert(n.clone(), InterpretVal::from_arg(v));
     }
-  }
 
-  /// Creates a location error from a parser error
-  fn new_from_parser_err(
-    err: ParseError<usize, String, (usize, String, usize)>,
-    lang: String,
-  ) -> Self {
-    match err {
-      ParseError::InvalidToken { location } => {
-        let (line, char) = get_lang_pos(&lang, location);
-        Self::Loc(LocationLangErr {
-          message: "Invalid token".to_string(),
-          lines: (line, line),
-          char: (char, char),
-          section: lang[location..location + 10].to_string(),
-        })
-      }
-      ParseError::UnrecognizedEOF { location, .. } => {
-        let (line, char) = get_lang_pos(&lang, location);
-        Self::Loc(LocationLangErr {
-          message: "Unexpected End of File".to_string(),
-          lines: (line, line),
-          char: (char, char),
-          section: lang[cmp::min(location - 10, 0)..].to_string(),
-        })
-      }
-      ParseError::UnrecognizedToken {
-        token: (l, _, r), ..
-      } => {
-        let (start_line, start_char) = get_lang_pos(&lang, l);
-        let (end_line, end_char) = get_lang_pos(&lang, r);
-        Self::Loc(LocationLangErr {
-          message: "Unrecognised token".to_string(),
-          lines: (start_line, end_line),
-          char: (start_char, end_char),
-          section: lang[l..r].to_string(),
-        })
-      }
-      ParseError::ExtraToken {
-        token: (l, _, r), ..
-      } => {
-        let (start_line, start_char) = get_lang_pos(&lang, l);
-        let (end_line, end_char) = get_lang_pos(&lang, r);
-        Self::Loc(LocationLangErr {
-          message: "Extra token".to_string(),
-          lines: (start_line, end_line),
-          char: (start_char, end_char),
-          section: lang[l..r].to_string(),
-        })
-      }
-      ParseError::User { error: (l, m, r) } => {
-        let (start_line, start_char) = get_lang_pos(&lang, l);
-        let (end_line, end_char) = get_lang_pos(&lang, r);
-        Self::Loc(LocationLangErr {
-          message: m,
-          lines: (start_line, end_line),
-          char: (start_char, end_char),
-          section: lang[l..r].to_string(),
-        })
-      }
+    let mut customs = Customs::new_from_hash(
+      self.lang.binary_operators.clone(),
+      self.lang.unary_operators.clone(),
+      self.lang.built_ins.clone(),
+      self.limits.as_ref().unwrap_or(&self.lang.limits).clone(),
+    );
+    if let Some(c) = &self.cancel {
+      customs.set_cancel(c.clone());
     }
-  }
-}
-
-fn get_lang_pos(lang: &str, pos: usize) -> (usize, usize) {
-  let new_lines = lang[0..pos]
-    .as_bytes()
-    .iter()
-    .enumerate()
-    .filter(|(_, c)| **c == b'\n');
-  let line_num = new_lines.clone().count();
-  let char = pos - new_lines.last().unwrap_or((0, &b'x')).0;
-
-  (line_num, char)
-}
+    let customs = Arc::new(customs);
 
-impl Debug for LanguageErr {
-  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
-    match self {
-      LanguageErr::Loc(l) => {
-        write!(
-          fmt,
-          "Error: \"{}\"\nAt lines: {}:{} - {}:{}\nCode: `{}`",
-          l.message,
-          l.lines.0 + 1,
-          l.char.0,
-          l.lines.1 + 1,
-          l.char.1,
-          l.section
-        )
-      }
-      LanguageErr::NoLoc(l) => {
-        write!(fmt, "Error: {}", l)
-      }
-    }
+    let _stack = StackStart::enter();
+    evaluate(
+      &self.lang.temp,
+      self.name.as_str(),
+      self
+        .arg
+        .as_ref()
+        .map(InterpretVal::from_arg)
+        .unwrap_or_else(|| InterpretVal::Tuple(vec![])),
+      globals,
+      &customs,
+    )
+    .and_then(|(v, root)| to_host_val(&v, &root, &customs, &Arc::new(self.text.clone())))
+    .map_err(|e| LanguageErr::new_from_int_err(e, &self.text))
   }
 }
 
@@ -417,19 +1019,21 @@ impl<C: CustomType> Debug for ReturnVal<C> {
         v.iter().map(|i| format!("{:?}", i)).join(", ")
       ),
       ReturnVal::Custom(v) => write!(fmt, "Custom({:?})", v),
+      ReturnVal::Callable(c) => write!(fmt, "Callable({:?})", c.val),
     }
   }
 }
 
-impl<C: CustomType> ToString for ReturnVal<C> {
-  fn to_string(&self) -> String {
+impl<C: CustomType> Display for ReturnVal<C> {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
     match self {
-      ReturnVal::Bool(b) => b.to_string(),
-      ReturnVal::Int(i) => i.to_string(),
-      ReturnVal::String(s) => s.to_string(),
-      ReturnVal::Tuple(v) => format!("({})", v.iter().map(|i| i.to_string()).join(", ")),
-      ReturnVal::List(v) => format!("[{}]", v.iter().map(|i| i.to_string()).join(", ")),
-      ReturnVal::Custom(v) => v.to_string(),
+      ReturnVal::Bool(b) => write!(fmt, "{}", b),
+      ReturnVal::Int(i) => write!(fmt, "{}", i),
+      ReturnVal::String(s) => write!(fmt, "{}", s),
+      ReturnVal::Tuple(v) => write!(fmt, "({})", v.iter().map(|i| i.to_string()).join(", ")),
+      ReturnVal::List(v) => write!(fmt, "[{}]", v.iter().map(|i| i.to_string()).join(", ")),
+      ReturnVal::Custom(v) => write!(fmt, "{}", v.to_string()),
+      ReturnVal::Callable(_) => write!(fmt, "<callable>"),
     }
   }
 }
@@ -438,9 +1042,9 @@ impl<C: CustomType> ToString for ReturnVal<C> {
 #[derive(Clone, Debug, PartialEq)]
 pub struct BlankCustom {}
 
-impl ToString for BlankCustom {
-  fn to_string(&self) -> String {
-    "Blank".to_string()
+impl Display for BlankCustom {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(fmt, "Blank")
   }
 }
 
@@ -0,0 +1,227 @@
+use std::cell::Cell;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::data_types::InterpretError;
+
+/// The call depth allowed by `Limits::default`, which fits in the 2 MiB stack of a spawned
+/// thread in optimised builds.
+pub const DEFAULT_MAX_DEPTH: usize = 200;
+
+/// Limits on the resources a single function call can use.
+/// Any limit left as `None` is not enforced.
+///
+/// By default only the call depth is limited, so a script that recurses forever fails with an
+/// error rather than overflowing the stack and aborting the host. Unoptimised builds use several
+/// times more stack for each call, so hosts built without optimisations should call scripts on a
+/// thread with a bigger stack, or set `max_stack`. Hosts running untrusted scripts should also set
+/// `max_steps` or `timeout`, and `max_alloc`.
+///
+/// ## Example
+/// ```
+/// use std::time::Duration;
+/// use funki_lang::limits::Limits;
+/// let limits = Limits {
+///   max_steps: Some(100_000),
+///   max_depth: Some(200),
+///   max_stack: Some(1_000_000),
+///   max_alloc: Some(1_000_000),
+///   timeout: Some(Duration::from_millis(100)),
+/// };
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+  /// The maximum number of expressions that can be evaluated.
+  pub max_steps: Option<u64>,
+  /// The maximum depth of nested function and lambda calls.
+  pub max_depth: Option<usize>,
+  /// The maximum stack, in bytes, that a call and the calls made by host builtins during it can
+  /// use. How much stack each nested call needs depends on the code and on how the host was
+  /// compiled, so this stops deep recursion on threads with small stacks before `max_depth` is
+  /// reached. Threads with bigger stacks can raise it.
+  pub max_stack: Option<usize>,
+  /// The maximum total size of the strings (in bytes) and lists (in items) that can be built.
+  pub max_alloc: Option<usize>,
+  /// The maximum time a call can run for.
+  pub timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+  fn default() -> Self {
+    Limits {
+      max_steps: None,
+      max_depth: Some(DEFAULT_MAX_DEPTH),
+      max_stack: None,
+      max_alloc: None,
+      timeout: None,
+    }
+  }
+}
+
+/// The limit that stopped an evaluation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Limit {
+  /// `Limits::max_steps` was exceeded.
+  Steps,
+  /// `Limits::max_depth` was exceeded.
+  Depth,
+  /// `Limits::max_stack` was exceeded.
+  Stack,
+  /// `Limits::max_alloc` was exceeded.
+  Allocation,
+  /// `Limits::timeout` was exceeded.
+  Timeout,
+}
+
+impl Display for Limit {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Limit::Steps => write!(fmt, "step limit"),
+      Limit::Depth => write!(fmt, "call depth limit"),
+      Limit::Stack => write!(fmt, "stack limit"),
+      Limit::Allocation => write!(fmt, "allocation limit"),
+      Limit::Timeout => write!(fmt, "timeout"),
+    }
+  }
+}
+
+thread_local! {
+  // Where the stack was when the outermost call on this thread started
+  static STACK_START: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+// The address of a local, which shows how deep the stack currently is
+fn stack_position() -> usize {
+  let marker = 0u8;
+  std::hint::black_box(&marker) as *const u8 as usize
+}
+
+// Marks where the stack was when a call from the host started, until it is dropped
+// Calls made by host builtins during a call are measured from the outermost call, so they share
+//  its stack limit
+pub struct StackStart {
+  outermost: bool,
+}
+
+impl StackStart {
+  pub fn enter() -> Self {
+    let outermost = STACK_START.get().is_none();
+    if outermost {
+      STACK_START.set(Some(stack_position()));
+    }
+    StackStart { outermost }
+  }
+}
+
+impl Drop for StackStart {
+  fn drop(&mut self) {
+    if self.outermost {
+      STACK_START.set(None);
+    }
+  }
+}
+
+// Tracks the resources used by a single call against its limits
+// Uses atomics so a tracker can be shared along with the rest of the customs
+#[derive(Debug)]
+pub struct LimitTracker {
+  limits: Limits,
+  deadline: Option<Instant>,
+  steps: AtomicU64,
+  depth: AtomicUsize,
+  alloc: AtomicUsize,
+}
+
+// Decrements the call depth when a call finishes
+pub struct DepthGuard<'a> {
+  tracker: &'a LimitTracker,
+}
+
+impl Drop for DepthGuard<'_> {
+  fn drop(&mut self) {
+    self.tracker.depth.fetch_sub(1, Ordering::Relaxed);
+  }
+}
+
+impl LimitTracker {
+  // Starts tracking a call, the timeout starts from when this is called
+  pub fn new(limits: Limits) -> Self {
+    Self {
+      deadline: limits.timeout.map(|t| Instant::now() + t),
+      limits,
+      steps: AtomicU64::new(0),
+      depth: AtomicUsize::new(0),
+      alloc: AtomicUsize::new(0),
+    }
+  }
+
+  // The limits being tracked
+  pub fn limits(&self) -> &Limits {
+    &self.limits
+  }
+
+  // Counts one evaluation step, checking the step limit and the timeout
+  pub fn step(&self) -> Result<(), InterpretError> {
+    let steps = self.steps.fetch_add(1, Ordering::Relaxed) + 1;
+    if let Some(max) = self.limits.max_steps {
+      if steps > max {
+        return Err(InterpretError::new_limit(
+          Limit::Steps,
+          &format!("Step limit of {} exceeded.", max),
+        ));
+      }
+    }
+    if let Some(deadline) = self.deadline {
+      if Instant::now() > deadline {
+        return Err(InterpretError::new_limit(
+          Limit::Timeout,
+          &format!("Timeout of {:?} exceeded.", self.limits.timeout.unwrap()),
+        ));
+      }
+    }
+    Ok(())
+  }
+
+  // Enters a function or lambda call, checking the depth and stack limits
+  // The depth is decremented when the returned guard is dropped
+  // The stack is only checked within a call marked with `StackStart`
+  pub fn enter(&self) -> Result<DepthGuard<'_>, InterpretError> {
+    let depth = self.depth.fetch_add(1, Ordering::Relaxed) + 1;
+    let guard = DepthGuard { tracker: self };
+    if let Some(max) = self.limits.max_depth {
+      if depth > max {
+        return Err(InterpretError::new_limit(
+          Limit::Depth,
+          &format!("Call depth limit of {} exceeded.", max),
+        ));
+      }
+    }
+    if let (Some(max), Some(start)) = (self.limits.max_stack, STACK_START.get()) {
+      if start.abs_diff(stack_position()) > max {
+        return Err(InterpretError::new_limit(
+          Limit::Stack,
+          &format!("Stack limit of {} bytes exceeded.", max),
+        ));
+      }
+    }
+    Ok(guard)
+  }
+
+  // Counts the size of a newly built string or list, checking the allocation limit
+  pub fn alloc(&self, size: usize) -> Result<(), InterpretError> {
+    if let Some(max) = self.limits.max_alloc {
+      let total = self
+        .alloc
+        .fetch_add(size, Ordering::Relaxed)
+        .saturating_add(size);
+      if total > max {
+        return Err(InterpretError::new_limit(
+          Limit::Allocation,
+          &format!("Allocation limit of {} exceeded.", max),
+        ));
+      }
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+// A `// line` or `/* block */` comment in the code, which the lexer skips
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comment {
+  pub start: usize,
+  pub end: usize,
+  pub text: String,
+}
+
+// Finds the comments in some code, skipping any comment markers inside strings
+// Interpolation strings are followed into their expressions, which can contain comments and other
+//  strings
+pub fn comments(code: &str) -> Vec<Comment> {
+  let bytes = code.as_bytes();
+  let mut res = vec![];
+  // Whether each level is inside an interpolation string rather than code, the first is always code
+  let mut levels = vec![false];
+  let mut i = 0;
+
+  while i < bytes.len() {
+    let next = bytes.get(i + 1).copied();
+    if *levels.last().unwrap() {
+      match (bytes[i], next) {
+        (b'\\', _) => i += 1,
+        (b'{', _) => levels.push(false),
+        (b'"', Some(b'f')) => {
+          levels.pop();
+          i += 1;
+        }
+        _ => (),
+      }
+      i += 1;
+      continue;
+    }
+
+    match (bytes[i], next) {
+      (b'/', Some(b'/')) => {
+        let end = code[i..].find(['\n', '\r']).map_or(code.len(), |e| i + e);
+        res.push(Comment {
+          start: i,
+          end,
+          text: code[i..end].to_string(),
+        });
+        i = end;
+      }
+      (b'/', Some(b'*')) => {
+        let end = code[i + 2..].find("*/").map_or(code.len(), |e| i + e + 4);
+        res.push(Comment {
+          start: i,
+          end,
+          text: code[i..end].to_string(),
+        });
+        i = end;
+      }
+      (b'f', Some(b'"')) if i == 0 || !is_name_char(bytes[i - 1]) => {
+        levels.push(true);
+        i += 2;
+      }
+      (b'"', _) => i = code[i + 1..].find('"').map_or(code.len(), |e| i + e + 2),
+      (b'}', _) if levels.len() > 1 => {
+        levels.pop();
+        i += 1;
+      }
+      _ => i += 1,
+    }
+  }
+  res
+}
+
+// Finds the doc comment of each function, the `///` comments on the lines just before its `#name`,
+//  with no blank lines between them
+// Each line of the doc has the `///` and the space after it removed
+pub fn doc_comments(
+  code: &str,
+  names: &HashMap<String, (usize, usize)>,
+) -> HashMap<String, String> {
+  let docs: Vec<Comment> = comments(code)
+    .into_iter()
+    .filter(|c| c.text.starts_with("///") && !c.text.starts_with("////"))
+    .filter(|c| {
+      code[..c.start]
+        .rsplit('\n')
+        .next()
+        .unwrap()
+        .trim()
+        .is_empty()
+    })
+    .collect();
+
+  let mut res = HashMap::new();
+  for (name, (start, _)) in names {
+    let mut lines = vec![];
+    let mut end = *start;
+    for c in docs.iter().rev().skip_while(|c| c.start >= *start) {
+      let between = &code[c.end..end];
+      if !between.trim().is_empty() || between.matches('\n').count() > 1 {
+        break;
+      }
+      let line = &c.text[3..];
+      lines.push(line.strip_prefix(' ').unwrap_or(line).trim_end());
+      end = c.start;
+    }
+    if !lines.is_empty() {
+      lines.reverse();
+      res.insert(name.clone(), lines.join("\n"));
+    }
+  }
+  res
+}
+
+// Whether a character can be part of a name, so an `f` after it does not start a string
+fn is_name_char(c: u8) -> bool {
+  c.is_ascii_alphanumeric() || c == b'_' || c == b'-'
+}
@@ -1,3 +1,4 @@
+pub mod comments;
 pub mod string_escapes;
 mod test;
 
@@ -446,10 +446,7 @@ fn test_custom_bin_operators() {
   );
 
   let res_2 = parser.parse(
-    &ParserState {
-      unary_ops: vec![],
-      binary_ops: vec![OperatorChars::QuestionMark],
-    },
+    &ParserState::with_ops(vec![], vec![OperatorChars::QuestionMark]),
     test_str,
   );
   assert!(res_2.is_ok());
@@ -480,10 +477,7 @@ a -> ?a;
   );
 
   let res_2 = parser.parse(
-    &ParserState {
-      unary_ops: vec![OperatorChars::QuestionMark],
-      binary_ops: vec![],
-    },
+    &ParserState::with_ops(vec![OperatorChars::QuestionMark], vec![]),
     test_str,
   );
   assert!(res_2.is_ok());
@@ -504,10 +498,10 @@ a -> a & ?b * ^d;
 
   let parser = language_definition::ProgramParser::new();
   let res = parser.parse(
-    &ParserState {
-      unary_ops: vec![OperatorChars::QuestionMark, OperatorChars::Carat],
-      binary_ops: vec![OperatorChars::And],
-    },
+    &ParserState::with_ops(
+      vec![OperatorChars::QuestionMark, OperatorChars::Carat],
+      vec![OperatorChars::And],
+    ),
     test_str,
   );
   assert!(dbg!(&res).is_ok());
@@ -516,3 +510,24 @@ a -> a & ?b * ^d;
     "Ok(#main a -> (CustomOp(a & CustomOp(? b)) * CustomOp(^ d)))"
   )
 }
+
+// Test that syntax errors in patterns are recovered from and recorded in the parser state
+#[test]
+fn test_error_recovery() {
+  use crate::parser::language_definition;
+  use crate::ParserState;
+  use lalrpop_util::ParseError;
+
+  let state = ParserState::new();
+  let res =
+    language_definition::ProgramParser::new().parse(&state, "#main x -> + ;\n#other x -> x;");
+  assert!(res.is_ok());
+  assert!(matches!(
+    state.errors.borrow().as_slice(),
+    [ParseError::UnrecognizedToken {
+      token: (11, _, 12),
+      ..
+    }]
+  ));
+  assert!(res.unwrap().env.contains_key("other"));
+}
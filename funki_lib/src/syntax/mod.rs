@@ -0,0 +1,447 @@
+use std::fmt::{Display, Formatter};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::ast;
+use crate::errors::Span;
+
+mod test;
+
+/// The tree of a parsed script, made by `Script::syntax`, with the functions in the order they
+/// are written.
+/// A function defined more than once only keeps the definition that is used.
+/// With the `serde` feature, the tree and its nodes can be serialized, for example to JSON.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Program {
+  functions: Vec<Function>,
+}
+
+impl Program {
+  /// The functions in the order they are written
+  pub fn functions(&self) -> &[Function] {
+    &self.functions
+  }
+
+  /// The function with a name, if the script defines it
+  pub fn function(&self, name: &str) -> Option<&Function> {
+    self.functions.iter().find(|f| f.name == name)
+  }
+}
+
+/// A function, made of patterns that are tried in order.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Function {
+  name: String,
+  name_span: Span,
+  span: Span,
+  doc: Option<String>,
+  patterns: Vec<Pattern>,
+}
+
+impl Function {
+  /// The name of the function, without the `#`
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// Where `#name` is in the code
+  pub fn name_span(&self) -> Span {
+    self.name_span
+  }
+
+  /// Where the whole function is in the code, from `#name` to the end of its last pattern
+  pub fn span(&self) -> Span {
+    self.span
+  }
+
+  /// The doc comment of the function, without the `///`s
+  pub fn doc(&self) -> Option<&str> {
+    self.doc.as_deref()
+  }
+
+  /// The patterns of the function, in the order they are tried
+  pub fn patterns(&self) -> &[Pattern] {
+    &self.patterns
+  }
+}
+
+/// A pattern of a function or a lambda, `args -> result | guard`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Pattern {
+  args: Option<Expr>,
+  guards: Vec<Expr>,
+  result: Expr,
+  span: Span,
+}
+
+impl Pattern {
+  /// What the arguments are matched against, `None` for patterns written without `->`
+  /// More than one argument is a tuple.
+  pub fn args(&self) -> Option<&Expr> {
+    self.args.as_ref()
+  }
+
+  /// The guards that must all be true for the pattern to match, in the order they are written
+  pub fn guards(&self) -> &[Expr] {
+    &self.guards
+  }
+
+  /// The expression the pattern evaluates to
+  pub fn result(&self) -> &Expr {
+    &self.result
+  }
+
+  /// Where the pattern is in the code, from its arguments to its last guard
+  pub fn span(&self) -> Span {
+    self.span
+  }
+}
+
+/// An expression and where it is in the code.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Expr {
+  kind: ExprKind,
+  span: Span,
+}
+
+impl Expr {
+  /// What kind of expression it is, with its parts
+  pub fn kind(&self) -> &ExprKind {
+    &self.kind
+  }
+
+  /// Where the expression is in the code
+  pub fn span(&self) -> Span {
+    self.span
+  }
+
+  /// The expressions directly within this one, in the order they are written
+  /// The expressions of a lambda are its arguments, guards and result.
+  pub fn children(&self) -> Vec<&Expr> {
+    use ExprKind::*;
+    match &self.kind {
+      Number(_) | Var(_) | Str(_) => vec![],
+      Binary { left, right, .. } | CustomBinary { left, right, .. } => vec![left, right],
+      Call { function, args } => vec![function, args],
+      Unary { expr, .. } | CustomUnary { expr, .. } => vec![expr],
+      Tuple(items) => items.iter().collect(),
+      Interpolation(parts) => parts
+        .iter()
+        .filter_map(|p| match p {
+          Part::Expr(e) => Some(e),
+          Part::Text(_) => None,
+        })
+        .collect(),
+      Lambda(p) => {
+        let mut res: Vec<&Expr> = p.args.iter().chain(&p.guards).collect();
+        res.push(&p.result);
+        res
+      }
+    }
+  }
+}
+
+/// The kinds of expression.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum ExprKind {
+  /// An integer
+  Number(i32),
+  /// A variable, or the name of a function or builtin
+  /// `_` in a pattern's arguments is also a variable.
+  Var(String),
+  /// A string without interpolation
+  Str(String),
+  /// An `f"..."f` string
+  Interpolation(Vec<Part>),
+  /// A tuple, or the arguments of a call
+  Tuple(Vec<Expr>),
+  /// A builtin binary operator
+  Binary {
+    left: Box<Expr>,
+    op: BinaryOp,
+    right: Box<Expr>,
+  },
+  /// A builtin unary operator
+  Unary { op: UnaryOp, expr: Box<Expr> },
+  /// A binary operator added by the host
+  CustomBinary {
+    left: Box<Expr>,
+    op: String,
+    right: Box<Expr>,
+  },
+  /// A unary operator added by the host
+  CustomUnary { op: String, expr: Box<Expr> },
+  /// A call, its arguments are always a tuple
+  Call {
+    function: Box<Expr>,
+    args: Box<Expr>,
+  },
+  /// A lambda, `|args => result|`
+  Lambda(Box<Pattern>),
+}
+
+/// A part of an `f"..."f` string.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum Part {
+  /// Text that is kept as it is
+  Text(String),
+  /// An expression in `{}`s
+  Expr(Expr),
+}
+
+/// The builtin binary operators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum BinaryOp {
+  Mul,
+  Div,
+  Mod,
+  Add,
+  Sub,
+  Eq,
+  Neq,
+  Leq,
+  Lt,
+  Geq,
+  Gt,
+  And,
+  Or,
+}
+
+impl Display for BinaryOp {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+    use BinaryOp::*;
+    let op = match self {
+      Mul => "*",
+      Div => "/",
+      Mod => "%",
+      Add => "+",
+      Sub => "-",
+      Eq => "==",
+      Neq => "!=",
+      Leq => "<=",
+      Lt => "<",
+      Geq => ">=",
+      Gt => ">",
+      And => "&&",
+      Or => "||",
+    };
+    write!(fmt, "{}", op)
+  }
+}
+
+/// The builtin unary operators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum UnaryOp {
+  Not,
+  Neg,
+}
+
+impl Display for UnaryOp {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      UnaryOp::Not => write!(fmt, "!"),
+      UnaryOp::Neg => write!(fmt, "-"),
+    }
+  }
+}
+
+/// Walks through a script's tree, calling a method for each node.
+/// Each method visits the node's children by default, so only the nodes of interest need a
+/// method. A method that is overridden can call the matching `walk_` function to keep visiting
+/// the children.
+///
+/// ## Example
+/// ```
+/// use funki_lang::{Script, BlankCustom};
+/// use funki_lang::syntax::{walk_expr, Expr, ExprKind, Visitor};
+///
+/// // Collects the variables, in the order they are used
+/// struct Vars(Vec<String>);
+///
+/// impl Visitor for Vars {
+///   fn visit_expr(&mut self, expr: &Expr) {
+///     if let ExprKind::Var(v) = expr.kind() {
+///       self.0.push(v.clone());
+///     }
+///     walk_expr(self, expr);
+///   }
+/// }
+///
+/// let x = Script::<BlankCustom>::from_text("#main (a, b) -> a + b * a;").unwrap();
+/// let mut vars = Vars(vec![]);
+/// vars.visit_program(&x.syntax());
+/// assert_eq!(vars.0, vec!["a", "b", "a", "b", "a"]);
+/// ```
+pub trait Visitor {
+  fn visit_program(&mut self, program: &Program) {
+    walk_program(self, program);
+  }
+
+  fn visit_function(&mut self, function: &Function) {
+    walk_function(self, function);
+  }
+
+  fn visit_pattern(&mut self, pattern: &Pattern) {
+    walk_pattern(self, pattern);
+  }
+
+  fn visit_expr(&mut self, expr: &Expr) {
+    walk_expr(self, expr);
+  }
+}
+
+/// Visits each function of a program
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+  for f in &program.functions {
+    visitor.visit_function(f);
+  }
+}
+
+/// Visits each pattern of a function
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, function: &Function) {
+  for p in &function.patterns {
+    visitor.visit_pattern(p);
+  }
+}
+
+/// Visits the arguments, guards and result of a pattern, in that order
+pub fn walk_pattern<V: Visitor + ?Sized>(visitor: &mut V, pattern: &Pattern) {
+  if let Some(a) = &pattern.args {
+    visitor.visit_expr(a);
+  }
+  for g in &pattern.guards {
+    visitor.visit_expr(g);
+  }
+  visitor.visit_expr(&pattern.result);
+}
+
+/// Visits the expressions directly within an expression, a lambda's pattern is visited as a
+/// pattern
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+  match &expr.kind {
+    ExprKind::Lambda(p) => visitor.visit_pattern(p),
+    _ => {
+      for e in expr.children() {
+        visitor.visit_expr(e);
+      }
+    }
+  }
+}
+
+// Builds the public tree of a program, with spans found in its code
+pub(crate) fn from_program(program: &ast::Program, code: &str) -> Program {
+  let mut functions: Vec<Function> = program
+    .env
+    .iter()
+    .filter_map(|(name, patterns)| {
+      let (start, name_end) = *program.names.get(name)?;
+      let end = patterns.last().map_or(name_end, |p| p.span().1);
+      Some(Function {
+        name: name.clone(),
+        name_span: Span::new(code, start, name_end),
+        span: Span::new(code, start, end),
+        doc: program.docs.get(name).cloned(),
+        patterns: patterns.iter().map(|p| from_pattern(p, code)).collect(),
+      })
+    })
+    .collect();
+  functions.sort_by_key(|f| f.span.start);
+  Program { functions }
+}
+
+// Converts a pattern, a lambda's arguments are always parsed as a tuple so one is unwrapped
+fn from_pattern(pattern: &ast::Pattern, code: &str) -> Pattern {
+  let (start, end) = pattern.span();
+  Pattern {
+    args: match pattern.has_args() {
+      true => Some(from_expr(&pattern.start.clone().unwrap_tuple(), code)),
+      false => None,
+    },
+    guards: pattern
+      .guards
+      .iter()
+      .map(|g| from_expr(&g.expr, code))
+      .collect(),
+    result: from_expr(&pattern.result, code),
+    span: Span::new(code, start, end),
+  }
+}
+
+fn from_expr(expr: &ast::Expr, code: &str) -> Expr {
+  use ast::ExprInner;
+  let boxed = |e: &ast::Expr| Box::new(from_expr(e, code));
+  let kind = match &expr.val {
+    ExprInner::Number(n) => ExprKind::Number(*n),
+    ExprInner::Var(v) => ExprKind::Var(v.clone()),
+    ExprInner::Str(s) => ExprKind::Str(s.clone()),
+    ExprInner::InterpolationString(parts) => ExprKind::Interpolation(
+      parts
+        .iter()
+        .map(|p| match p {
+          ast::InterpolationPart::String(s) => Part::Text(s.clone()),
+          ast::InterpolationPart::Expr(e) => Part::Expr(from_expr(e, code)),
+        })
+        .collect(),
+    ),
+    ExprInner::Tuple(items) => ExprKind::Tuple(items.iter().map(|e| from_expr(e, code)).collect()),
+    ExprInner::Op(l, op, r) => ExprKind::Binary {
+      left: boxed(l),
+      op: binary_op(*op),
+      right: boxed(r),
+    },
+    ExprInner::Unary(op, e) => ExprKind::Unary {
+      op: match op {
+        ast::UnaryOp::Not => UnaryOp::Not,
+        ast::UnaryOp::Neg => UnaryOp::Neg,
+      },
+      expr: boxed(e),
+    },
+    ExprInner::CustomBinOp(l, op, r) => ExprKind::CustomBinary {
+      left: boxed(l),
+      op: op.to_string(),
+      right: boxed(r),
+    },
+    ExprInner::CustomUnaryOp(op, e) => ExprKind::CustomUnary {
+      op: op.to_string(),
+      expr: boxed(e),
+    },
+    ExprInner::FuncCall(f, args) => ExprKind::Call {
+      function: boxed(f),
+      args: boxed(args),
+    },
+    ExprInner::Lambda(p) => ExprKind::Lambda(Box::new(from_pattern(p, code))),
+  };
+  Expr {
+    kind,
+    span: Span::new(code, expr.start, expr.end),
+  }
+}
+
+fn binary_op(op: ast::Opcode) -> BinaryOp {
+  use ast::Opcode;
+  match op {
+    Opcode::Mul => BinaryOp::Mul,
+    Opcode::Div => BinaryOp::Div,
+    Opcode::Mod => BinaryOp::Mod,
+    Opcode::Add => BinaryOp::Add,
+    Opcode::Sub => BinaryOp::Sub,
+    Opcode::Eq => BinaryOp::Eq,
+    Opcode::Neq => BinaryOp::Neq,
+    Opcode::Leq => BinaryOp::Leq,
+    Opcode::Lt => BinaryOp::Lt,
+    Opcode::Geq => BinaryOp::Geq,
+    Opcode::Gt => BinaryOp::Gt,
+    Opcode::And => BinaryOp::And,
+    Opcode::Or => BinaryOp::Or,
+  }
+}
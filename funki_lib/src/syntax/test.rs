@@ -0,0 +1,90 @@
+#[cfg(test)]
+use crate::syntax::{walk_pattern, BinaryOp, ExprKind, Part, Pattern, Visitor};
+#[cfg(test)]
+use crate::{BlankCustom, Script};
+
+// Tests functions are in the order they are written, with their spans and docs
+#[test]
+fn test_syntax_functions() {
+  let code = "/// Signs\n#sign n -> 1 | n > 0;\n  n -> 0;\n#main sign(2);";
+  let script = Script::<BlankCustom>::from_text(code).unwrap();
+  let tree = script.syntax();
+  let names: Vec<&str> = tree.functions().iter().map(|f| f.name()).collect();
+  assert_eq!(names, vec!["sign", "main"]);
+
+  let sign = tree.function("sign").unwrap();
+  assert_eq!(sign.doc(), Some("Signs"));
+  assert_eq!(&code[sign.name_span().range()], "#sign");
+  assert_eq!(
+    &code[sign.span().range()],
+    "#sign n -> 1 | n > 0;\n  n -> 0"
+  );
+  assert_eq!(sign.span().start_line, 2);
+  let first = &sign.patterns()[0];
+  assert_eq!(&code[first.span().range()], "n -> 1 | n > 0");
+  assert_eq!(
+    first.guards()[0].kind(),
+    &ExprKind::Binary {
+      left: Box::new(first.guards()[0].children()[0].clone()),
+      op: BinaryOp::Gt,
+      right: Box::new(first.guards()[0].children()[1].clone()),
+    }
+  );
+
+  let main = &tree.function("main").unwrap().patterns()[0];
+  assert_eq!(main.args(), None);
+  assert!(matches!(main.result().kind(), ExprKind::Call { .. }));
+  assert_eq!(&code[main.result().span().range()], "sign(2)");
+  assert_eq!(tree.function("missing"), None);
+}
+
+// Tests lambdas, strings and tuples are converted with the spans of their parts
+#[test]
+fn test_syntax_exprs() {
+  let code = "#main (xs, s) -> (map(xs, |x => -x|), f\"a{s}b\"f);";
+  let script = Script::<BlankCustom>::from_text(code).unwrap();
+  let tree = script.syntax();
+  let pattern = &tree.functions()[0].patterns()[0];
+  let items = match pattern.result().kind() {
+    ExprKind::Tuple(items) => items,
+    k => panic!("Expected a tuple, found {:?}", k),
+  };
+
+  let lambda = items[0].children()[1].children()[1];
+  let lambda = match lambda.kind() {
+    ExprKind::Lambda(p) => p,
+    k => panic!("Expected a lambda, found {:?}", k),
+  };
+  assert_eq!(
+    lambda.args().unwrap().kind(),
+    &ExprKind::Var("x".to_string())
+  );
+  assert_eq!(&code[lambda.result().span().range()], "-x");
+
+  match items[1].kind() {
+    ExprKind::Interpolation(parts) => {
+      assert_eq!(parts[0], Part::Text("a".to_string()));
+      assert!(matches!(&parts[1], Part::Expr(e) if &code[e.span().range()] == "s"));
+    }
+    k => panic!("Expected an f-string, found {:?}", k),
+  }
+}
+
+// Tests a visitor visits every pattern, including those of lambdas
+#[test]
+fn test_visitor() {
+  struct Patterns(Vec<usize>);
+
+  impl Visitor for Patterns {
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+      self.0.push(pattern.span().start);
+      walk_pattern(self, pattern);
+    }
+  }
+
+  let code = "#f 0 -> 1;\n  n -> fold(list(n), 0, |(a, b) => a + b|);\n#main f(1);";
+  let script = Script::<BlankCustom>::from_text(code).unwrap();
+  let mut patterns = Patterns(vec![]);
+  patterns.visit_program(&script.syntax());
+  assert_eq!(patterns.0, vec![3, 13, 36, 61]);
+}
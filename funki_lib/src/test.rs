@@ -87,3 +87,1379 @@ fn test_binary_ops() {
 
   assert_eq!(format!("{:?}", res), "Int(8)");
 }
+
+// Tests custom operators are used by functions called without an argument
+#[test]
+fn test_call_without_arg() {
+  use crate::*;
+  let mut lang = Language::new();
+  lang.add_bin_op(
+    OperatorChars::Carat,
+    CustomBinOp {
+      function: |l: ReturnVal<BlankCustom>, r| {
+        if let (ReturnVal::Int(l), ReturnVal::Int(r)) = (l, r) {
+          Ok(Argument::Int(l.pow(r as u32)))
+        } else {
+          panic!()
+        }
+      },
+    },
+  );
+
+  let parsed = lang.parse("#main 2 ^ 3;".to_string()).unwrap();
+  let res = parsed.function("main").unwrap().call();
+  assert_eq!(format!("{:?}", res.unwrap()), "Int(8)");
+}
+
+// Tests globals set on the script and for a single call
+#[test]
+fn test_globals() {
+  use crate::*;
+  let mut lang = Script::<BlankCustom>::from_text(
+    "#main x -> f\"{greeting} {x} from {site}\"f;
+     #shadow site -> site;
+     #helper helper_site();
+     #helper_site site;",
+  )
+  .unwrap();
+  lang
+    .set_global("site", Argument::String("funki".to_string()))
+    .unwrap()
+    .set_global("greeting", Argument::String("Hello".to_string()))
+    .unwrap();
+
+  let res = lang
+    .function("main")
+    .unwrap()
+    .arg(Argument::String("Alfie".to_string()))
+    .call()
+    .unwrap();
+  assert_eq!(res.to_string(), "Hello Alfie from funki");
+
+  // Per call globals shadow script globals
+  let res = lang
+    .function("main")
+    .unwrap()
+    .arg(Argument::String("Alfie".to_string()))
+    .with_global("greeting", Argument::String("Hi".to_string()))
+    .call()
+    .unwrap();
+  assert_eq!(res.to_string(), "Hi Alfie from funki");
+
+  // Pattern variables shadow globals
+  let res = lang
+    .function("shadow")
+    .unwrap()
+    .arg(Argument::Int(3))
+    .call()
+    .unwrap();
+  assert_eq!(format!("{:?}", res), "Int(3)");
+
+  // Globals are visible from functions called by other functions
+  let res = lang.function("helper").unwrap().call().unwrap();
+  assert_eq!(format!("{:?}", res), "String(funki)");
+}
+
+// Tests globals cannot share a name with a function
+#[test]
+fn test_global_collisions() {
+  use crate::*;
+  let mut lang = Script::<BlankCustom>::from_text("#main x -> x; #site 1;").unwrap();
+  assert_eq!(
+    format!(
+      "{:?}",
+      lang.set_global("site", Argument::Int(1)).err().unwrap()
+    ),
+    "Error: Global \"site\" has the same name as a function."
+  );
+
+  assert_eq!(
+    format!(
+      "{:?}",
+      lang
+        .function("main")
+        .unwrap()
+        .with_global("main", Argument::Int(1))
+        .call()
+        .err()
+        .unwrap()
+    ),
+    "Error: Global \"main\" has the same name as a function."
+  );
+}
+
+// Tests functions, lambdas and builtins can be returned and called from the host
+#[test]
+fn test_callables() {
+  use crate::*;
+  let callable = {
+    let lang = Script::<BlankCustom>::from_text(
+      "#main n -> |x => add(x, n)|;
+       #add (a, b) -> a + b;
+       #funcs (add, |x => x|, len);",
+    )
+    .unwrap();
+    let res = lang
+      .function("main")
+      .unwrap()
+      .arg(Argument::Int(3))
+      .call()
+      .unwrap();
+
+    let funcs = lang.function("funcs").unwrap().call().unwrap();
+    if let ReturnVal::Tuple(v) = funcs {
+      assert_eq!(v.len(), 3);
+      if let ReturnVal::Callable(c) = &v[0] {
+        let res = c
+          .call(Argument::Tuple(vec![Argument::Int(1), Argument::Int(2)]))
+          .unwrap();
+        assert_eq!(format!("{:?}", res), "Int(3)");
+      } else {
+        panic!("Function was not returned as a callable");
+      }
+      if let ReturnVal::Callable(c) = &v[2] {
+        let res = c
+          .call(Argument::List(vec![Argument::Int(1), Argument::Int(2)]))
+          .unwrap();
+        assert_eq!(format!("{:?}", res), "Int(2)");
+      } else {
+        panic!("Builtin was not returned as a callable");
+      }
+    } else {
+      panic!("Wrong type returned");
+    }
+
+    res
+  };
+
+  // The script and function have been dropped, but the callable can still be called repeatedly
+  if let ReturnVal::Callable(c) = callable {
+    for i in 0..3 {
+      let res = c.call(Argument::Int(i)).unwrap();
+      assert_eq!(format!("{:?}", res), format!("Int({})", i + 3));
+    }
+    assert!(c.call(Argument::List(vec![])).is_err());
+  } else {
+    panic!("Lambda was not returned as a callable");
+  }
+}
+
+// Tests scripts and their results can be shared between threads
+#[test]
+fn test_send_sync() {
+  use crate::*;
+  fn assert_send_sync<T: Send + Sync>() {}
+  assert_send_sync::<Language<BlankCustom>>();
+  assert_send_sync::<Script<BlankCustom>>();
+  assert_send_sync::<LangFunc<BlankCustom>>();
+  assert_send_sync::<ReturnVal<BlankCustom>>();
+  assert_send_sync::<Callable<BlankCustom>>();
+  assert_send_sync::<LanguageErr>();
+}
+
+// Tests the same script can be rendered from many threads in parallel
+#[test]
+fn test_parallel_render() {
+  use crate::*;
+  let lang = Script::<BlankCustom>::from_text(
+    "#square n -> n * n;
+     #main (name, i) -> f\"{name} is {square(i)} {map(list(1, 2), |x => x + i|)}\"f;
+     #adder n -> |x => x + n|;",
+  )
+  .unwrap();
+
+  std::thread::scope(|s| {
+    let handles = (0..8)
+      .map(|t| {
+        let lang = &lang;
+        s.spawn(move || {
+          for i in 0..50 {
+            let res = lang
+              .function("main")
+              .unwrap()
+              .arg(Argument::Tuple(vec![
+                Argument::String(format!("t{}", t)),
+                Argument::Int(i),
+              ]))
+              .call()
+              .unwrap();
+            assert_eq!(
+              res.to_string(),
+              format!("t{} is {} [{}, {}]", t, i * i, i + 1, i + 2)
+            );
+          }
+          lang
+            .function("adder")
+            .unwrap()
+            .arg(Argument::Int(t))
+            .call()
+            .unwrap()
+        })
+      })
+      .collect::<Vec<_>>();
+
+    // Callables created on one thread can be called from another
+    for (t, h) in handles.into_iter().enumerate() {
+      if let ReturnVal::Callable(c) = h.join().unwrap() {
+        let res = std::thread::spawn(move || c.call(Argument::Int(1)).unwrap())
+          .join()
+          .unwrap();
+        assert_eq!(format!("{:?}", res), format!("Int({})", t + 1));
+      } else {
+        panic!("Lambda was not returned as a callable");
+      }
+    }
+  });
+}
+
+// Tests each limit stops evaluation with its own error
+#[test]
+fn test_limits() {
+  use crate::limits::{Limit, Limits};
+  use crate::*;
+  use std::time::Duration;
+
+  let mut lang = Language::<BlankCustom>::new();
+  lang.set_limits(Limits {
+    max_depth: Some(50),
+    ..Default::default()
+  });
+  let script = lang
+    .parse(
+      "#main x -> main(x);
+       #count n -> count(n + 1);
+       #repeat n -> \"abc\" * n;
+       #sum xs -> fold(xs, 0, |(a, b) => a + b|);"
+        .to_string(),
+    )
+    .unwrap();
+
+  match script.function("main").unwrap().call() {
+    Err(e) if e.kind() == ErrorKind::LimitExceeded(Limit::Depth) => assert_eq!(
+      format!("{:?}", e),
+      "Error: \"Call depth limit of 50 exceeded.\"\nAt lines: 1:11 - 1:18\nCode: `main(x)`"
+    ),
+    r => panic!("Expected depth limit, got {:?}", r.map(|_| ())),
+  }
+
+  let res = script
+    .function("count")
+    .unwrap()
+    .arg(Argument::Int(0))
+    .limits(Limits {
+      max_steps: Some(100),
+      ..Default::default()
+    })
+    .call();
+  assert_eq!(
+    res.unwrap_err().kind(),
+    ErrorKind::LimitExceeded(Limit::Steps)
+  );
+
+  let res = script
+    .function("repeat")
+    .unwrap()
+    .arg(Argument::Int(1000))
+    .limits(Limits {
+      max_alloc: Some(1000),
+      ..Default::default()
+    })
+    .call();
+  assert_eq!(
+    res.unwrap_err().kind(),
+    ErrorKind::LimitExceeded(Limit::Allocation)
+  );
+
+  // The same call is fine with a higher limit
+  let res = script
+    .function("repeat")
+    .unwrap()
+    .arg(Argument::Int(100))
+    .limits(Limits {
+      max_alloc: Some(1000),
+      ..Default::default()
+    })
+    .call();
+  assert!(res.is_ok());
+
+  let res = script
+    .function("sum")
+    .unwrap()
+    .arg(Argument::List((0..1_000_000).map(Argument::Int).collect()))
+    .limits(Limits {
+      timeout: Some(Duration::from_millis(10)),
+      ..Default::default()
+    })
+    .call();
+  assert_eq!(
+    res.unwrap_err().kind(),
+    ErrorKind::LimitExceeded(Limit::Timeout)
+  );
+}
+
+// Tests the default limits stop a script that recurses forever, but not one that recurses a little
+#[test]
+fn test_default_limits() {
+  use crate::limits::{Limit, Limits, DEFAULT_MAX_DEPTH};
+  use crate::*;
+  let script = Script::<BlankCustom>::from_text(
+    "#main x -> main(x);\n#count 0 -> 0;\n  n -> count(n - 1) + 1;",
+  )
+  .unwrap();
+  let call = |name: &str, n: i32, limits: Limits| {
+    script
+      .function(name)
+      .unwrap()
+      .arg(Argument::Int(n))
+      .limits(limits)
+      .call()
+  };
+  assert_eq!(Limits::default().max_stack, None);
+  assert!(matches!(
+    call("count", 40, Limits::default()),
+    Ok(ReturnVal::Int(40))
+  ));
+
+  // Unoptimised builds use more stack for each call than the 2 MiB a test thread has
+  std::thread::scope(|s| {
+    std::thread::Builder::new()
+      .stack_size(64 << 20)
+      .spawn_scoped(s, || {
+        for name in ["main", "count"] {
+          let err = call(name, 1_000, Limits::default()).unwrap_err();
+          assert_eq!(err.kind(), ErrorKind::LimitExceeded(Limit::Depth));
+          assert_eq!(err.stack().len(), DEFAULT_MAX_DEPTH);
+        }
+      })
+      .unwrap();
+  });
+
+  // The stack limit stops deep recursion on a thread with a small stack
+  let limits = Limits {
+    max_depth: None,
+    max_stack: Some(100_000),
+    ..Default::default()
+  };
+  let err = call("main", 1, limits).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::LimitExceeded(Limit::Stack));
+}
+
+// Tests arithmetic that can't give an int is an error rather than a panic
+#[test]
+fn test_arithmetic_errors() {
+  use crate::*;
+  let script = Script::<BlankCustom>::from_text(
+    "#div (a, b) -> a / b;
+     #rem (a, b) -> a % b;
+     #add (a, b) -> a + b;
+     #neg a -> -a;
+     #repeat (s, n) -> s * n;",
+  )
+  .unwrap();
+  let call = |name: &str, arg: Argument<BlankCustom>| {
+    let err = script.function(name).unwrap().arg(arg).call().unwrap_err();
+    (err.kind(), err.message().to_string())
+  };
+  let ints = |a, b| Argument::Tuple(vec![Argument::Int(a), Argument::Int(b)]);
+
+  assert_eq!(
+    call("div", ints(1, 0)),
+    (
+      ErrorKind::RuntimeError,
+      "Division by zero, 1 / 0.".to_string()
+    )
+  );
+  assert_eq!(
+    call("rem", ints(1, 0)),
+    (
+      ErrorKind::RuntimeError,
+      "Division by zero, 1 % 0.".to_string()
+    )
+  );
+  assert_eq!(
+    call("div", ints(i32::MIN, -1)).1,
+    "Integer overflow, -2147483648 / -1."
+  );
+  assert_eq!(
+    call("add", ints(i32::MAX, 1)).1,
+    "Integer overflow, 2147483647 + 1."
+  );
+  assert_eq!(
+    call("neg", Argument::Int(i32::MIN)).1,
+    "Integer overflow, -(-2147483648)."
+  );
+  assert_eq!(
+    call(
+      "repeat",
+      Argument::Tuple(vec![Argument::String("a".to_string()), Argument::Int(-1)])
+    ),
+    (
+      ErrorKind::RuntimeError,
+      "Can't repeat a string a negative number of times, \"a\" * -1.".to_string()
+    )
+  );
+}
+
+// Tests a running call can be cancelled from another thread
+#[test]
+fn test_cancellation() {
+  use crate::*;
+  use std::sync::atomic::{AtomicBool, Ordering};
+  use std::sync::Arc;
+  use std::time::Duration;
+
+  let script = Script::<BlankCustom>::from_text(
+    "#main xs -> fold(xs, 0, |(a, b) => a + fold(xs, 0, |(c, d) => c + d|)|);",
+  )
+  .unwrap();
+
+  let token = Arc::new(AtomicBool::new(false));
+  let canceller = {
+    let token = token.clone();
+    std::thread::spawn(move || {
+      std::thread::sleep(Duration::from_millis(20));
+      token.store(true, Ordering::Relaxed);
+    })
+  };
+
+  let res = script
+    .function("main")
+    .unwrap()
+    .arg(Argument::List((0..10000).map(Argument::Int).collect()))
+    .cancel_token(token)
+    .call();
+  canceller.join().unwrap();
+
+  let err = res.unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::Cancelled);
+  assert_eq!(format!("{:?}", err), "Error: Call was cancelled.");
+}
+
+// Tests a long running function returned to the host can be cancelled
+#[test]
+fn test_cancel_callable() {
+  use crate::*;
+  use std::sync::atomic::{AtomicBool, Ordering};
+  use std::sync::Arc;
+  use std::time::Duration;
+
+  let script = Script::<BlankCustom>::from_text(
+    "#main n -> |xs => fold(xs, 0, |(a, b) => a + fold(xs, 0, |(c, d) => c + d|)|)|;",
+  )
+  .unwrap();
+  let token = Arc::new(AtomicBool::new(false));
+  let callable = match script
+    .function("main")
+    .unwrap()
+    .arg(Argument::Int(1))
+    .call()
+  {
+    Ok(ReturnVal::Callable(c)) => c,
+    r => panic!("Expected a callable, found {:?}", r.err()),
+  };
+  let xs = Argument::List((0..10000).map(Argument::Int).collect());
+
+  let canceller = {
+    let token = token.clone();
+    std::thread::spawn(move || {
+      std::thread::sleep(Duration::from_millis(20));
+      token.store(true, Ordering::Relaxed);
+    })
+  };
+  let res = callable
+    .clone()
+    .cancel_token(token.clone())
+    .call(xs.clone());
+  canceller.join().unwrap();
+  assert_eq!(
+    format!("{:?}", res.err().unwrap()),
+    "Error: Call was cancelled."
+  );
+
+  // Functions returned from a call keep its token
+  let token = Arc::new(AtomicBool::new(false));
+  let callable = match script
+    .function("main")
+    .unwrap()
+    .arg(Argument::Int(1))
+    .cancel_token(token.clone())
+    .call()
+  {
+    Ok(ReturnVal::Callable(c)) => c,
+    r => panic!("Expected a callable, found {:?}", r.err()),
+  };
+  token.store(true, Ordering::Relaxed);
+  assert_eq!(
+    format!("{:?}", callable.call(xs).err().unwrap()),
+    "Error: Call was cancelled."
+  );
+}
+
+// Tests builtins and features can be removed from a language
+#[test]
+fn test_sandbox() {
+  use crate::*;
+  let mut lang = Language::<BlankCustom>::new();
+  lang.remove_builtin("fold");
+  lang.disable_feature(Feature::Lambdas);
+  lang.disable_feature(Feature::Recursion);
+
+  assert_eq!(
+    format!(
+      "{:?}",
+      lang
+        .parse("#main x -> len(x) + fold(x, 0, add);\n#add (a, b) -> a + b;".to_string())
+        .err()
+        .unwrap()
+    ),
+    "Error: \"Builtin \"fold\" is not available.\"\nAt lines: 1:20 - 1:24\nCode: `fold`"
+  );
+
+  // A pattern variable with the same name as a removed builtin is fine
+  assert!(lang.parse("#main fold -> fold + 1;".to_string()).is_ok());
+
+  assert_eq!(
+    format!(
+      "{:?}",
+      lang
+        .parse("#main x -> map(x, |y => y|);".to_string())
+        .err()
+        .unwrap()
+    ),
+    "Error: \"Lambdas are disabled.\"\nAt lines: 1:18 - 1:26\nCode: `|y => y|`"
+  );
+
+  let err = lang
+    .parse("#main x -> other(x);\n#other x -> main(x - 1);".to_string())
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{:?}", err),
+    "Error: \"Recursion is disabled, but \"main\" can call itself through \"other\".\"\nAt lines: 1:11 - 1:16\nCode: `other`\n\n\
+     Error: \"Recursion is disabled, but \"other\" can call itself through \"main\".\"\nAt lines: 2:13 - 2:17\nCode: `main`"
+  );
+
+  // Every violation is reported
+  let err = lang
+    .parse("#main x -> map(main(x), |y => fold(y)|);".to_string())
+    .err()
+    .unwrap();
+  assert_eq!(
+    err.errors().map(|e| e.message()).collect::<Vec<_>>(),
+    vec![
+      "Recursion is disabled, but \"main\" can call itself through \"main\".",
+      "Lambdas are disabled.",
+      "Builtin \"fold\" is not available.",
+    ]
+  );
+
+  // Only calls by name are checked, so a function passed to itself is stopped by the limits
+  let script = lang
+    .parse("#app x -> x(x);\n#main app(app);".to_string())
+    .unwrap();
+  let err = script
+    .function("main")
+    .unwrap()
+    .limits(crate::limits::Limits {
+      max_depth: Some(20),
+      ..Default::default()
+    })
+    .call()
+    .unwrap_err();
+  assert_eq!(
+    err.kind(),
+    ErrorKind::LimitExceeded(crate::limits::Limit::Depth)
+  );
+
+  assert!(lang
+    .parse("#main x -> map(x, other);\n#other x -> x - 1;".to_string())
+    .is_ok());
+}
+
+// Tests custom functions override builtins with the same name
+#[test]
+fn test_override_builtin() {
+  use crate::*;
+  let mut lang = Language::<BlankCustom>::new();
+  lang.add_custom_function(
+    "len".to_string(),
+    CustomBuiltIn {
+      function: |_| Ok(Argument::Int(42)),
+    },
+  );
+  let script = lang.parse("#main len(list(1, 2));".to_string()).unwrap();
+  assert_eq!(
+    format!("{:?}", script.function("main").unwrap().call().unwrap()),
+    "Int(42)"
+  );
+}
+
+// Tests errors have the right kinds and locations
+#[test]
+fn test_error_kinds() {
+  use crate::*;
+
+  let err = Script::<BlankCustom>::from_text("#main x -> x +\n  ;").unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::ParseError);
+  assert_eq!(err.range(), Some(17..18));
+  assert_eq!(err.line_col(), Some((2, 3)));
+  assert_eq!(err.to_string(), "Unrecognised token at line 2, column 3");
+
+  let err = Script::<BlankCustom>::from_text("#main x -> ").unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+  let script = Script::<BlankCustom>::from_text(
+    "#main x -> \"ü\" + (x + y);\n#other 1 -> 2;\n#add x -> x + 1;",
+  )
+  .unwrap();
+  assert_eq!(
+    script.function("missing").err().unwrap().kind(),
+    ErrorKind::UnknownFunction
+  );
+
+  let err = script
+    .function("main")
+    .unwrap()
+    .arg(Argument::Int(1))
+    .call()
+    .unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::UnknownVariable);
+  let span = err.span().unwrap();
+  assert_eq!(span.range(), 23..24);
+  assert_eq!((span.start_line, span.start_col), (1, 23));
+  assert_eq!((span.end_line, span.end_col), (1, 24));
+
+  let err = script
+    .function("add")
+    .unwrap()
+    .arg(Argument::List(vec![]))
+    .call()
+    .unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::TypeMismatch);
+  assert_eq!(
+    err.message(),
+    "Add operator not defined for List([]) + Int(1)."
+  );
+
+  let err = script
+    .function("other")
+    .unwrap()
+    .arg(Argument::Int(2))
+    .call()
+    .unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::NoMatchingPattern);
+
+  let mut lang = Language::<BlankCustom>::new();
+  lang.add_custom_function(
+    "fail".to_string(),
+    CustomBuiltIn {
+      function: |_| Err(Box::new("Host failure.")),
+    },
+  );
+  lang.remove_builtin("len");
+  let err = lang.parse("#main len(1);".to_string()).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::SandboxViolation);
+  let mut script = lang.parse("#main fail(1);".to_string()).unwrap();
+  let err = script.function("main").unwrap().call().unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::HostError);
+  assert_eq!(err.to_string(), "Host failure. at line 1, column 7");
+  assert_eq!(
+    script
+      .set_global("main", Argument::Int(1))
+      .err()
+      .unwrap()
+      .kind(),
+    ErrorKind::InvalidGlobal
+  );
+
+  // Errors can be used with `?` as a boxed error
+  fn run() -> Result<(), Box<dyn std::error::Error>> {
+    Script::<BlankCustom>::from_text("#main ;")?;
+    Ok(())
+  }
+  assert_eq!(
+    run().unwrap_err().to_string(),
+    "Unrecognised token at line 1, column 7"
+  );
+}
+
+// Tests errors are rendered with the code they refer to
+#[test]
+fn test_render_errors() {
+  use crate::*;
+  let script = Script::<BlankCustom>::from_text(
+    "#main t -> \nf\"Hello {t + \n \"hi\"}\"f;\n#other 1 -> 2;\n(2, x) -> x;\n#third other(3);",
+  )
+  .unwrap();
+
+  let err = script
+    .function("main")
+    .unwrap()
+    .arg(Argument::Int(1))
+    .call()
+    .unwrap_err();
+  assert_eq!(
+    err.render(false),
+    "error[type mismatch]: Add operator not defined for Int(1) + String(\"hi\").
+ --> 2:10
+  |
+2 | f\"Hello {t + 
+  |          ^^^^
+3 |  \"hi\"}\"f;
+  | ^^^^^
+  = in `main`, pattern 0, with 1
+"
+  );
+
+  let err = script.function("third").unwrap().call().unwrap_err();
+  assert_eq!(err.labels().len(), 2);
+  assert_eq!(
+    err.render(false),
+    "error[no matching pattern]: Cannot find applicable pattern.
+ --> 6:8
+  |
+4 | #other 1 -> 2;
+  |        - this pattern did not match
+5 | (2, x) -> x;
+  | ------ this pattern did not match
+6 | #third other(3);
+  |        ^^^^^^^^
+  = in `other`, with 3, called at 6:8
+  = in `third`, pattern 0, with ()
+"
+  );
+
+  let rendered = err.render(true);
+  assert!(rendered.starts_with("\u{1b}[1;31merror[no matching pattern]\u{1b}[0m"));
+  assert!(rendered.contains("\u{1b}[1;31m       ^^^^^^^^\u{1b}[0m"));
+
+  assert_eq!(
+    script.function("missing").err().unwrap().render(false),
+    "error[unknown function]: Cannot find function \"missing\".\n"
+  );
+}
+
+// Tests runtime errors record the calls they unwound through
+#[test]
+fn test_error_stack() {
+  use crate::*;
+  let script = Script::<BlankCustom>::from_text(
+    "#main xs -> first(xs);\n#first xs -> map(xs, helper);\n#helper 0 -> 0;\nx -> x + \"a\" + x;",
+  )
+  .unwrap();
+
+  let err = script
+    .function("main")
+    .unwrap()
+    .arg(Argument::List(vec![Argument::Int(0), Argument::Int(1)]))
+    .call()
+    .unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::TypeMismatch);
+
+  let stack = err.stack();
+  assert_eq!(
+    stack
+      .iter()
+      .map(|f| (f.function.as_str(), f.pattern, f.argument.as_str()))
+      .collect::<Vec<_>>(),
+    vec![
+      ("helper", Some(1), "1"),
+      ("map", None, "(list(0, 1), helper)"),
+      ("first", Some(0), "list(0, 1)"),
+      ("main", Some(0), "list(0, 1)"),
+    ]
+  );
+  // The builtin calls the helper, so there is no call site for it
+  assert_eq!(stack[0].call_site, None);
+  assert_eq!(stack[1].call_site.unwrap().range(), 36..51);
+  assert_eq!(stack[2].call_site.unwrap().start_line, 1);
+  assert_eq!(stack[3].call_site, None);
+
+  // Long arguments are cut short
+  let err = script
+    .function("first")
+    .unwrap()
+    .arg(Argument::List((0..100).map(Argument::Int).collect()))
+    .call()
+    .unwrap_err();
+  assert_eq!(
+    err.stack()[2].argument,
+    "list(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 1..."
+  );
+
+  // Lambdas are named as such
+  let err = Script::<BlankCustom>::from_text("#main map(list(1), |(a, b) => a|);")
+    .unwrap()
+    .function("main")
+    .unwrap()
+    .call()
+    .unwrap_err();
+  assert_eq!(err.stack()[0].function, "<lambda>");
+  assert_eq!(err.stack()[0].pattern, None);
+}
+
+// Tests a call that recurses until a limit shows the repeated calls once in the rendered error
+#[test]
+fn test_render_repeated_stack() {
+  use crate::limits::Limits;
+  use crate::*;
+  let err = Script::<BlankCustom>::from_text("#main x -> main(x);")
+    .unwrap()
+    .function("main")
+    .unwrap()
+    .arg(Argument::Int(1))
+    .limits(Limits {
+      max_depth: Some(5),
+      ..Default::default()
+    })
+    .call()
+    .unwrap_err();
+  assert_eq!(err.stack().len(), 5);
+  assert_eq!(
+    err.render(false),
+    "error[call depth limit exceeded]: Call depth limit of 5 exceeded.
+ --> 1:12
+  |
+1 | #main x -> main(x);
+  |            ^^^^^^^
+  = in `main`, pattern 0, with 1, called at 1:12
+  = ... repeated 3 more times
+  = in `main`, pattern 0, with 1
+"
+  );
+}
+
+// Tests every syntax error in the code is reported
+#[test]
+fn test_multiple_parse_errors() {
+  use crate::*;
+  let code = "#main x -> x +;\n#ok x -> x;\n#other x -> ) x;\n#third x -> (x;\n#last -> ;";
+  let err = Script::<BlankCustom>::from_text(code).unwrap_err();
+  assert_eq!(
+    err
+      .errors()
+      .map(|e| (e.kind(), e.line_col().unwrap()))
+      .collect::<Vec<_>>(),
+    vec![
+      (ErrorKind::ParseError, (1, 15)),
+      (ErrorKind::ParseError, (3, 13)),
+      (ErrorKind::ParseError, (4, 15)),
+      (ErrorKind::ParseError, (5, 7)),
+    ]
+  );
+  assert_eq!(
+    err.to_string(),
+    "Unrecognised token at line 1, column 15 (and 3 more errors)"
+  );
+  assert_eq!(
+    format!("{:?}", err).split("\n\n").nth(1).unwrap(),
+    "Error: \"Unrecognised token\"\nAt lines: 3:13 - 3:14\nCode: `)`"
+  );
+
+  // A single error in a function name does not stop the rest of the code being checked
+  let err = Language::<BlankCustom>::new()
+    .parse("#main x -> x;\n# x -> x;\n#other -> ;".to_string())
+    .unwrap_err();
+  assert_eq!(err.errors().count(), 2);
+
+  // The error the parser stops at is included
+  let err = Script::<BlankCustom>::from_text("#main x -> ) x;\n#other x -> x").unwrap_err();
+  assert_eq!(
+    err.errors().map(|e| e.kind()).collect::<Vec<_>>(),
+    vec![ErrorKind::ParseError, ErrorKind::UnexpectedEof]
+  );
+  // The errors share one copy of the code
+  let sources: Vec<_> = err.errors().map(|e| e.source().unwrap().as_ptr()).collect();
+  assert!(sources.iter().all(|s| *s == sources[0]));
+}
+
+// Tests unknown names suggest similar names that are defined
+#[test]
+fn test_suggestions() {
+  use crate::*;
+  let mut lang = Language::<BlankCustom>::new();
+  lang.add_custom_function(
+    "shout".to_string(),
+    CustomBuiltIn {
+      function: |_| Ok(Argument::Int(1)),
+    },
+  );
+  lang.add_bin_op(
+    OperatorChars::And,
+    CustomBinOp {
+      function: |_, _| Ok(Argument::Int(1)),
+    },
+  );
+  lang.add_unary_op(
+    OperatorChars::QuestionMark,
+    CustomUnaryOp {
+      function: |_| Ok(Argument::Int(1)),
+    },
+  );
+  let script = lang
+    .parse(
+      "#main count -> cuont + 1;\n#helper x -> lne(x);\n#loud x -> shuot(x);\n#far x -> zzz;"
+        .to_string(),
+    )
+    .unwrap();
+
+  let message = |f: &str| {
+    script
+      .function(f)
+      .unwrap()
+      .arg(Argument::Int(1))
+      .call()
+      .unwrap_err()
+      .message()
+      .to_string()
+  };
+  assert_eq!(
+    message("main"),
+    "Cannot resolve variable cuont. Did you mean \"count\"?"
+  );
+  assert_eq!(
+    message("helper"),
+    "Cannot resolve variable lne. Did you mean \"len\"?"
+  );
+  assert_eq!(
+    message("loud"),
+    "Cannot resolve variable shuot. Did you mean \"shout\"?"
+  );
+  assert_eq!(message("far"), "Cannot resolve variable zzz.");
+
+  assert_eq!(
+    script.function("hepler").err().unwrap().message(),
+    "Cannot find function \"hepler\". Did you mean \"helper\"?"
+  );
+
+  assert_eq!(
+    lang
+      .parse("#main x -> x ? x;".to_string())
+      .err()
+      .unwrap()
+      .message(),
+    "This binary operator is not defined, `?` is only defined as a unary operator"
+  );
+  assert_eq!(
+    lang
+      .parse("#main x -> x ^ x;".to_string())
+      .err()
+      .unwrap()
+      .message(),
+    "This binary operator is not defined. Did you mean `&`?"
+  );
+}
+
+// Tests the lints found in a script
+#[test]
+fn test_lints() {
+  use crate::*;
+  let mut script = Script::<BlankCustom>::from_text(
+    "#main (x, len) -> x + helpr(x) + site;
+#fib 0 -> 1;
+n -> n;
+_ -> 0 | 1 == 1;
+#count xs -> map(xs, |x => 1|);
+#ok (a, b) -> fold(a, b, |(c, d) => c + d|) | a != list();",
+  )
+  .unwrap();
+  script
+    .set_global("site", Argument::String("funki".to_string()))
+    .unwrap();
+
+  let lints = script.lints();
+  assert_eq!(
+    lints
+      .iter()
+      .map(|l| (l.code(), l.span.start_line, l.span.start_col))
+      .collect::<Vec<_>>(),
+    vec![
+      ("W001", 1, 11),
+      ("W002", 1, 11),
+      ("W005", 1, 23),
+      ("W003", 4, 1),
+      ("W004", 4, 10),
+      ("W001", 5, 23),
+      ("W006", 6, 5),
+    ]
+  );
+  assert_eq!(
+    lints[1].to_string(),
+    "warning[W002]: \"len\" shadows a builtin or global with the same name. at line 1, column 11"
+  );
+}
+
+// Tests patterns that do not match every argument are found, with examples of missing arguments
+#[test]
+fn test_exhaustiveness() {
+  use crate::*;
+  let script = Script::<BlankCustom>::from_text(
+    "#fib 0 -> 1;
+1 -> 1;
+-1 -> 0;
+#greet \"hi\" -> 1;
+\"\" -> 0;
+#pair (0, x) -> x;
+(1, (\"a\", y)) -> y;
+(x, y, z) -> z;
+#all (0, _) -> 1;
+(_, x) -> x;
+#guarded x -> x | x > 0;",
+  )
+  .unwrap();
+
+  let lints = script.lints();
+  let found: Vec<(&str, String)> = lints
+    .iter()
+    .filter(|l| l.kind == LintKind::NonExhaustive || l.kind == LintKind::ArityMismatch)
+    .map(|l| (l.code(), l.message.clone()))
+    .collect();
+  assert_eq!(
+    found,
+    vec![
+      (
+        "W006",
+        "Patterns of \"fib\" do not cover every argument, for example `2`, `3`, `4`.".to_string()
+      ),
+      (
+        "W006",
+        "Patterns of \"greet\" do not cover every argument, for example `\"a\"`, `\"aa\"`, `\"aaa\"`."
+          .to_string()
+      ),
+      (
+        "W006",
+        "Patterns of \"pair\" do not cover every argument, for example `(2, _)`, `(3, _)`, `(4, _)`."
+          .to_string()
+      ),
+      (
+        "W007",
+        "This pattern takes 3 items, but an earlier pattern takes 2.".to_string()
+      ),
+      (
+        "W006",
+        "Patterns of \"guarded\" do not cover every argument, for example `_`.".to_string()
+      ),
+    ]
+  );
+
+  // A tuple of the wrong length does not match a pattern
+  let err = script
+    .function("pair")
+    .unwrap()
+    .arg(Argument::Tuple(vec![
+      Argument::Int(0),
+      Argument::Int(1),
+      Argument::Int(2),
+    ]))
+    .call();
+  assert!(matches!(err, Ok(ReturnVal::Int(2))));
+  let err = script
+    .function("all")
+    .unwrap()
+    .arg(Argument::Tuple(vec![
+      Argument::Int(0),
+      Argument::Int(1),
+      Argument::Int(2),
+    ]))
+    .call()
+    .unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::NoMatchingPattern);
+}
+
+// Tests types are inferred and checked when type checking is enabled
+#[test]
+fn test_type_checking() {
+  use crate::types::Type;
+  use crate::*;
+  let code = "#main x -> len(x) + \"items\";\n#other list(1, \"a\");";
+  let mut lang = Language::<BlankCustom>::new();
+  assert!(lang.parse(code.to_string()).is_ok());
+
+  lang.enable_type_checking();
+  let err = lang.parse(code.to_string()).unwrap_err();
+  assert_eq!(
+    err
+      .errors()
+      .map(|e| (e.kind(), e.message(), e.line_col().unwrap()))
+      .collect::<Vec<_>>(),
+    vec![
+      (
+        ErrorKind::TypeMismatch,
+        "Add operator not defined for Int + String.",
+        (1, 12)
+      ),
+      (
+        ErrorKind::TypeMismatch,
+        "Expected Int, found String.",
+        (2, 16)
+      ),
+    ]
+  );
+
+  let script = lang
+    .parse(
+      "#id x -> x;
+#pair (id(1), id(\"a\"));
+#fib 0 -> 1;
+1 -> 1;
+n -> fib(n - 1) + fib(n - 2);
+#names xs -> map(xs, |(name, age) => name + \" is \" + age|) | len(xs) > 0;
+#apply (f, x) -> f(x);"
+        .to_string(),
+    )
+    .unwrap();
+  let types = script.signatures().unwrap();
+  assert_eq!(
+    types
+      .iter()
+      .map(|(n, t)| format!("{}: {}", n, t))
+      .collect::<Vec<_>>(),
+    vec![
+      "apply: (a -> b, a) -> b",
+      "fib: Int -> Int",
+      "id: a -> a",
+      "names: [(String, a)] -> [String]",
+      "pair: () -> (Int, String)",
+    ]
+  );
+
+  let err = lang.parse("#main 1 < \"a\";".to_string()).unwrap_err();
+  assert_eq!(
+    err.message(),
+    "Comparison of types not supported Int < String."
+  );
+
+  // Custom builtins can be used as any type unless their type is declared
+  lang.add_custom_function(
+    "shout".to_string(),
+    CustomBuiltIn {
+      function: |a| Ok(Argument::String(a.to_string().to_uppercase())),
+    },
+  );
+  assert!(lang.parse("#main shout(1) - 1;".to_string()).is_ok());
+  lang.declare_signature("shout", Type::function(Type::String, Type::String));
+  let err = lang.parse("#main shout(1) - 1;".to_string()).unwrap_err();
+  assert_eq!(err.message(), "Expected String, found Int.");
+  assert_eq!(err.range(), Some(12..13));
+  assert_eq!(
+    lang
+      .parse("#main x -> shout(x);".to_string())
+      .unwrap()
+      .signatures()
+      .unwrap()["main"],
+    Type::function(Type::String, Type::String)
+  );
+}
+
+// Tests typed builtins are only called with arguments matching their parameters
+#[test]
+fn test_typed_builtins() {
+  use crate::external_operators::TypedBuiltIn;
+  use crate::types::Type;
+  use crate::*;
+  let mut lang = Language::<BlankCustom>::new();
+  lang.add_typed_function(
+    "pad".to_string(),
+    TypedBuiltIn {
+      params: vec![
+        ("text".to_string(), Type::String),
+        ("width".to_string(), Type::Int),
+      ],
+      result: Type::String,
+      function: |args| match args.as_slice() {
+        [ReturnVal::String(s), ReturnVal::Int(w)] => match usize::try_from(*w) {
+          Ok(w) => Ok(Argument::String(format!("{:>1$}", s, w))),
+          Err(_) => Err(Box::new("`width` can't be negative.")),
+        },
+        _ => Err(Box::new("Unreachable.")),
+      },
+    },
+  );
+  lang.add_typed_function(
+    "count".to_string(),
+    TypedBuiltIn {
+      params: vec![("items".to_string(), Type::list(Type::Var(0)))],
+      result: Type::Int,
+      function: |args| match args.as_slice() {
+        [ReturnVal::List(l)] => Ok(Argument::Int(l.len() as i32)),
+        _ => Err(Box::new("Unreachable.")),
+      },
+    },
+  );
+  // A second function with the same name is ignored, keeping the first signature
+  lang.add_typed_function(
+    "pad".to_string(),
+    TypedBuiltIn {
+      params: vec![("text".to_string(), Type::String)],
+      result: Type::String,
+      function: |_| Err(Box::new("Replaced.")),
+    },
+  );
+
+  let script = lang
+    .parse(
+      "#main pad(\"ab\", 4);
+#wrong_type pad(\"ab\", \"cd\");
+#too_many pad(\"ab\", 4, 5);
+#count_all count(list(1, 2, 3));
+#count_one count(1);
+#negative pad(\"ab\", 0 - 1);"
+        .to_string(),
+    )
+    .unwrap();
+
+  assert_eq!(
+    format!("{:?}", script.function("main").unwrap().call().unwrap()),
+    "String(  ab)"
+  );
+  assert_eq!(
+    format!(
+      "{:?}",
+      script.function("count_all").unwrap().call().unwrap()
+    ),
+    "Int(3)"
+  );
+
+  let err = script.function("wrong_type").unwrap().call().unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::TypeMismatch);
+  assert_eq!(
+    err.to_string(),
+    "`pad` expects `width` to be Int, but was given \"cd\". at line 2, column 23"
+  );
+  assert_eq!(err.stack()[0].function, "pad");
+
+  let err = script.function("too_many").unwrap().call().unwrap_err();
+  assert_eq!(
+    err.message(),
+    "`pad` takes 2 arguments (text, width), but was given 3."
+  );
+  assert_eq!(err.line_col(), Some((3, 11)));
+
+  let err = script.function("count_one").unwrap().call().unwrap_err();
+  assert_eq!(
+    err.message(),
+    "`count` expects `items` to be [a], but was given 1."
+  );
+
+  let err = script.function("negative").unwrap().call().unwrap_err();
+  assert_eq!(err.message(), "`width` can't be negative.");
+
+  // The parameters are declared for type checking
+  lang.enable_type_checking();
+  let err = lang
+    .parse("#main pad(\"ab\", \"cd\");".to_string())
+    .unwrap_err();
+  assert_eq!(err.message(), "Expected Int, found String.");
+}
+
+// Tests bool arguments
+#[test]
+fn test_bool_arguments() {
+  use crate::*;
+  let script = Script::<BlankCustom>::from_text(
+    "#main (b, x) -> x * 2 | b;
+       (b, x) -> x;",
+  )
+  .unwrap();
+
+  let res = script
+    .function("main")
+    .unwrap()
+    .arg(Argument::Tuple(vec![
+      Argument::Bool(true),
+      Argument::Int(4),
+    ]))
+    .call()
+    .unwrap();
+  assert_eq!(format!("{:?}", res), "Int(8)");
+
+  let res = script
+    .function("main")
+    .unwrap()
+    .arg(Argument::Tuple(vec![
+      Argument::Bool(false),
+      Argument::Int(4),
+    ]))
+    .call()
+    .unwrap();
+  assert_eq!(format!("{:?}", res), "Int(4)");
+}
+
+// Tests names are found and resolved to where they are defined
+#[test]
+fn test_navigation() {
+  use crate::*;
+  let code = "#main (x, n) -> map(x, |x => x + n + scale|);\n#helper 0 -> nope;\n  y -> main(y);";
+  let mut script = Script::<BlankCustom>::from_text(code).unwrap();
+  script.set_global("scale", Argument::Int(2)).unwrap();
+  let at = |s: &str, nth: usize| code.match_indices(s).nth(nth).unwrap().0;
+
+  let symbols = script.symbols();
+  assert_eq!(
+    symbols.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(),
+    vec!["main", "helper"]
+  );
+  assert_eq!(symbols[1].patterns, vec!["0 -> nope", "y -> main(y)"]);
+  assert_eq!(symbols[1].span.start_line, 2);
+  assert_eq!(symbols[1].span.end_line, 3);
+
+  // The lambda's `x` shadows the pattern's
+  let lambda_x = at("x", 2);
+  let r = script.reference_at(at("x", 3)).unwrap();
+  assert_eq!(r.kind, ReferenceKind::Variable);
+  assert_eq!(r.definition.unwrap().start, lambda_x);
+  let r = script.reference_at(at("x", 1)).unwrap();
+  assert_eq!(r.definition.unwrap().start, at("x", 0));
+  let r = script.reference_at(at("n", 2) + 1).unwrap();
+  assert_eq!(
+    (r.name.as_str(), r.definition.unwrap().start),
+    ("n", at("n", 1))
+  );
+
+  let kind = |s: &str| script.reference_at(at(s, 0)).map(|r| r.kind);
+  assert_eq!(kind("scale"), Some(ReferenceKind::Global));
+  assert_eq!(kind("map"), Some(ReferenceKind::Builtin));
+  assert_eq!(kind("nope"), Some(ReferenceKind::Unknown));
+  let r = script.reference_at(at("main(y)", 0)).unwrap();
+  assert_eq!(r.kind, ReferenceKind::Function);
+  assert_eq!(r.definition.unwrap().range(), 0..5);
+  assert_eq!(script.reference_at(at("->", 0)), None);
+
+  // Names in scope
+  let names = script.names_at(at("scale", 0));
+  assert_eq!(names[0], ("x".to_string(), ReferenceKind::Variable));
+  assert_eq!(names[1], ("n".to_string(), ReferenceKind::Variable));
+  assert_eq!(names[2], ("helper".to_string(), ReferenceKind::Function));
+  assert!(names.contains(&("scale".to_string(), ReferenceKind::Global)));
+  let names = script.names_at(at("main(y)", 0));
+  assert_eq!(names[0], ("y".to_string(), ReferenceKind::Variable));
+  assert_eq!(names[1].1, ReferenceKind::Function);
+}
+
+// Tests assert_eq passes on equal values and locates the first difference between unequal ones
+#[test]
+fn test_assert_eq() {
+  use crate::*;
+  let script = Script::<BlankCustom>::from_text(
+    "#test_pass assert_eq(list((1, \"a\"), (2, \"b\")), list((1, \"a\"), (2, \"b\")));
+#test_fail assert_eq(list((1, \"a\"), (2, \"c\")), list((1, \"a\"), (2, \"b\")));
+#test_length assert_eq(list(1), list(1, 2));",
+  )
+  .unwrap();
+  let run = |name: &str| script.function(name).unwrap().call();
+
+  assert!(matches!(run("test_pass"), Ok(ReturnVal::Bool(true))));
+  let e = run("test_fail").unwrap_err();
+  assert_eq!(e.kind(), ErrorKind::AssertionFailed);
+  assert_eq!(
+    e.message(),
+    "Assertion failed at `[1].1`, expected \"b\" but found \"c\"."
+  );
+  // Located at the actual value
+  assert_eq!(e.span().unwrap().start_line, 2);
+  assert_eq!(e.span().unwrap().start_col, 22);
+  assert_eq!(
+    run("test_length").unwrap_err().message(),
+    "Assertion failed, expected list(1, 2) but found list(1)."
+  );
+}
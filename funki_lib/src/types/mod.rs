@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// The type of a value in a script, as found by type inference, see `Script::signatures`.
+/// Also used to declare the types of custom builtins, see `Language::declare_signature`.
+///
+/// A tuple of one item is the same as the item, as it is when a script runs, so functions called
+/// with one argument take that argument's type.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Type {
+  /// Integers
+  Int,
+  /// `true` and `false`
+  Bool,
+  /// Strings
+  String,
+  /// A tuple with a type for each item, the empty tuple is the argument of a function called with
+  /// no arguments
+  Tuple(Vec<Type>),
+  /// A list where every item has the same type
+  List(Box<Type>),
+  /// A function, lambda or builtin from its argument type to its result type
+  /// Functions taking more than one argument take a tuple
+  Function(Box<Type>, Box<Type>),
+  /// A value of the host's custom type
+  /// Operators on custom values are defined by the host, so any operator can be used with them
+  Custom,
+  /// A type variable, which can be any type
+  /// The same number stands for the same type everywhere in a signature
+  Var(usize),
+}
+
+impl Type {
+  /// A tuple of the given types, or the type itself if there is only one
+  pub fn tuple(items: Vec<Type>) -> Type {
+    if items.len() == 1 {
+      items.into_iter().next().unwrap()
+    } else {
+      Type::Tuple(items)
+    }
+  }
+
+  /// A list of the given type
+  pub fn list(item: Type) -> Type {
+    Type::List(Box::new(item))
+  }
+
+  /// A function from one type to another
+  ///
+  /// ## Example
+  /// ```
+  /// use funki_lang::types::Type;
+  /// let t = Type::function(Type::tuple(vec![Type::Int, Type::Var(0)]), Type::list(Type::Var(0)));
+  /// assert_eq!(t.to_string(), "(Int, a) -> [a]");
+  /// ```
+  pub fn function(arg: Type, result: Type) -> Type {
+    Type::Function(Box::new(arg), Box::new(result))
+  }
+
+  // Renumbers the type variables in a set of types from zero in the order they appear, so they
+  //  display as `a`, `b`, ...
+  pub(crate) fn normalise(types: &[Type]) -> Vec<Type> {
+    let mut names = HashMap::new();
+    types.iter().map(|t| t.rename(&mut names)).collect()
+  }
+
+  // Renames the type variables in this type, giving new variables the next free number
+  fn rename(&self, names: &mut HashMap<usize, usize>) -> Type {
+    match self {
+      Type::Var(v) => {
+        let n = names.len();
+        Type::Var(*names.entry(*v).or_insert(n))
+      }
+      Type::Tuple(v) => Type::Tuple(v.iter().map(|t| t.rename(names)).collect()),
+      Type::List(t) => Type::list(t.rename(names)),
+      Type::Function(a, r) => Type::function(a.rename(names), r.rename(names)),
+      t => t.clone(),
+    }
+  }
+}
+
+impl Display for Type {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Type::Int => write!(fmt, "Int"),
+      Type::Bool => write!(fmt, "Bool"),
+      Type::String => write!(fmt, "String"),
+      Type::Custom => write!(fmt, "Custom"),
+      Type::Tuple(v) => write!(
+        fmt,
+        "({})",
+        v.iter()
+          .map(|t| t.to_string())
+          .collect::<Vec<_>>()
+          .join(", ")
+      ),
+      Type::List(t) => write!(fmt, "[{}]", t),
+      Type::Function(a, r) => match **a {
+        Type::Function(..) => write!(fmt, "({}) -> {}", a, r),
+        _ => write!(fmt, "{} -> {}", a, r),
+      },
+      Type::Var(v) if *v < 26 => write!(fmt, "{}", (b'a' + *v as u8) as char),
+      Type::Var(v) => write!(fmt, "t{}", v),
+    }
+  }
+}